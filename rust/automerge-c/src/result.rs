@@ -391,9 +391,11 @@ impl From<Result<Vec<am::ChangeHash>, am::InvalidChangeHashSlice>> for AMresult
 impl From<Result<Vec<(am::Value<'static>, am::ObjId)>, am::AutomergeError>> for AMresult {
     fn from(maybe: Result<Vec<(am::Value<'static>, am::ObjId)>, am::AutomergeError>) -> Self {
         match maybe {
+            // `AM*GetAll()` have always returned the winning value last
             Ok(pairs) => Self::items(
                 pairs
                     .into_iter()
+                    .rev()
                     .map(|(v, o)| AMitem::exact(o, v.into()))
                     .collect(),
             ),
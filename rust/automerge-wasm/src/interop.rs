@@ -288,15 +288,15 @@ impl TryFrom<JS> for am::sync::State {
             .0
             .as_bool()
             .ok_or(error::BadSyncState::InFlightNotBoolean)?;
-        Ok(am::sync::State {
-            shared_heads,
-            last_sent_heads,
-            their_heads,
-            their_need,
-            their_have,
-            sent_hashes,
-            in_flight,
-        })
+        let mut state = am::sync::State::new();
+        state.shared_heads = shared_heads;
+        state.last_sent_heads = last_sent_heads;
+        state.their_heads = their_heads;
+        state.their_need = their_need;
+        state.their_have = their_have;
+        state.sent_hashes = sent_hashes;
+        state.in_flight = in_flight;
+        Ok(state)
     }
 }
 
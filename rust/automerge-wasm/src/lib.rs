@@ -181,8 +181,10 @@ impl Automerge {
     }
 
     pub fn merge(&mut self, other: &mut Automerge) -> Result<Array, error::Merge> {
-        let heads = self.doc.merge(&mut other.doc)?;
-        let heads: Array = heads
+        self.doc.merge(&mut other.doc)?;
+        let heads: Array = self
+            .doc
+            .get_heads()
             .iter()
             .map(|h| JsValue::from_str(&hex::encode(h.0)))
             .collect();
@@ -526,7 +528,8 @@ impl Automerge {
             } else {
                 self.doc.get_all(&obj, prop)
             }?;
-            for (value, id) in values {
+            // getAll has always returned the winning value last
+            for (value, id) in values.into_iter().rev() {
                 let sub = Array::new();
                 let (datatype, js_value) = alloc(&value, self.text_rep);
                 sub.push(&datatype.into());
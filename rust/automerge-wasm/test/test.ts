@@ -233,7 +233,7 @@ describe('Automerge', () => {
       assert.deepEqual(doc.getWithType(text, 12), ["str", "?"])
     })
 
-    it.skip('should NOT be able to insert objects into text', () => {
+    it('should NOT be able to insert objects into text', () => {
       const doc = create(true)
       const text = doc.putObject("/", "text", "Hello world");
       assert.throws(() => {
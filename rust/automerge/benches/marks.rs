@@ -0,0 +1,68 @@
+use automerge::{
+    marks::{ExpandMark, Mark},
+    transaction::Transactable,
+    Automerge, Change, ObjId, ObjType, ReadDoc, ROOT,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// A document with a text object containing `len` characters and a mark every 100 characters
+fn marked_text(len: usize) -> (Automerge, ObjId) {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let text = tx.put_object(ROOT, "text", ObjType::Text).unwrap();
+    tx.splice_text(&text, 0, 0, &"a".repeat(len)).unwrap();
+    for start in (0..len).step_by(100) {
+        let mark = Mark::new("bold".to_string(), true, start, start + 50);
+        tx.mark(&text, mark, ExpandMark::After).unwrap();
+    }
+    tx.commit();
+    (doc, text)
+}
+
+// `n` changes by another actor each inserting a single character into the text
+fn remote_edits(doc: &Automerge, text: &ObjId, n: usize) -> Vec<Change> {
+    let mut remote = doc.fork();
+    let heads = remote.get_heads();
+    for i in 0..n {
+        let mut tx = remote.transaction();
+        tx.splice_text(text, (i * 7) % tx.length(text), 0, "b")
+            .unwrap();
+        tx.commit();
+    }
+    remote
+        .get_changes(&heads)
+        .unwrap()
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("marks");
+    group.sample_size(10);
+    for size in [1_000, 10_000] {
+        let (doc, text) = marked_text(10_000);
+        let changes = remote_edits(&doc, &text, size);
+        group.throughput(criterion::Throughput::Elements(size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("apply single character edits and read marks", size),
+            &changes,
+            |b, changes| {
+                b.iter_batched(
+                    || (doc.clone(), changes.clone()),
+                    |(mut doc, changes)| {
+                        for change in changes {
+                            doc.apply_changes(vec![change]).unwrap();
+                            doc.marks(&text).unwrap();
+                        }
+                    },
+                    criterion::BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
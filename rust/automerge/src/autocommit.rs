@@ -1,17 +1,19 @@
+use std::collections::BTreeMap;
 use std::ops::RangeBounds;
 
 use crate::exid::ExId;
 use crate::marks::{ExpandMark, Mark};
-use crate::op_observer::{BranchableObserver, OpObserver};
+use crate::op_observer::{BranchableObserver, OpObserver, Patch};
 use crate::sync::SyncDoc;
-use crate::transaction::{CommitOptions, Transactable};
+use crate::transaction::{CommitOptions, SpliceResult, Transactable, Transaction};
 use crate::{
     sync, Keys, KeysAt, ListRange, ListRangeAt, MapRange, MapRangeAt, ObjType, Parents, ReadDoc,
-    ScalarValue,
+    ScalarValue, TextCell,
 };
 use crate::{
     transaction::{Observation, Observed, TransactionInner, UnObserved},
-    ActorId, Automerge, AutomergeError, Change, ChangeHash, Prop, TextEncoding, Value, Values,
+    ActorId, Automerge, AutomergeError, Change, ChangeHash, ChangeMeta, Prop, TextEncoding, Value,
+    ValueType, Values,
 };
 
 /// An automerge document that automatically manages transactions.
@@ -164,6 +166,17 @@ impl<Obs: Observation> AutoCommitWithObs<Obs> {
         self
     }
 
+    /// Set the function which provides the timestamp of changes, see [`Automerge::set_clock`]
+    pub fn set_clock(&mut self, clock: Box<dyn Fn() -> i64 + Send + Sync>) {
+        self.doc.set_clock(clock)
+    }
+
+    /// Share the storage of equal string values, see [`Automerge::with_interning`]
+    pub fn with_interning(mut self, enabled: bool) -> Self {
+        self.doc.set_interning(enabled);
+        self
+    }
+
     fn ensure_transaction_open(&mut self) {
         if self.transaction.is_none() {
             let args = self.doc.transaction_args();
@@ -208,11 +221,33 @@ impl<Obs: Observation> AutoCommitWithObs<Obs> {
         }
     }
 
-    /// Takes all the changes in `other` which are not in `self` and applies them
+    /// Apply a batch of changes in any order, see [`Automerge::apply_changes_unordered`].
+    pub fn apply_changes_unordered(&mut self, changes: Vec<Change>) -> Result<(), AutomergeError> {
+        self.ensure_transaction_closed();
+        if let Some(observer) = self.observation.observer() {
+            self.doc
+                .apply_changes_unordered_with(changes, Some(observer))
+        } else {
+            self.doc.apply_changes_unordered(changes)
+        }
+    }
+
+    /// Apply a single change, see [`Automerge::apply_change`].
+    pub fn apply_change(&mut self, change: Change) -> Result<(), AutomergeError> {
+        self.ensure_transaction_closed();
+        if let Some(observer) = self.observation.observer() {
+            self.doc.apply_change_with(change, Some(observer))
+        } else {
+            self.doc.apply_change(change)
+        }
+    }
+
+    /// Takes all the changes in `other` which are not in `self` and applies them, returning the
+    /// number of changes applied. See [`Automerge::merge`].
     pub fn merge<Obs2: Observation>(
         &mut self,
         other: &mut AutoCommitWithObs<Obs2>,
-    ) -> Result<Vec<ChangeHash>, AutomergeError> {
+    ) -> Result<usize, AutomergeError> {
         self.ensure_transaction_closed();
         other.ensure_transaction_closed();
         if let Some(observer) = self.observation.observer() {
@@ -228,6 +263,19 @@ impl<Obs: Observation> AutoCommitWithObs<Obs> {
         self.doc.save()
     }
 
+    /// A hash of the current value of the document, see [`Automerge::state_hash`]
+    pub fn state_hash(&self) -> [u8; 32] {
+        self.doc.state_hash()
+    }
+
+    /// Save this document so that documents with the same heads produce the same bytes
+    ///
+    /// See [`Automerge::save_canonical`]
+    pub fn save_canonical(&mut self) -> Vec<u8> {
+        self.ensure_transaction_closed();
+        self.doc.save_canonical()
+    }
+
     /// Save this document, but don't run it through DEFLATE afterwards
     pub fn save_and_verify(&mut self) -> Result<Vec<u8>, AutomergeError> {
         self.ensure_transaction_closed();
@@ -270,11 +318,92 @@ impl<Obs: Observation> AutoCommitWithObs<Obs> {
         self.doc.get_changes(have_deps)
     }
 
+    /// Get the changes a peer whose heads are `their_heads` needs to catch up with this
+    /// document, see [`Automerge::changes_needed_by`]
+    pub fn changes_needed_by(&mut self, their_heads: &[ChangeHash]) -> Vec<&Change> {
+        self.ensure_transaction_closed();
+        self.doc.changes_needed_by(their_heads)
+    }
+
+    /// Get the hashes of the changes which modified `obj` or anything inside it, see
+    /// [`Automerge::changes_touching`]
+    pub fn changes_touching(&mut self, obj: &ExId) -> Vec<ChangeHash> {
+        self.ensure_transaction_closed();
+        self.doc.changes_touching(obj)
+    }
+
+    /// Get patches which build the current state of this document from an empty document, see
+    /// [`Automerge::replay_patches`]
+    pub fn replay_patches(&mut self) -> Vec<Patch<char>> {
+        self.ensure_transaction_closed();
+        self.doc.replay_patches()
+    }
+
+    /// Get the changes whose metadata `pred` returns true for, see [`Automerge::changes_where`]
+    pub fn changes_where<F: Fn(&ChangeMeta<'_>) -> bool>(&mut self, pred: F) -> Vec<&Change> {
+        self.ensure_transaction_closed();
+        self.doc.changes_where(pred)
+    }
+
+    /// Get the patches to `obj` and its descendants between `before` and `after`, see
+    /// [`Automerge::diff_subtree`]
+    pub fn diff_subtree(
+        &mut self,
+        obj: &ExId,
+        before: &[ChangeHash],
+        after: &[ChangeHash],
+    ) -> Result<Vec<Patch<char>>, AutomergeError> {
+        self.ensure_transaction_closed();
+        self.doc.diff_subtree(obj, before, after)
+    }
+
     pub fn get_change_by_hash(&mut self, hash: &ChangeHash) -> Option<&Change> {
         self.ensure_transaction_closed();
         self.doc.get_change_by_hash(hash)
     }
 
+    /// Get the changes made by `actor`, in order of their sequence number.
+    pub fn changes_by(&mut self, actor: &ActorId) -> impl Iterator<Item = &Change> {
+        self.ensure_transaction_closed();
+        self.doc.changes_by(actor)
+    }
+
+    /// Get the heads of the document just before the change with `hash` was made, see
+    /// [`Automerge::change_frontier`].
+    pub fn change_frontier(&mut self, hash: &ChangeHash) -> Option<Vec<ChangeHash>> {
+        self.ensure_transaction_closed();
+        self.doc.change_frontier(hash)
+    }
+
+    /// The number of changes which have been applied to this document, see
+    /// [`Automerge::len_changes`].
+    pub fn len_changes(&mut self) -> usize {
+        self.ensure_transaction_closed();
+        self.doc.len_changes()
+    }
+
+    /// The number of operations in this document, see [`Automerge::len_ops`].
+    pub fn len_ops(&mut self) -> usize {
+        self.ensure_transaction_closed();
+        self.doc.len_ops()
+    }
+
+    /// Replace the history of this document with a single change, see [`Automerge::flatten`].
+    pub fn flatten(&mut self) -> Result<(), AutomergeError> {
+        self.ensure_transaction_closed();
+        self.doc.flatten()
+    }
+
+    /// Get a page of the history of this document, see [`Automerge::changes_page`].
+    pub fn changes_page(
+        &mut self,
+        after: Option<ChangeHash>,
+        limit: usize,
+    ) -> Result<Vec<&Change>, AutomergeError> {
+        self.ensure_transaction_closed();
+        self.doc.changes_page(after, limit)
+    }
+
     /// Get changes in `other` that are not in `self
     pub fn get_changes_added<'a>(&mut self, other: &'a mut Self) -> Vec<&'a Change> {
         self.ensure_transaction_closed();
@@ -298,6 +427,15 @@ impl<Obs: Observation> AutoCommitWithObs<Obs> {
         self.doc.dump()
     }
 
+    /// Iterate over every op in the document, see [`Automerge::debug_ops`].
+    ///
+    /// This closes the transaction first, if one is in progress.
+    #[cfg(feature = "debug-internals")]
+    pub fn debug_ops(&mut self) -> impl Iterator<Item = crate::DebugOp> + '_ {
+        self.ensure_transaction_closed();
+        self.doc.debug_ops()
+    }
+
     /// Return a graphviz representation of the opset.
     ///
     /// # Arguments
@@ -317,6 +455,23 @@ impl<Obs: Observation> AutoCommitWithObs<Obs> {
         self.doc.get_heads()
     }
 
+    /// Get the current heads of the document without copying them, see [`Automerge::heads`].
+    ///
+    /// This closes the transaction first, if one is in progress.
+    pub fn heads(&mut self) -> &[ChangeHash] {
+        self.ensure_transaction_closed();
+        self.doc.heads()
+    }
+
+    /// Whether the heads of the document are different to `prev`, see
+    /// [`Automerge::heads_changed_since`].
+    ///
+    /// This closes the transaction first, if one is in progress.
+    pub fn heads_changed_since(&mut self, prev: &[ChangeHash]) -> bool {
+        self.ensure_transaction_closed();
+        self.doc.heads_changed_since(prev)
+    }
+
     /// Commit any uncommitted changes
     ///
     /// Returns `None` if there were no operations to commit
@@ -357,6 +512,31 @@ impl<Obs: Observation> AutoCommitWithObs<Obs> {
             .unwrap_or(0)
     }
 
+    /// Run `f` in a new transaction, committing it if `f` returns `Ok` and rolling it back if it
+    /// returns `Err`
+    ///
+    /// Any changes made before this is called are committed first, so on error the document is
+    /// left exactly as it was when `transact` was called.
+    pub fn transact<F, O, E>(&mut self, f: F) -> Result<O, E>
+    where
+        F: FnOnce(&mut Transaction<'_, Obs>) -> Result<O, E>,
+    {
+        self.ensure_transaction_closed();
+        let args = self.doc.transaction_args();
+        let mut tx = Transaction::new(&mut self.doc, args, self.observation.branch());
+        match f(&mut tx) {
+            Ok(result) => {
+                let (current, _) = tx.commit_observed();
+                self.observation.merge(&current);
+                Ok(result)
+            }
+            Err(error) => {
+                tx.rollback();
+                Err(error)
+            }
+        }
+    }
+
     /// Generate an empty change
     ///
     /// The main reason to do this is if you wish to create a "merge commit" which has all the
@@ -433,6 +613,21 @@ impl<Obs: Observation> ReadDoc for AutoCommitWithObs<Obs> {
         self.doc.list_range_at(obj, range, heads)
     }
 
+    fn entries_typed<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<Vec<(String, ValueType)>, AutomergeError> {
+        self.doc.entries_typed(obj)
+    }
+
+    fn entries_typed_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Vec<(String, ValueType)>, AutomergeError> {
+        self.doc.entries_typed_at(obj, heads)
+    }
+
     fn values<O: AsRef<ExId>>(&self, obj: O) -> Values<'_> {
         self.doc.values(obj)
     }
@@ -445,6 +640,14 @@ impl<Obs: Observation> ReadDoc for AutoCommitWithObs<Obs> {
         self.doc.length(obj)
     }
 
+    fn text_length_utf16<O: AsRef<ExId>>(&self, obj: O) -> Result<usize, AutomergeError> {
+        self.doc.text_length_utf16(obj)
+    }
+
+    fn text_length_chars<O: AsRef<ExId>>(&self, obj: O) -> Result<usize, AutomergeError> {
+        self.doc.text_length_chars(obj)
+    }
+
     fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize {
         self.doc.length_at(obj, heads)
     }
@@ -453,10 +656,36 @@ impl<Obs: Observation> ReadDoc for AutoCommitWithObs<Obs> {
         self.doc.object_type(obj)
     }
 
+    fn objects_of_type(&self, obj_type: ObjType) -> Vec<ExId> {
+        self.doc.objects_of_type(obj_type)
+    }
+
+    fn index_of<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        elem: &ExId,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Option<usize>, AutomergeError> {
+        self.doc.index_of(obj, elem, heads)
+    }
+
     fn marks<O: AsRef<ExId>>(&self, obj: O) -> Result<Vec<Mark<'_>>, AutomergeError> {
         self.doc.marks(obj)
     }
 
+    fn has_mark<O: AsRef<ExId>>(&self, obj: O, name: &str) -> Result<bool, AutomergeError> {
+        self.doc.has_mark(obj, name)
+    }
+
+    fn marks_in_range<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<Mark<'static>>, AutomergeError> {
+        self.doc.marks_in_range(obj, start, end)
+    }
+
     fn marks_at<O: AsRef<ExId>>(
         &self,
         obj: O,
@@ -465,10 +694,44 @@ impl<Obs: Observation> ReadDoc for AutoCommitWithObs<Obs> {
         self.doc.marks_at(obj, heads)
     }
 
+    fn marks_after_splice<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        pos: usize,
+        del: usize,
+        insert_len: usize,
+    ) -> Result<Vec<Mark<'static>>, AutomergeError> {
+        self.doc.marks_after_splice(obj, pos, del, insert_len)
+    }
+
+    fn marks_at_index<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        index: usize,
+    ) -> Result<BTreeMap<String, ScalarValue>, AutomergeError> {
+        self.doc.marks_at_index(obj, index)
+    }
+
     fn text<O: AsRef<ExId>>(&self, obj: O) -> Result<String, AutomergeError> {
         self.doc.text(obj)
     }
 
+    fn text_with_tombstones<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<Vec<TextCell>, AutomergeError> {
+        self.doc.text_with_tombstones(obj)
+    }
+
+    fn text_slice<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        start: usize,
+        end: usize,
+    ) -> Result<String, AutomergeError> {
+        self.doc.text_slice(obj, start, end)
+    }
+
     fn text_at<O: AsRef<ExId>>(
         &self,
         obj: O,
@@ -511,6 +774,14 @@ impl<Obs: Observation> ReadDoc for AutoCommitWithObs<Obs> {
         self.doc.get_all_at(obj, prop, heads)
     }
 
+    fn counter_components<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(ActorId, i64)>, AutomergeError> {
+        self.doc.counter_components(obj, prop)
+    }
+
     fn get_missing_deps(&self, heads: &[ChangeHash]) -> Vec<ChangeHash> {
         self.doc.get_missing_deps(heads)
     }
@@ -584,6 +855,37 @@ impl<Obs: Observation> Transactable for AutoCommitWithObs<Obs> {
         )
     }
 
+    fn insert_many<O: AsRef<ExId>, V: Into<ScalarValue>, I: IntoIterator<Item = V>>(
+        &mut self,
+        obj: O,
+        index: usize,
+        vals: I,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        self.ensure_transaction_open();
+        let (current, tx) = self.transaction.as_mut().unwrap();
+        tx.insert_many(&mut self.doc, current.observer(), obj.as_ref(), index, vals)
+    }
+
+    fn push<O: AsRef<ExId>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        value: V,
+    ) -> Result<ExId, AutomergeError> {
+        self.ensure_transaction_open();
+        let (current, tx) = self.transaction.as_mut().unwrap();
+        tx.push(&mut self.doc, current.observer(), obj.as_ref(), value)
+    }
+
+    fn push_object<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        value: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        self.ensure_transaction_open();
+        let (current, tx) = self.transaction.as_mut().unwrap();
+        tx.push_object(&mut self.doc, current.observer(), obj.as_ref(), value)
+    }
+
     fn increment<O: AsRef<ExId>, P: Into<Prop>>(
         &mut self,
         obj: O,
@@ -600,20 +902,41 @@ impl<Obs: Observation> Transactable for AutoCommitWithObs<Obs> {
         obj: O,
         prop: P,
     ) -> Result<(), AutomergeError> {
+        self.ensure_transaction_open();
+        let (current, tx) = self.transaction.as_mut().unwrap();
+        tx.delete(&mut self.doc, current.observer(), obj.as_ref(), prop)
+            .map(|_| ())
+    }
+
+    fn delete_checked<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+    ) -> Result<bool, AutomergeError> {
         self.ensure_transaction_open();
         let (current, tx) = self.transaction.as_mut().unwrap();
         tx.delete(&mut self.doc, current.observer(), obj.as_ref(), prop)
     }
 
-    /// Splice new elements into the given sequence. Returns a vector of the OpIds used to insert
-    /// the new elements
+    fn delete_elem<O: AsRef<ExId>>(&mut self, obj: O, elem: &ExId) -> Result<(), AutomergeError> {
+        self.ensure_transaction_open();
+        let (current, tx) = self.transaction.as_mut().unwrap();
+        tx.delete_elem(&mut self.doc, current.observer(), obj.as_ref(), elem)
+    }
+
+    fn clear<O: AsRef<ExId>>(&mut self, obj: O) -> Result<(), AutomergeError> {
+        self.ensure_transaction_open();
+        let (current, tx) = self.transaction.as_mut().unwrap();
+        tx.clear(&mut self.doc, current.observer(), obj.as_ref())
+    }
+
     fn splice<O: AsRef<ExId>, V: IntoIterator<Item = ScalarValue>>(
         &mut self,
         obj: O,
         pos: usize,
         del: usize,
         vals: V,
-    ) -> Result<(), AutomergeError> {
+    ) -> Result<SpliceResult, AutomergeError> {
         self.ensure_transaction_open();
         let (current, tx) = self.transaction.as_mut().unwrap();
         tx.splice(
@@ -632,7 +955,7 @@ impl<Obs: Observation> Transactable for AutoCommitWithObs<Obs> {
         pos: usize,
         del: usize,
         text: &str,
-    ) -> Result<(), AutomergeError> {
+    ) -> Result<SpliceResult, AutomergeError> {
         self.ensure_transaction_open();
         let (current, tx) = self.transaction.as_mut().unwrap();
         tx.splice_text(
@@ -645,6 +968,27 @@ impl<Obs: Observation> Transactable for AutoCommitWithObs<Obs> {
         )
     }
 
+    fn splice_text_with_expand<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: usize,
+        text: &str,
+        expand_override: Option<ExpandMark>,
+    ) -> Result<SpliceResult, AutomergeError> {
+        self.ensure_transaction_open();
+        let (current, tx) = self.transaction.as_mut().unwrap();
+        tx.splice_text_with_expand(
+            &mut self.doc,
+            current.observer(),
+            obj.as_ref(),
+            pos,
+            del,
+            text,
+            expand_override,
+        )
+    }
+
     fn mark<O: AsRef<ExId>>(
         &mut self,
         obj: O,
@@ -1,8 +1,9 @@
 use std::cmp::Ordering;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
 use std::fmt::Debug;
 use std::num::NonZeroU64;
 use std::ops::RangeBounds;
+use std::sync::Arc;
 
 use itertools::Itertools;
 
@@ -10,21 +11,27 @@ use crate::change_graph::ChangeGraph;
 use crate::columnar::Key as EncodedKey;
 use crate::exid::ExId;
 use crate::keys::Keys;
-use crate::marks::{Mark, MarkStateMachine};
-use crate::op_observer::{BranchableObserver, OpObserver};
+use crate::marks::{marks_from_ops, sort_marks, Anchors, Mark, MarkStateMachine};
+use crate::op_observer::{
+    BranchableObserver, HasPatches, OpObserver, Patch, SubtreeObserver, VecOpObserver,
+};
 use crate::op_set::OpSet;
 use crate::parents::Parents;
+use crate::storage::load::{LoadProgress, LoadWarning, WithProgress};
 use crate::storage::{self, load, CompressConfig, VerificationMode};
 use crate::transaction::{
-    self, CommitOptions, Failure, Observed, Success, Transaction, TransactionArgs, UnObserved,
+    self, CommitOptions, Failure, Observed, Success, Transactable, Transaction, TransactionArgs,
+    UnObserved,
 };
 use crate::types::{
     ActorId, ChangeHash, Clock, ElemId, Export, Exportable, Key, ListEncoding, MarkData, ObjId, Op,
     OpId, OpType, ScalarValue, TextEncoding, Value,
 };
+use crate::walk::StateHasher;
 use crate::{
-    query, AutomergeError, Change, KeysAt, ListRange, ListRangeAt, MapRange, MapRangeAt, ObjType,
-    Prop, ReadDoc, Values,
+    query, AutomergeError, Change, ChangeMeta, IntegrityError, KeysAt, ListRange, ListRangeAt,
+    MapRange, MapRangeAt, MergeStrategies, ObjType, Prop, ReadDoc, TextCell, ValueType, Values,
+    ROOT,
 };
 
 mod current_state;
@@ -38,6 +45,16 @@ pub(crate) enum Actor {
     Cached(usize),
 }
 
+/// The function set with [`Automerge::set_clock`]
+#[derive(Clone)]
+struct TimestampSource(Arc<dyn Fn() -> i64 + Send + Sync>);
+
+impl Debug for TimestampSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TimestampSource")
+    }
+}
+
 /// What to do when loading a document partially succeeds
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OnPartialLoad {
@@ -88,12 +105,14 @@ pub struct Automerge {
     history: Vec<Change>,
     /// Mapping from change hash to index into the history list.
     history_index: HashMap<ChangeHash, usize>,
+    /// The depth of each change in the history, one more than the deepest of its dependencies.
+    depths: Vec<u64>,
     /// Graph of changes
     change_graph: ChangeGraph,
     /// Mapping from actor index to list of seqs seen for them.
     states: HashMap<usize, Vec<usize>>,
-    /// Current dependencies of this document (heads hashes).
-    deps: HashSet<ChangeHash>,
+    /// Current dependencies of this document (heads hashes), sorted by hash.
+    deps: Vec<ChangeHash>,
     /// Heads at the last save.
     saved: Vec<ChangeHash>,
     /// The set of operations that form this document.
@@ -103,6 +122,8 @@ pub struct Automerge {
     /// The maximum operation counter this document has seen.
     max_op: u64,
     text_encoding: TextEncoding,
+    /// Where the timestamps of local changes come from if the commit doesn't give one.
+    clock: Option<TimestampSource>,
 }
 
 impl Automerge {
@@ -112,6 +133,7 @@ impl Automerge {
             queue: vec![],
             history: vec![],
             history_index: HashMap::new(),
+            depths: vec![],
             change_graph: ChangeGraph::new(),
             states: HashMap::new(),
             ops: Default::default(),
@@ -120,9 +142,17 @@ impl Automerge {
             actor: Actor::Unused(ActorId::random()),
             max_op: 0,
             text_encoding: Default::default(),
+            clock: None,
         }
     }
 
+    /// Create a new document with an actor id generated from `seed`, see [`ActorId::from_seed`].
+    ///
+    /// Only for tests and tooling, real documents should use [`Self::new`].
+    pub fn new_with_actor_seed(seed: u64) -> Self {
+        Automerge::new().with_actor(ActorId::from_seed(seed))
+    }
+
     pub(crate) fn ops_mut(&mut self) -> &mut OpSet {
         &mut self.ops
     }
@@ -131,6 +161,10 @@ impl Automerge {
         &self.ops
     }
 
+    pub(crate) fn max_op(&self) -> u64 {
+        self.max_op
+    }
+
     /// Whether this document has any operations
     pub fn is_empty(&self) -> bool {
         self.history.is_empty() && self.queue.is_empty()
@@ -175,6 +209,40 @@ impl Automerge {
         self
     }
 
+    /// Set the function which provides the timestamp of changes made to this document
+    ///
+    /// When a transaction is committed without a time (see
+    /// [`CommitOptions::with_time`](crate::transaction::CommitOptions::with_time)) the time of the
+    /// change is whatever `clock` returns, by default it is `0`. Return `0` to leave changes
+    /// without a timestamp. The clock is kept by [`Self::fork`] and [`Self::fork_at`] but not by
+    /// [`Self::load`].
+    pub fn set_clock(&mut self, clock: Box<dyn Fn() -> i64 + Send + Sync>) {
+        self.clock = Some(TimestampSource(Arc::from(clock)));
+    }
+
+    /// The time to give a local change if the commit didn't set one
+    pub(crate) fn clock_time(&self) -> Option<i64> {
+        self.clock.as_ref().map(|clock| (clock.0)())
+    }
+
+    /// Share the storage of equal string values in this document
+    ///
+    /// Each string value is normally stored separately, with interning enabled all the values with
+    /// the same contents share one copy. This only matters for strings longer than 23 bytes,
+    /// shorter strings are stored inline and don't allocate. It doesn't change anything which is
+    /// read from or saved by the document. Interning is kept by [`Self::fork`] and
+    /// [`Self::fork_at`] but not by [`Self::load`].
+    pub fn with_interning(mut self, enabled: bool) -> Self {
+        self.set_interning(enabled);
+        self
+    }
+
+    /// Enable or disable string interning, see [`Self::with_interning`]
+    pub fn set_interning(&mut self, enabled: bool) -> &mut Self {
+        self.ops.set_interning(enabled);
+        self
+    }
+
     /// Set the actor id for this document.
     pub fn with_actor(mut self, actor: ActorId) -> Self {
         self.actor = Actor::Unused(actor);
@@ -354,6 +422,20 @@ impl Automerge {
         }
     }
 
+    /// Wrap `observer` so that it only observes changes to `obj` and the objects inside it.
+    ///
+    /// The returned observer can be used anywhere an observer is accepted, e.g.
+    /// [`Self::apply_changes_with`] or [`crate::AutoCommit::with_observer`]. See
+    /// [`SubtreeObserver`] for what happens when `obj` is deleted.
+    pub fn observe_subtree<Obs: OpObserver>(
+        &self,
+        obj: &ExId,
+        observer: Obs,
+    ) -> Result<SubtreeObserver<Obs>, AutomergeError> {
+        self.exid_to_obj(obj)?;
+        Ok(SubtreeObserver::new(obj.clone(), observer))
+    }
+
     /// Generate an empty change
     ///
     /// The main reason to do this is if you want to create a "merge commit", which is a change
@@ -395,19 +477,102 @@ impl Automerge {
         }
         let mut f = Self::new();
         f.set_actor(ActorId::random());
+        f.clock = self.clock.clone();
+        f.set_interning(self.ops.interning());
         f.apply_changes(changes.into_iter().rev().cloned())?;
         Ok(f)
     }
 
+    /// Replace the history of this document with a single change which recreates its current
+    /// state
+    ///
+    /// The new change is made by a fresh random actor and has no dependencies, so the flattened
+    /// document shares no history with the original one and can't be merged with it or any other
+    /// copies of it. As the objects are recreated, [`ExId`]s from before flattening don't refer to
+    /// them. Conflicts are resolved to the winning value. The marks of lists and text are
+    /// recreated with the [`ExpandMark`](crate::marks::ExpandMark) of the ops at their start and
+    /// end. The clock, text encoding and interning of the document are kept.
+    pub fn flatten(&mut self) -> Result<(), AutomergeError> {
+        let mut flat = Automerge::new()
+            .with_encoding(self.text_encoding)
+            .with_actor(ActorId::random());
+        flat.clock = self.clock.clone();
+        flat.set_interning(self.ops.interning());
+        let mut tx = flat.transaction();
+        self.copy_object(&ExId::Root, ObjType::Map, &mut tx, &ExId::Root)?;
+        tx.commit();
+        *self = flat;
+        Ok(())
+    }
+
+    fn copy_object<T: Transactable>(
+        &self,
+        obj: &ExId,
+        obj_type: ObjType,
+        tx: &mut T,
+        target: &ExId,
+    ) -> Result<(), AutomergeError> {
+        match obj_type {
+            ObjType::Map | ObjType::Table => {
+                for (key, value, id) in self.map_range(obj, ..) {
+                    match value {
+                        Value::Object(obj_type) => {
+                            let child = tx.put_object(target, key, obj_type)?;
+                            self.copy_object(&id, obj_type, tx, &child)?;
+                        }
+                        Value::Scalar(value) => tx.put(target, key, flat_scalar(&value))?,
+                    }
+                }
+            }
+            ObjType::List => {
+                for (index, value, id) in self.list_range(obj, ..) {
+                    match value {
+                        Value::Object(obj_type) => {
+                            let child = tx.insert_object(target, index, obj_type)?;
+                            self.copy_object(&id, obj_type, tx, &child)?;
+                        }
+                        Value::Scalar(value) => tx.insert(target, index, flat_scalar(&value))?,
+                    }
+                }
+                self.copy_marks(obj, tx, target)?;
+            }
+            ObjType::Text => {
+                tx.splice_text(target, 0, 0, &self.text(obj)?)?;
+                self.copy_marks(obj, tx, target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_marks<T: Transactable>(
+        &self,
+        obj: &ExId,
+        tx: &mut T,
+        target: &ExId,
+    ) -> Result<(), AutomergeError> {
+        let (obj, obj_type) = self.exid_to_obj(obj)?;
+        let encoding = ListEncoding::new(obj_type, self.text_encoding);
+        let query = self.ops.search(&obj, query::MarkOps::new(encoding));
+        let anchors = Anchors::new(&query.ops);
+        for mark in marks_from_ops(&query.ops, self) {
+            let expand = anchors.expand(&mark);
+            tx.mark(target, mark.into_owned(), expand)?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn exid_to_obj(&self, id: &ExId) -> Result<(ObjId, ObjType), AutomergeError> {
         match id {
             ExId::Root => Ok((ObjId::root(), ObjType::Map)),
             ExId::Id(..) => {
-                let obj = ObjId(self.exid_to_opid(id)?);
+                let obj = self
+                    .exid_to_opid(id)
+                    .map(ObjId)
+                    .map_err(|_| AutomergeError::InvalidObj(id.clone()))?;
                 if let Some(obj_type) = self.ops.object_type(&obj) {
                     Ok((obj, obj_type))
                 } else {
-                    Err(AutomergeError::NotAnObject)
+                    Err(AutomergeError::InvalidObj(id.clone()))
                 }
             }
         }
@@ -415,18 +580,17 @@ impl Automerge {
 
     pub(crate) fn exid_to_opid(&self, id: &ExId) -> Result<OpId, AutomergeError> {
         match id {
-            ExId::Root => Err(AutomergeError::Fail),
+            ExId::Root => Err(AutomergeError::RootHasNoOpId),
             ExId::Id(ctr, actor, idx) => {
                 if self.ops.m.actors.cache.get(*idx) == Some(actor) {
                     Ok(OpId::new(*ctr, *idx))
                 } else {
-                    // FIXME - make a real error
                     let idx = self
                         .ops
                         .m
                         .actors
                         .lookup(actor)
-                        .ok_or(AutomergeError::Fail)?;
+                        .ok_or_else(|| AutomergeError::UnknownActor(actor.clone()))?;
                     Ok(OpId::new(*ctr, idx))
                 }
             }
@@ -442,6 +606,29 @@ impl Automerge {
         Self::load_with::<()>(data, OnPartialLoad::Error, VerificationMode::Check, None)
     }
 
+    /// Load a document, rejecting any data this version of automerge does not understand
+    ///
+    /// [`Self::load`] skips over columns and change data added by later versions of automerge so
+    /// that they can be read by older versions. This instead fails if there are any unknown
+    /// columns or extra change data, as well as if there are unknown chunk types, any chunk has an
+    /// invalid checksum or there is trailing data after the last chunk.
+    pub fn load_strict(data: &[u8]) -> Result<Self, AutomergeError> {
+        load::check_strict(storage::parse::Input::new(data))?;
+        Self::load(data)
+    }
+
+    /// Load a document, returning anything in the data which was skipped or not understood
+    ///
+    /// This loads exactly as [`Self::load`] does, so data which can't be loaded is still an
+    /// error, but also reports the parts of the data which [`Self::load`] silently skips over,
+    /// such as columns or change data added by later versions of automerge or written by other
+    /// implementations. [`Self::load_strict`] fails on the same data these warnings describe.
+    pub fn load_with_warnings(data: &[u8]) -> Result<(Self, Vec<LoadWarning>), AutomergeError> {
+        let doc = Self::load(data)?;
+        let warnings = load::find_warnings(storage::parse::Input::new(data))?;
+        Ok((doc, warnings))
+    }
+
     /// Load a document without verifying the head hashes
     ///
     /// This is useful for debugging as it allows you to examine a corrupted document.
@@ -457,11 +644,44 @@ impl Automerge {
     /// Load a document with an observer
     #[tracing::instrument(skip(data, observer), err)]
     pub fn load_with<Obs: OpObserver>(
+        data: &[u8],
+        on_error: OnPartialLoad,
+        mode: VerificationMode,
+        observer: Option<&mut Obs>,
+    ) -> Result<Self, AutomergeError> {
+        Self::load_impl(data, on_error, mode, observer, |_| {})
+    }
+
+    /// Load a document, calling `progress` every so often to say how far it has got
+    ///
+    /// `progress` is called every 16384 ops while decoding a document chunk, once the first chunk
+    /// has been loaded, every 1000 changes after that and once more when loading is finished. The
+    /// ops in a document chunk are decoded before anything else, so a document made by
+    /// [`Self::save`] mostly reports [`LoadProgress::ops`], while the changes of an incremental
+    /// save are reported in [`LoadProgress::changes`]. Other than that this is [`Self::load`].
+    pub fn load_with_progress<F: FnMut(LoadProgress)>(
+        data: &[u8],
+        progress: F,
+    ) -> Result<Self, AutomergeError> {
+        Self::load_impl::<(), _>(
+            data,
+            OnPartialLoad::Error,
+            VerificationMode::Check,
+            None,
+            progress,
+        )
+    }
+
+    fn load_impl<Obs: OpObserver, F: FnMut(LoadProgress)>(
         data: &[u8],
         on_error: OnPartialLoad,
         mode: VerificationMode,
         mut observer: Option<&mut Obs>,
+        mut progress: F,
     ) -> Result<Self, AutomergeError> {
+        const PROGRESS_OPS: usize = 1 << 14;
+        const PROGRESS_CHANGES: usize = 1000;
+        let total_bytes = data.len();
         if data.is_empty() {
             tracing::trace!("no data, initializing empty document");
             return Ok(Self::new());
@@ -473,18 +693,30 @@ impl Automerge {
             return Err(load::Error::BadChecksum.into());
         }
 
+        let first_chunk_bytes = total_bytes - remaining.unconsumed_bytes().len();
+        let mut ops = 0;
         let mut change: Option<Change> = None;
         let mut am = match first_chunk {
             storage::Chunk::Document(d) => {
                 tracing::trace!("first chunk is document chunk, inflating");
+                let observer = WithProgress::new(OpSet::builder(), PROGRESS_OPS, |ops| {
+                    progress(LoadProgress {
+                        bytes: 0,
+                        total_bytes,
+                        ops,
+                        changes: 0,
+                    })
+                });
                 let storage::load::Reconstructed {
                     max_op,
-                    result: op_set,
+                    result: (op_set, decoded),
                     changes,
                     heads,
-                } = storage::load::reconstruct_document(&d, mode, OpSet::builder())
+                } = storage::load::reconstruct_document(&d, mode, observer)
                     .map_err(|e| load::Error::InflateDocument(Box::new(e)))?;
-                let mut hashes_by_index = HashMap::new();
+                ops = decoded;
+                let mut history_index = HashMap::with_capacity(changes.len());
+                let mut depths = Vec::with_capacity(changes.len());
                 let mut actor_to_history: HashMap<usize, Vec<usize>> = HashMap::new();
                 let mut change_graph = ChangeGraph::new();
                 for (index, change) in changes.iter().enumerate() {
@@ -492,22 +724,29 @@ impl Automerge {
                     // all the changes
                     let actor_index = op_set.m.actors.lookup(change.actor_id()).unwrap();
                     actor_to_history.entry(actor_index).or_default().push(index);
-                    hashes_by_index.insert(index, change.hash());
+                    depths.push(change_depth(&history_index, &depths, change));
+                    history_index.insert(change.hash(), index);
                     change_graph.add_change(change, actor_index)?;
                 }
-                let history_index = hashes_by_index.into_iter().map(|(k, v)| (v, k)).collect();
                 Self {
                     queue: vec![],
                     history: changes,
                     history_index,
+                    depths,
                     states: actor_to_history,
                     change_graph,
                     ops: op_set,
-                    deps: heads.into_iter().collect(),
+                    deps: {
+                        let mut deps = heads.into_iter().collect::<Vec<_>>();
+                        deps.sort_unstable();
+                        deps.dedup();
+                        deps
+                    },
                     saved: Default::default(),
                     actor: Actor::Unused(ActorId::random()),
                     max_op,
                     text_encoding: Default::default(),
+                    clock: None,
                 }
             }
             storage::Chunk::Change(stored_change) => {
@@ -530,13 +769,34 @@ impl Automerge {
                 Self::new()
             }
         };
+        progress(LoadProgress {
+            bytes: first_chunk_bytes,
+            total_bytes,
+            ops,
+            changes: am.history.len() + change.iter().len(),
+        });
         tracing::trace!("loading change chunks");
         match load::load_changes(remaining.reset()) {
             load::LoadedChanges::Complete(c) => {
-                am.apply_changes(change.into_iter().chain(c))?;
+                let mut changes = change.into_iter().chain(c).peekable();
+                while changes.peek().is_some() {
+                    am.apply_changes(changes.by_ref().take(PROGRESS_CHANGES))?;
+                    progress(LoadProgress {
+                        bytes: first_chunk_bytes,
+                        total_bytes,
+                        ops,
+                        changes: am.history.len() + am.queue.len(),
+                    });
+                }
                 if !am.queue.is_empty() {
                     return Err(AutomergeError::MissingDeps);
                 }
+                progress(LoadProgress {
+                    bytes: total_bytes,
+                    total_bytes,
+                    ops,
+                    changes: am.history.len(),
+                });
             }
             load::LoadedChanges::Partial { error, .. } => {
                 if on_error == OnPartialLoad::Error {
@@ -634,9 +894,9 @@ impl Automerge {
                 }
                 if self.is_causally_ready(&c) {
                     if empty_at_start {
-                        self.apply_change::<()>(c, &mut None);
+                        self.apply_ready_change::<()>(c, &mut None);
                     } else {
-                        self.apply_change(c, &mut op_observer);
+                        self.apply_ready_change(c, &mut op_observer);
                     }
                 } else {
                     self.queue.push(c);
@@ -646,9 +906,9 @@ impl Automerge {
         while let Some(c) = self.pop_next_causally_ready_change() {
             if !self.history_index.contains_key(&c.hash()) {
                 if empty_at_start {
-                    self.apply_change::<()>(c, &mut None);
+                    self.apply_ready_change::<()>(c, &mut None);
                 } else {
-                    self.apply_change(c, &mut op_observer);
+                    self.apply_ready_change(c, &mut op_observer);
                 }
             }
         }
@@ -660,7 +920,118 @@ impl Automerge {
         Ok(())
     }
 
-    fn apply_change<Obs: OpObserver>(&mut self, change: Change, observer: &mut Option<&mut Obs>) {
+    /// Apply a batch of changes in any order.
+    ///
+    /// [`Self::apply_changes`] queues a change whose dependencies haven't been applied yet and
+    /// keeps it queued if they never arrive. This instead sorts the batch so every change comes
+    /// after its dependencies and applies it, and if a change depends on something which is
+    /// neither in the document nor in the batch (or the dependencies form a cycle) it returns
+    /// [`AutomergeError::MissingDeps`] without applying anything.
+    pub fn apply_changes_unordered(&mut self, changes: Vec<Change>) -> Result<(), AutomergeError> {
+        self.apply_changes_unordered_with::<()>(changes, None)
+    }
+
+    /// Like [`Self::apply_changes_unordered`] but with an observer
+    pub fn apply_changes_unordered_with<Obs: OpObserver>(
+        &mut self,
+        changes: Vec<Change>,
+        op_observer: Option<&mut Obs>,
+    ) -> Result<(), AutomergeError> {
+        let sorted = self.sort_by_deps(changes)?;
+        self.apply_changes_with(sorted, op_observer)
+    }
+
+    /// Order the changes which are not already in this document so each comes after its
+    /// dependencies
+    fn sort_by_deps(&self, changes: Vec<Change>) -> Result<Vec<Change>, AutomergeError> {
+        let mut pending = changes
+            .into_iter()
+            .filter(|c| !self.history_index.contains_key(&c.hash()))
+            .map(|c| (c.hash(), c))
+            .collect::<HashMap<_, _>>();
+        let mut waiting_on: HashMap<ChangeHash, Vec<ChangeHash>> = HashMap::new();
+        let mut missing = HashMap::new();
+        let mut ready = Vec::new();
+        for (hash, change) in &pending {
+            let unapplied = change
+                .deps()
+                .iter()
+                .filter(|d| !self.history_index.contains_key(d))
+                .collect::<Vec<_>>();
+            for dep in &unapplied {
+                if !pending.contains_key(dep) {
+                    return Err(AutomergeError::MissingDeps);
+                }
+                waiting_on.entry(**dep).or_default().push(*hash);
+            }
+            if unapplied.is_empty() {
+                ready.push(*hash);
+            } else {
+                missing.insert(*hash, unapplied.len());
+            }
+        }
+
+        let mut sorted = Vec::with_capacity(pending.len());
+        while let Some(hash) = ready.pop() {
+            for dependent in waiting_on.remove(&hash).unwrap_or_default() {
+                let count = missing.get_mut(&dependent).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    missing.remove(&dependent);
+                    ready.push(dependent);
+                }
+            }
+            sorted.push(pending.remove(&hash).unwrap());
+        }
+        if !missing.is_empty() {
+            // whatever is left depends on itself
+            return Err(AutomergeError::MissingDeps);
+        }
+        Ok(sorted)
+    }
+
+    /// Apply a single change to this document.
+    ///
+    /// Unlike [`Self::apply_changes`] a change whose dependencies are not in the document is not
+    /// queued until they arrive, instead this returns [`AutomergeError::MissingDeps`]. Applying
+    /// a change which is already in the document does nothing and returns `Ok(())`.
+    pub fn apply_change(&mut self, change: Change) -> Result<(), AutomergeError> {
+        self.apply_change_with::<()>(change, None)
+    }
+
+    /// Like [`Self::apply_change`] but with an observer
+    pub fn apply_change_with<Obs: OpObserver>(
+        &mut self,
+        change: Change,
+        mut op_observer: Option<&mut Obs>,
+    ) -> Result<(), AutomergeError> {
+        if self.history_index.contains_key(&change.hash()) {
+            return Ok(());
+        }
+        if self.duplicate_seq(&change) {
+            return Err(AutomergeError::DuplicateSeqNumber(
+                change.seq(),
+                change.actor_id().clone(),
+            ));
+        }
+        if !self.is_causally_ready(&change) {
+            return Err(AutomergeError::MissingDeps);
+        }
+        self.apply_ready_change(change, &mut op_observer);
+        // changes queued by `apply_changes` may have been waiting for this one
+        while let Some(c) = self.pop_next_causally_ready_change() {
+            if !self.history_index.contains_key(&c.hash()) {
+                self.apply_ready_change(c, &mut op_observer);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_ready_change<Obs: OpObserver>(
+        &mut self,
+        change: Change,
+        observer: &mut Option<&mut Obs>,
+    ) {
         let ops = self.import_ops(&change);
         self.update_history(change, ops.len());
         if let Some(observer) = observer {
@@ -749,16 +1120,27 @@ impl Automerge {
     }
 
     /// Takes all the changes in `other` which are not in `self` and applies them
-    pub fn merge(&mut self, other: &mut Self) -> Result<Vec<ChangeHash>, AutomergeError> {
+    ///
+    /// Returns the number of changes which were applied, use [`Self::get_heads`] for the new
+    /// heads. Merging a document whose heads are all already in `self` does nothing and returns
+    /// `0` without looking at the rest of its history.
+    pub fn merge(&mut self, other: &mut Self) -> Result<usize, AutomergeError> {
         self.merge_with::<()>(other, None)
     }
 
-    /// Takes all the changes in `other` which are not in `self` and applies them
+    /// Like [`Self::merge`] but with an observer
     pub fn merge_with<Obs: OpObserver>(
         &mut self,
         other: &mut Self,
         op_observer: Option<&mut Obs>,
-    ) -> Result<Vec<ChangeHash>, AutomergeError> {
+    ) -> Result<usize, AutomergeError> {
+        if other
+            .get_heads()
+            .iter()
+            .all(|h| self.history_index.contains_key(h))
+        {
+            return Ok(0);
+        }
         // TODO: Make this fallible and figure out how to do this transactionally
         let changes = self
             .get_changes_added(other)
@@ -766,8 +1148,69 @@ impl Automerge {
             .cloned()
             .collect::<Vec<_>>();
         tracing::trace!(changes=?changes.iter().map(|c| c.hash()).collect::<Vec<_>>(), "merging new changes");
+        let before = self.history.len();
         self.apply_changes_with(changes, op_observer)?;
-        Ok(self.get_heads())
+        Ok(self.history.len() - before)
+    }
+
+    /// Like [`Self::merge`] but then settle the conflicts `strategies` has rules for
+    ///
+    /// See [`Self::apply_merge_strategies`].
+    pub fn merge_with_strategies(
+        &mut self,
+        other: &mut Self,
+        strategies: &MergeStrategies,
+    ) -> Result<usize, AutomergeError> {
+        let merged = self.merge(other)?;
+        self.apply_merge_strategies(strategies);
+        Ok(merged)
+    }
+
+    /// Settle the conflicts in this document which `strategies` has rules for
+    ///
+    /// Every conflicting map property which matches a rule and holds only numbers is given the
+    /// value the rule's [`MergeStrategy`](crate::MergeStrategy) picks, all in one new change.
+    /// Nothing is written if the value which currently wins the conflict is already the one
+    /// picked, so replicas which settle the same conflict pick the same value and once their
+    /// deciding changes are merged there is nothing left to settle. Returns the hash of the
+    /// change, or `None` if nothing was written.
+    pub fn apply_merge_strategies(&mut self, strategies: &MergeStrategies) -> Option<ChangeHash> {
+        if strategies.is_empty() {
+            return None;
+        }
+        let mut decisions = Vec::new();
+        for obj_type in strategies.obj_types() {
+            for obj in self.objects_of_type(obj_type) {
+                for key in self.keys(&obj) {
+                    let strategy = match strategies.find(obj_type, &key) {
+                        Some(strategy) => strategy,
+                        None => continue,
+                    };
+                    let values = self.get_all(&obj, key.as_str()).unwrap_or_default();
+                    let scalars = values
+                        .iter()
+                        .filter_map(|(v, _)| v.to_scalar())
+                        .collect::<Vec<_>>();
+                    if values.len() < 2 || scalars.len() < values.len() {
+                        continue;
+                    }
+                    if let Some(decided) = strategy.decide(&scalars) {
+                        if decided != scalars[0] {
+                            decisions.push((obj.clone(), key, decided.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        if decisions.is_empty() {
+            return None;
+        }
+        let mut tx = self.transaction();
+        for (obj, key, value) in decisions {
+            // the property was just read from this document so this can't fail
+            tx.put(&obj, key, value).unwrap();
+        }
+        tx.commit()
     }
 
     /// Save the entirety of this document in a compact form.
@@ -790,6 +1233,86 @@ impl Automerge {
         bytes
     }
 
+    /// A hash of the current value of the document, ignoring how it got there
+    ///
+    /// Documents which have the same value, the same keys, list elements, text and scalars
+    /// everywhere, have the same hash even if they were built from different changes. Which
+    /// operation set a value, conflicting values which lost and marks on text are not part of the
+    /// value. Counters are hashed by their current value.
+    pub fn state_hash(&self) -> [u8; 32] {
+        let mut hasher = StateHasher::new();
+        self.walk(ROOT, &mut hasher)
+            .expect("the root is always a map");
+        hasher.finish()
+    }
+
+    fn text_length_in(&self, obj: &ExId, encoding: TextEncoding) -> Result<usize, AutomergeError> {
+        let (obj, obj_type) = self.exid_to_obj(obj)?;
+        if obj_type != ObjType::Text {
+            return Err(AutomergeError::InvalidOp(obj_type));
+        }
+        let encoding = ListEncoding::Text(encoding);
+        Ok(self.ops.search(&obj, query::Len::new(encoding)).len)
+    }
+
+    /// Save the document so that documents with the same state always produce the same bytes
+    ///
+    /// Two documents have the same state when they have the same heads, and therefore contain
+    /// the same changes, however those changes were applied or merged. [`Self::save`] writes the
+    /// changes in the order this document received them so its output can differ between such
+    /// documents, this orders them by their dependencies, breaking ties by hash. The output loads
+    /// like any other saved document. Only the actors which made or are referenced by the changes
+    /// are written, so actors which were cached without making a change don't affect the output.
+    /// Unlike [`Self::save`] this doesn't affect what [`Self::save_incremental`] returns.
+    pub fn save_canonical(&self) -> Vec<u8> {
+        let heads = self.get_heads();
+        crate::storage::save::save_document_used_actors(
+            self.canonical_history().into_iter(),
+            self.ops.iter().map(|(objid, _, op)| (objid, op)),
+            &self.ops.m.actors,
+            &self.ops.m.props,
+            &heads,
+            None,
+        )
+    }
+
+    /// The changes in the history in dependency order, picking the lowest hash whenever more
+    /// than one change is ready
+    fn canonical_history(&self) -> Vec<&Change> {
+        let mut missing = self
+            .history
+            .iter()
+            .map(|c| c.deps().len())
+            .collect::<Vec<_>>();
+        let mut dependents: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut ready = BTreeSet::new();
+        for (index, change) in self.history.iter().enumerate() {
+            for dep in change.deps() {
+                dependents
+                    .entry(self.history_index[dep])
+                    .or_default()
+                    .push(index);
+            }
+            if change.deps().is_empty() {
+                ready.insert((change.hash(), index));
+            }
+        }
+
+        let mut sorted = Vec::with_capacity(self.history.len());
+        while let Some(next) = ready.iter().next().copied() {
+            ready.remove(&next);
+            let (_, index) = next;
+            for dependent in dependents.remove(&index).unwrap_or_default() {
+                missing[dependent] -= 1;
+                if missing[dependent] == 0 {
+                    ready.insert((self.history[dependent].hash(), dependent));
+                }
+            }
+            sorted.push(&self.history[index]);
+        }
+        sorted
+    }
+
     /// Save the document and attempt to load it before returning - slow!
     pub fn save_and_verify(&mut self) -> Result<Vec<u8>, AutomergeError> {
         let bytes = self.save();
@@ -853,7 +1376,7 @@ impl Automerge {
     }
 
     /// Get the changes since `have_deps` in this document using a clock internally.
-    fn get_changes_clock(&self, have_deps: &[ChangeHash]) -> Result<Vec<&Change>, AutomergeError> {
+    fn get_changes_clock(&self, have_deps: &[ChangeHash]) -> Vec<&Change> {
         // get the clock for the given deps
         let clock = self.clock_at(have_deps);
 
@@ -874,10 +1397,10 @@ impl Automerge {
         // ensure the changes are still in sorted order
         change_indexes.sort_unstable();
 
-        Ok(change_indexes
+        change_indexes
             .into_iter()
             .map(|i| &self.history[i])
-            .collect())
+            .collect()
     }
 
     /// Get the last change this actor made to the document.
@@ -889,6 +1412,50 @@ impl Automerge {
             .find(|c| c.actor_id() == self.get_actor());
     }
 
+    /// Get the changes made by `actor`, in order of their sequence number.
+    ///
+    /// If `actor` has not made any changes to this document the iterator will be empty.
+    pub fn changes_by(&self, actor: &ActorId) -> impl Iterator<Item = &Change> {
+        self.ops
+            .m
+            .actors
+            .lookup(actor)
+            .and_then(|actor_index| self.states.get(&actor_index))
+            .into_iter()
+            .flatten()
+            .map(move |&i| &self.history[i])
+    }
+
+    /// Get the heads of the document just before the change with `hash` was made.
+    ///
+    /// These are the dependencies of the change with any which are ancestors of the others
+    /// removed, so forking at them reproduces the document the author of the change saw. Returns
+    /// `None` if the change is not in this document.
+    pub fn change_frontier(&self, hash: &ChangeHash) -> Option<Vec<ChangeHash>> {
+        let change = self.get_change_by_hash(hash)?;
+        let mut frontier = change.deps().iter().copied().collect::<BTreeSet<_>>();
+        for dep in change.deps() {
+            if let Some(dep) = self.get_change_by_hash(dep) {
+                self.change_graph
+                    .remove_ancestors(&mut frontier, dep.deps());
+            }
+        }
+        Some(frontier.into_iter().collect())
+    }
+
+    /// The number of changes which have been applied to this document.
+    ///
+    /// Changes which are waiting for their dependencies are not counted.
+    pub fn len_changes(&self) -> usize {
+        self.history.len()
+    }
+
+    /// The number of operations in this document, including those which have since been
+    /// overwritten or deleted and those in an open transaction.
+    pub fn len_ops(&self) -> usize {
+        self.ops.len()
+    }
+
     fn clock_at(&self, heads: &[ChangeHash]) -> Clock {
         self.change_graph.clock_for_heads(heads)
     }
@@ -916,6 +1483,8 @@ impl Automerge {
             .push(history_index);
 
         self.history_index.insert(change.hash(), history_index);
+        self.depths
+            .push(change_depth(&self.history_index, &self.depths, &change));
         self.change_graph
             .add_change(&change, actor_index)
             .expect("Change's deps should already be in the document");
@@ -926,10 +1495,11 @@ impl Automerge {
     }
 
     fn update_deps(&mut self, change: &Change) {
-        for d in change.deps() {
-            self.deps.remove(d);
+        self.deps.retain(|d| !change.deps().contains(d));
+        let hash = change.hash();
+        if let Err(index) = self.deps.binary_search(&hash) {
+            self.deps.insert(index, hash);
         }
-        self.deps.insert(change.hash());
     }
 
     #[doc(hidden)]
@@ -1017,6 +1587,22 @@ impl Automerge {
         }
     }
 
+    /// Iterate over every op in the document, including ops which have been overwritten or
+    /// deleted
+    ///
+    /// Deletes themselves are not stored as ops, they only appear in the `succ` of the op they
+    /// deleted.
+    ///
+    /// Objects come in the order they were created and the ops of each object in the order they
+    /// are stored. This is for debuggers and inspectors, the shape of [`crate::DebugOp`] follows
+    /// the internals of the document and may change in any release.
+    #[cfg(feature = "debug-internals")]
+    pub fn debug_ops(&self) -> impl Iterator<Item = crate::DebugOp> + '_ {
+        self.ops
+            .iter()
+            .map(move |(obj, _, op)| crate::DebugOp::new(&self.ops, obj, op))
+    }
+
     /// Return a graphviz representation of the opset.
     ///
     /// # Arguments
@@ -1034,6 +1620,57 @@ impl Automerge {
         self.ops.visualise(objects)
     }
 
+    /// Check that the internal state of this document is consistent.
+    ///
+    /// This checks that every object was created by an op in its parent, that the `pred` and
+    /// `succ` of every op refer to each other, and that every change in the document is in the
+    /// history of the heads of the document. It is intended as a debugging aid and should never
+    /// fail for a document which was built using the public API.
+    ///
+    /// The checks are performed in an order which only depends on the contents of the document,
+    /// so two replicas with the same changes will report the same violation.
+    pub fn verify_integrity(&self) -> Result<(), IntegrityError> {
+        self.ops.verify_integrity()?;
+
+        let heads = self.get_heads();
+        for head in &heads {
+            if !self.history_index.contains_key(head) {
+                return Err(IntegrityError::MissingHead(*head));
+            }
+        }
+
+        let hashes = self
+            .history
+            .iter()
+            .map(|c| c.hash())
+            .sorted()
+            .collect::<Vec<_>>();
+        for hash in &hashes {
+            for dep in self.history[self.history_index[hash]].deps() {
+                if !self.history_index.contains_key(dep) {
+                    return Err(IntegrityError::MissingDep {
+                        change: *hash,
+                        dep: *dep,
+                    });
+                }
+            }
+        }
+
+        let mut reachable = HashSet::new();
+        let mut to_visit = heads;
+        while let Some(hash) = to_visit.pop() {
+            if reachable.insert(hash) {
+                let change = &self.history[self.history_index[&hash]];
+                to_visit.extend(change.deps().iter().copied());
+            }
+        }
+        if let Some(hash) = hashes.into_iter().find(|h| !reachable.contains(h)) {
+            return Err(IntegrityError::UnreachableChange(hash));
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn insert_op(&mut self, obj: &ObjId, op: Op) -> Op {
         let q = self.ops.search(obj, query::SeekOp::new(&op));
 
@@ -1142,14 +1779,209 @@ impl Automerge {
     }
 
     /// Get the heads of this document.
+    ///
+    /// The heads are sorted by hash, so two documents with the same heads always return them in
+    /// the same order and the vectors can be compared directly.
     pub fn get_heads(&self) -> Vec<ChangeHash> {
-        let mut deps: Vec<_> = self.deps.iter().copied().collect();
-        deps.sort_unstable();
-        deps
+        self.deps.clone()
+    }
+
+    /// Get the heads of this document without copying them, in the same order as
+    /// [`Self::get_heads`]
+    ///
+    /// The slice borrows the document so it can't be held across a change to it, use
+    /// [`Self::get_heads`] to keep the heads around.
+    pub fn heads(&self) -> &[ChangeHash] {
+        &self.deps
+    }
+
+    /// Whether the heads of this document are different to `prev`
+    ///
+    /// This is the same as `self.get_heads() != prev` for a `prev` which came from
+    /// [`Self::get_heads`] but doesn't build the heads to compare them. The order of `prev`
+    /// doesn't matter.
+    pub fn heads_changed_since(&self, prev: &[ChangeHash]) -> bool {
+        prev.len() != self.deps.len() || !prev.iter().all(|h| self.deps.binary_search(h).is_ok())
     }
 
     pub fn get_changes(&self, have_deps: &[ChangeHash]) -> Result<Vec<&Change>, AutomergeError> {
-        self.get_changes_clock(have_deps)
+        Ok(self.get_changes_clock(have_deps))
+    }
+
+    /// Get the changes a peer whose heads are `their_heads` needs to catch up with this document
+    ///
+    /// These are the changes which are not ancestors of any of `their_heads`, in the order they
+    /// were applied to this document so every change comes after its dependencies. Hashes in
+    /// `their_heads` which are not in this document are ignored, they are changes the peer has
+    /// which this document doesn't. As the ancestors of an unknown head can't be found this may
+    /// return changes the peer already has, which is harmless as applying a change twice has no
+    /// effect.
+    pub fn changes_needed_by(&self, their_heads: &[ChangeHash]) -> Vec<&Change> {
+        self.get_changes_clock(their_heads)
+    }
+
+    /// Get the hashes of the changes which modified `obj` or anything inside it
+    ///
+    /// A change is included if it has an op in `obj` or in an object which is or was inside
+    /// `obj`, or if it created `obj`. Objects which have since been deleted still count. The
+    /// hashes are in the order the changes were applied to this document, so each comes after
+    /// its dependencies. An `obj` which is not in this document has no changes.
+    pub fn changes_touching(&self, obj: &ExId) -> Vec<ChangeHash> {
+        let obj = match self.exid_to_obj(obj) {
+            Ok((obj, _)) => obj,
+            Err(_) => return Vec::new(),
+        };
+        // Deletes are only kept in the succ of the ops they delete, which are in the same object
+        let mut touched = BTreeSet::new();
+        let mut touch = |id: &OpId| touched.extend(self.history_index_of_op(id));
+        if !obj.is_root() {
+            touch(obj.opid());
+        }
+        for o in self.ops.descendants(&obj) {
+            for op in self.ops.iter_ops(&o) {
+                touch(&op.id);
+                op.succ.iter().for_each(&mut touch);
+            }
+        }
+        touched
+            .into_iter()
+            .map(|index| self.history[index].hash())
+            .collect()
+    }
+
+    /// The index in the history of the change which made the op with ID `id`
+    fn history_index_of_op(&self, id: &OpId) -> Option<usize> {
+        let changes = self.states.get(&id.actor())?;
+        let pos = changes.partition_point(|index| self.history[*index].max_op() < id.counter());
+        changes.get(pos).copied()
+    }
+
+    /// Get the patches which turn `obj` and its descendants as they were at `before` into how
+    /// they are at `after`
+    ///
+    /// This forks the document at `before`, which replays the history up to `before`, and then
+    /// applies every change in `after` which is not in `before` to the fork. Only the changes
+    /// which [`Self::changes_touching`] says modify `obj` are observed, but the others still have
+    /// to be applied as later changes may depend on them. So however small `obj` is this costs
+    /// about as much as calling [`Self::fork_at`] with `after`, it only saves building patches
+    /// for the rest of the document. Changes which remove `obj` from its parent are not reported.
+    ///
+    /// Returns [`AutomergeError::InvalidHash`] if a hash in `before` or `after` isn't in this
+    /// document and [`AutomergeError::NotAnAncestor`] if a hash in `before` isn't an ancestor of
+    /// `after` or one of its heads, as the changes it has which `after` doesn't can't be undone.
+    pub fn diff_subtree(
+        &self,
+        obj: &ExId,
+        before: &[ChangeHash],
+        after: &[ChangeHash],
+    ) -> Result<Vec<Patch<char>>, AutomergeError> {
+        self.exid_to_obj(obj)?;
+        if let Some(hash) = before
+            .iter()
+            .chain(after)
+            .find(|h| !self.history_index.contains_key(h))
+        {
+            return Err(AutomergeError::InvalidHash(*hash));
+        }
+        let after_clock = self.clock_at(after);
+        let covered = |change: &Change| {
+            let actor = self.ops.m.actors.lookup(change.actor_id()).unwrap();
+            after_clock.covers(&OpId::new(change.max_op(), actor))
+        };
+        if let Some(hash) = before
+            .iter()
+            .find(|h| !covered(&self.history[self.history_index[h]]))
+        {
+            return Err(AutomergeError::NotAnAncestor(*hash));
+        }
+        let mut doc = self.fork_at(before)?;
+        let touching = self
+            .changes_touching(obj)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let mut observer = SubtreeObserver::new(obj.clone(), VecOpObserver::default());
+        for change in self.get_changes_clock(before) {
+            if !covered(change) {
+                continue;
+            }
+            let hash = change.hash();
+            let change = std::iter::once(change.clone());
+            if touching.contains(&hash) {
+                doc.apply_changes_with(change, Some(&mut observer))?;
+            } else {
+                // Not observed, but later changes which touch `obj` may depend on it
+                doc.apply_changes(change)?;
+            }
+        }
+        Ok(observer.into_inner().take_patches())
+    }
+
+    /// Get patches which build the current state of this document from an empty document
+    ///
+    /// These are the patches [`Self::load_with`] passes to an observer. Every object is created
+    /// by a patch to its parent before any patch to the object itself, and the elements of a
+    /// sequence are inserted in order, so applying the patches in order to an empty view
+    /// reproduces the document. Only the values which are visible now are included, not the
+    /// history which led to them.
+    pub fn replay_patches(&self) -> Vec<Patch<char>> {
+        let mut observer = VecOpObserver::default();
+        current_state::observe_current_state(self, &mut observer);
+        observer.take_patches()
+    }
+
+    /// Get the changes whose metadata `pred` returns true for
+    ///
+    /// `pred` is given the [`ChangeMeta`] of each change, which doesn't require decoding the
+    /// operations of the change. The changes are in the order they were applied to this
+    /// document, so each comes after its dependencies.
+    pub fn changes_where<F: Fn(&ChangeMeta<'_>) -> bool>(&self, pred: F) -> Vec<&Change> {
+        self.history.iter().filter(|c| pred(&c.meta())).collect()
+    }
+
+    /// Get up to `limit` changes from the history of this document, starting after `after`
+    ///
+    /// Changes are ordered by their depth, which is one more than the depth of the deepest of
+    /// their dependencies, and then by hash. Every change comes after all of its dependencies in
+    /// this order and it doesn't depend on the order changes were applied in, so passing the
+    /// hash of the last change of one page as `after` returns the next page. `None` starts from
+    /// the beginning. If `after` isn't in the history this returns
+    /// [`AutomergeError::InvalidHash`].
+    ///
+    /// Each call looks at every change in the history but only sorts the `limit` changes it
+    /// returns. Changes applied while paging which sort before `after` are not returned by later
+    /// pages.
+    pub fn changes_page(
+        &self,
+        after: Option<ChangeHash>,
+        limit: usize,
+    ) -> Result<Vec<&Change>, AutomergeError> {
+        let after = after
+            .map(|hash| {
+                self.history_index
+                    .get(&hash)
+                    .map(|index| (self.depths[*index], hash))
+                    .ok_or(AutomergeError::InvalidHash(hash))
+            })
+            .transpose()?;
+        // The `limit` smallest changes after `after` seen so far, largest first
+        let mut page = BinaryHeap::with_capacity(limit.min(self.history.len()) + 1);
+        for (index, change) in self.history.iter().enumerate() {
+            let key = (self.depths[index], change.hash());
+            if after.map_or(false, |after| key <= after) {
+                continue;
+            }
+            if page.len() < limit {
+                page.push((key, index));
+            } else if page.peek().map_or(false, |(largest, _)| key < *largest) {
+                page.pop();
+                page.push((key, index));
+            }
+        }
+        Ok(page
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(_, index)| &self.history[index])
+            .collect())
     }
 
     /// Get changes in `other` that are not in `self
@@ -1258,6 +2090,37 @@ impl ReadDoc for Automerge {
         ListRangeAt::new(self, None)
     }
 
+    fn entries_typed<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<Vec<(String, ValueType)>, AutomergeError> {
+        let obj = obj.as_ref();
+        let obj_type = self.exid_to_obj(obj)?.1;
+        if obj_type.is_sequence() {
+            return Err(AutomergeError::InvalidOp(obj_type));
+        }
+        Ok(self
+            .map_range(obj, ..)
+            .map(|(key, value, _)| (key.to_string(), value.value_type()))
+            .collect())
+    }
+
+    fn entries_typed_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Vec<(String, ValueType)>, AutomergeError> {
+        let obj = obj.as_ref();
+        let obj_type = self.exid_to_obj(obj)?.1;
+        if obj_type.is_sequence() {
+            return Err(AutomergeError::InvalidOp(obj_type));
+        }
+        Ok(self
+            .map_range_at(obj, .., heads)
+            .map(|(key, value, _)| (key.to_string(), value.value_type()))
+            .collect())
+    }
+
     fn values<O: AsRef<ExId>>(&self, obj: O) -> Values<'_> {
         if let Ok((obj, obj_type)) = self.exid_to_obj(obj.as_ref()) {
             if obj_type.is_sequence() {
@@ -1301,6 +2164,15 @@ impl ReadDoc for Automerge {
         }
     }
 
+    fn text_length_utf16<O: AsRef<ExId>>(&self, obj: O) -> Result<usize, AutomergeError> {
+        self.text_length_in(obj.as_ref(), TextEncoding::Utf16)
+    }
+
+    fn text_length_chars<O: AsRef<ExId>>(&self, obj: O) -> Result<usize, AutomergeError> {
+        // the utf8 text width of an element is the number of chars in it
+        self.text_length_in(obj.as_ref(), TextEncoding::Utf8)
+    }
+
     fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize {
         if let Ok((inner_obj, obj_type)) = self.exid_to_obj(obj.as_ref()) {
             let clock = self.clock_at(heads);
@@ -1322,6 +2194,95 @@ impl ReadDoc for Automerge {
         Ok(obj_type)
     }
 
+    fn objects_of_type(&self, obj_type: ObjType) -> Vec<ExId> {
+        self.ops
+            .iter_objs()
+            .filter(|(_, t, _)| *t == obj_type)
+            .map(|(obj, _, _)| self.id_to_exid(obj.0))
+            .filter(|obj| {
+                self.parents(obj)
+                    .map(|p| p.visible_path().is_some())
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    fn index_of<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        elem: &ExId,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Option<usize>, AutomergeError> {
+        let (obj, obj_type) = self.exid_to_obj(obj.as_ref())?;
+        if !obj_type.is_sequence() {
+            return Err(AutomergeError::InvalidOp(obj_type));
+        }
+        let encoding = ListEncoding::new(obj_type, self.text_encoding);
+        let elem_id = self
+            .exid_to_opid(elem)
+            .map(ElemId)
+            .map_err(|_| AutomergeError::InvalidElemId(elem.clone()))?;
+        match heads {
+            None => {
+                let index = self
+                    .ops
+                    .search(&obj, query::ElemIdPos::new(elem_id, encoding))
+                    .index()
+                    .ok_or_else(|| AutomergeError::InvalidElemId(elem.clone()))?;
+                // the element is only visible if it is the one at its index
+                let nth = self.ops.search(&obj, query::Nth::new(index, encoding));
+                Ok(match nth.key() {
+                    Ok(Key::Seq(e)) if e == elem_id => Some(index),
+                    _ => None,
+                })
+            }
+            Some(heads) => {
+                let clock = self.clock_at(heads);
+                let mut window = query::VisWindow::default();
+                let mut index = 0;
+                for (key, key_ops) in &self.ops.iter_ops(&obj).group_by(|o| o.elemid_or_key()) {
+                    let visible = key_ops
+                        .filter(|o| window.visible_at(o, index, &clock))
+                        .last()
+                        .filter(|o| matches!(o.action, OpType::Make(_) | OpType::Put(_)));
+                    if key == Key::Seq(elem_id) {
+                        return Ok(visible.map(|_| index));
+                    }
+                    if let Some(o) = visible {
+                        index += o.width(encoding);
+                    }
+                }
+                Err(AutomergeError::InvalidElemId(elem.clone()))
+            }
+        }
+    }
+
+    fn marks_at_index<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        index: usize,
+    ) -> Result<BTreeMap<String, ScalarValue>, AutomergeError> {
+        let obj = obj.as_ref();
+        let obj_type = self.exid_to_obj(obj)?.1;
+        if !obj_type.is_sequence() {
+            return Err(AutomergeError::InvalidOp(obj_type));
+        }
+        let len = self.length(obj);
+        if index > len {
+            return Err(AutomergeError::IndexOutOfBounds { len, pos: index });
+        }
+        let marks = if index < len {
+            self.marks(obj)?
+        } else {
+            self.marks_after_splice(obj, index, 0, 1)?
+        };
+        Ok(marks
+            .into_iter()
+            .filter(|m| m.start <= index && index < m.end)
+            .map(|m| (m.name().to_string(), m.value().clone()))
+            .collect())
+    }
+
     fn text<O: AsRef<ExId>>(&self, obj: O) -> Result<String, AutomergeError> {
         let obj = self.exid_to_obj(obj.as_ref())?.0;
         let query = self.ops.search(&obj, query::ListVals::new());
@@ -1332,6 +2293,71 @@ impl ReadDoc for Automerge {
         Ok(buffer)
     }
 
+    fn text_with_tombstones<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<Vec<TextCell>, AutomergeError> {
+        let (obj, obj_type) = self.exid_to_obj(obj.as_ref())?;
+        if obj_type != ObjType::Text {
+            return Err(AutomergeError::InvalidOp(obj_type));
+        }
+        let mut cells = Vec::new();
+        for (key, mut key_ops) in &self.ops.iter_ops(&obj).group_by(|o| o.elemid_or_key()) {
+            let elem = match key {
+                Key::Seq(ElemId(id)) => self.id_to_exid(id),
+                Key::Map(_) => continue,
+            };
+            let mut is_mark = false;
+            let visible = key_ops.find(|o| {
+                is_mark |= o.is_mark();
+                o.visible()
+            });
+            cells.push(match visible {
+                Some(o) => TextCell::Visible(o.to_str().to_string(), elem),
+                None if is_mark => continue,
+                None => TextCell::Deleted(elem),
+            });
+        }
+        Ok(cells)
+    }
+
+    fn text_slice<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        start: usize,
+        end: usize,
+    ) -> Result<String, AutomergeError> {
+        let (obj, obj_type) = self.exid_to_obj(obj.as_ref())?;
+        let encoding = ListEncoding::new(obj_type, self.text_encoding);
+        let end = end.min(self.ops.search(&obj, query::Len::new(encoding)).len);
+        if start >= end {
+            return Ok(String::new());
+        }
+        // seek to the element containing `start` rather than walking every op before it
+        let query = self.ops.search(&obj, query::Nth::new(start, encoding));
+        let first = match query.ops_pos.first() {
+            Some(first) => *first,
+            None => return Ok(String::new()),
+        };
+        let mut pos = query.index();
+        let mut last_elem = None;
+        let mut buffer = String::new();
+        for op in self.ops.iter_ops_from(&obj, first) {
+            if op.insert {
+                last_elem = None;
+            }
+            if last_elem.is_none() && op.visible() {
+                if pos >= end {
+                    break;
+                }
+                last_elem = op.elemid();
+                buffer.push_str(op.to_str());
+                pos += op.width(encoding);
+            }
+        }
+        Ok(buffer)
+    }
+
     fn text_at<O: AsRef<ExId>>(
         &self,
         obj: O,
@@ -1354,27 +2380,60 @@ impl ReadDoc for Automerge {
     fn marks<O: AsRef<ExId>>(&self, obj: O) -> Result<Vec<Mark<'_>>, AutomergeError> {
         let (obj, obj_type) = self.exid_to_obj(obj.as_ref())?;
         let encoding = ListEncoding::new(obj_type, self.text_encoding);
-        let ops_by_key = self.ops().iter_ops(&obj).group_by(|o| o.elemid_or_key());
-        let mut pos = 0;
-        let mut marks = MarkStateMachine::default();
+        let query = self.ops.search(&obj, query::MarkOps::new(encoding));
+        Ok(marks_from_ops(&query.ops, self))
+    }
 
-        Ok(ops_by_key
+    fn has_mark<O: AsRef<ExId>>(&self, obj: O, name: &str) -> Result<bool, AutomergeError> {
+        let (obj, obj_type) = self.exid_to_obj(obj.as_ref())?;
+        let encoding = ListEncoding::new(obj_type, self.text_encoding);
+        let query = self.ops.search(&obj, query::MarkOps::new(encoding));
+        // marks with other names don't affect this one, and the end of a mark which wasn't
+        // passed to the state machine is ignored by it
+        let mut state = MarkStateMachine::default();
+        Ok(query
+            .ops
+            .iter()
+            .filter(|(_, op)| match &op.action {
+                OpType::MarkBegin(_, data) => data.name == name,
+                _ => true,
+            })
+            .filter_map(|(pos, op)| state.process(*pos, op, self))
+            .any(|mark| mark.start < mark.end))
+    }
+
+    fn marks_in_range<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<Mark<'static>>, AutomergeError> {
+        let (obj, obj_type) = self.exid_to_obj(obj.as_ref())?;
+        let encoding = ListEncoding::new(obj_type, self.text_encoding);
+        // the marks which are open at `end` are cut off there, so the mark ops after it don't
+        // need to be looked at
+        let query = self.ops.search(&obj, query::MarkOps::until(encoding, end));
+        let mut state = MarkStateMachine::default();
+        let mut spans = query
+            .ops
+            .iter()
+            .filter_map(|(pos, op)| state.process(*pos, op, self))
+            .collect::<Vec<_>>();
+        // any mark which ended at `end` has been closed, so the open ones carry on past it
+        spans.extend(state.open_marks(end.saturating_add(1)));
+        let mut result = Anchors::new(&query.ops)
+            .join(spans.into_iter())
             .into_iter()
-            .filter_map(|(_key, key_ops)| {
-                key_ops
-                    .filter(|o| o.visible_or_mark())
-                    .last()
-                    .and_then(|o| match &o.action {
-                        OpType::Make(_) | OpType::Put(_) => {
-                            pos += o.width(encoding);
-                            None
-                        }
-                        OpType::MarkBegin(_, data) => marks.mark_begin(o.id, pos, data, self),
-                        OpType::MarkEnd(_) => marks.mark_end(o.id, pos, self),
-                        OpType::Increment(_) | OpType::Delete => None,
-                    })
+            .filter(|m| m.start < end && start < m.end)
+            .map(|m| {
+                let mut m = m.into_owned();
+                m.start = m.start.max(start);
+                m.end = m.end.min(end);
+                m
             })
-            .collect())
+            .collect::<Vec<_>>();
+        sort_marks(&mut result);
+        Ok(result)
     }
 
     fn marks_at<O: AsRef<ExId>>(
@@ -1388,24 +2447,72 @@ impl ReadDoc for Automerge {
         let ops_by_key = self.ops().iter_ops(&obj).group_by(|o| o.elemid_or_key());
         let mut window = query::VisWindow::default();
         let mut pos = 0;
-        let mut marks = MarkStateMachine::default();
+        let mut mark_ops = Vec::new();
+
+        for (_key, key_ops) in &ops_by_key {
+            if let Some(o) = key_ops.filter(|o| window.visible_at(o, pos, &clock)).last() {
+                match &o.action {
+                    OpType::Make(_) | OpType::Put(_) => pos += o.width(encoding),
+                    OpType::MarkBegin(..) | OpType::MarkEnd(_) => mark_ops.push((pos, o)),
+                    OpType::Increment(_) | OpType::Delete => {}
+                }
+            }
+        }
+        Ok(marks_from_ops(&mark_ops, self))
+    }
 
-        Ok(ops_by_key
+    fn marks_after_splice<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        pos: usize,
+        del: usize,
+        insert_len: usize,
+    ) -> Result<Vec<Mark<'static>>, AutomergeError> {
+        let (obj, obj_type) = self.exid_to_obj(obj.as_ref())?;
+        if !obj_type.is_sequence() {
+            return Err(AutomergeError::InvalidOp(obj_type));
+        }
+        let encoding = ListEncoding::new(obj_type, self.text_encoding);
+
+        // find the position of every mark op as it would be after the deletion
+        let mut len = 0;
+        let mut anchors = Vec::new();
+        for (_key, key_ops) in &self.ops().iter_ops(&obj).group_by(|o| o.elemid_or_key()) {
+            if let Some(o) = key_ops.filter(|o| o.visible_or_mark()).last() {
+                if o.is_mark() {
+                    let after_del = if len <= pos {
+                        len
+                    } else if len <= pos.saturating_add(del) {
+                        pos
+                    } else {
+                        len - del
+                    };
+                    anchors.push((o, after_del));
+                } else {
+                    len += o.width(encoding);
+                }
+            }
+        }
+        if pos > len {
+            return Err(AutomergeError::IndexOutOfBounds { len, pos });
+        }
+
+        // an insert at `pos` goes after the last valid anchor at `pos` (see `query::InsertNth`),
+        // everything which comes after that is pushed back by the inserted elements
+        let last_anchor = anchors
+            .iter()
+            .rposition(|(o, p)| *p == pos && o.valid_mark_anchor());
+        let mark_ops = anchors
             .into_iter()
-            .filter_map(|(_key, key_ops)| {
-                key_ops
-                    .filter(|o| window.visible_at(o, pos, &clock))
-                    .last()
-                    .and_then(|o| match &o.action {
-                        OpType::Make(_) | OpType::Put(_) => {
-                            pos += o.width(encoding);
-                            None
-                        }
-                        OpType::MarkBegin(_, data) => marks.mark_begin(o.id, pos, data, self),
-                        OpType::MarkEnd(_) => marks.mark_end(o.id, pos, self),
-                        OpType::Increment(_) | OpType::Delete => None,
-                    })
+            .enumerate()
+            .map(|(i, (o, p))| {
+                let stays_before = p < pos || (p == pos && last_anchor.map_or(false, |a| i <= a));
+                (if stays_before { p } else { p + insert_len }, o)
             })
+            .collect::<Vec<_>>();
+        Ok(marks_from_ops(&mark_ops, self)
+            .into_iter()
+            .map(Mark::into_owned)
             .collect())
     }
 
@@ -1414,7 +2521,7 @@ impl ReadDoc for Automerge {
         obj: O,
         prop: P,
     ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
-        Ok(self.get_all(obj, prop.into())?.last().cloned())
+        Ok(self.get_all(obj, prop.into())?.into_iter().next())
     }
 
     fn get_at<O: AsRef<ExId>, P: Into<Prop>>(
@@ -1423,7 +2530,7 @@ impl ReadDoc for Automerge {
         prop: P,
         heads: &[ChangeHash],
     ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
-        Ok(self.get_all_at(obj, prop, heads)?.last().cloned())
+        Ok(self.get_all_at(obj, prop, heads)?.into_iter().next())
     }
 
     fn get_all<O: AsRef<ExId>, P: Into<Prop>>(
@@ -1440,6 +2547,7 @@ impl ReadDoc for Automerge {
                         .search(&obj, query::Prop::new(p))
                         .ops
                         .into_iter()
+                        .rev()
                         .map(|o| (o.value(), self.id_to_exid(o.id)))
                         .collect()
                 } else {
@@ -1455,6 +2563,7 @@ impl ReadDoc for Automerge {
                     .search(&obj, query::Nth::new(n, encoding))
                     .ops
                     .into_iter()
+                    .rev()
                     .map(|o| (o.value(), self.id_to_exid(o.id)))
                     .collect()
             }
@@ -1478,6 +2587,7 @@ impl ReadDoc for Automerge {
                         .search(&obj, query::PropAt::new(p, clock))
                         .ops
                         .into_iter()
+                        .rev()
                         .map(|o| (o.clone_value(), self.id_to_exid(o.id)))
                         .collect()
                 } else {
@@ -1493,6 +2603,7 @@ impl ReadDoc for Automerge {
                     .search(&obj, query::NthAt::new(n, clock, encoding))
                     .ops
                     .into_iter()
+                    .rev()
                     .map(|o| (o.clone_value(), self.id_to_exid(o.id)))
                     .collect()
             }
@@ -1500,6 +2611,61 @@ impl ReadDoc for Automerge {
         Ok(result)
     }
 
+    fn counter_components<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(ActorId, i64)>, AutomergeError> {
+        let ex_obj = obj.as_ref();
+        let (obj, obj_type) = self.exid_to_obj(ex_obj)?;
+        let prop = prop.into();
+        let winner = match &prop {
+            Prop::Map(p) => self.ops.m.props.lookup(p).and_then(|p| {
+                let query = self.ops.search(&obj, query::Prop::new(p));
+                query.ops.last().copied().zip(query.ops_pos.last().copied())
+            }),
+            Prop::Seq(n) => {
+                let encoding = ListEncoding::new(obj_type, self.text_encoding);
+                let query = self.ops.search(&obj, query::Nth::new(*n, encoding));
+                query.ops.last().copied().zip(query.ops_pos.last().copied())
+            }
+        };
+        let (op, pos) = winner.ok_or_else(|| AutomergeError::MissingProp {
+            obj: ex_obj.clone(),
+            prop: prop.clone(),
+        })?;
+        let start = match &op.action {
+            OpType::Put(ScalarValue::Counter(c)) => c.start,
+            _ => {
+                return Err(AutomergeError::InvalidValueType {
+                    expected: ValueType::Counter.to_string(),
+                    unexpected: op.value().value_type().to_string(),
+                })
+            }
+        };
+        let mut components = HashMap::new();
+        components.insert(op.id.actor(), start);
+        // increments come after the op they increment among the ops for the same key
+        let key = op.elemid_or_key();
+        for inc in self
+            .ops
+            .iter_ops_from(&obj, pos + 1)
+            .take_while(|o| o.elemid_or_key() == key)
+        {
+            if let OpType::Increment(n) = inc.action {
+                if op.succ.contains(&inc.id) {
+                    *components.entry(inc.id.actor()).or_insert(0) += n;
+                }
+            }
+        }
+        let mut components = components
+            .into_iter()
+            .map(|(actor, n)| (self.ops.m.actors[actor].clone(), n))
+            .collect::<Vec<_>>();
+        components.sort();
+        Ok(components)
+    }
+
     fn get_missing_deps(&self, heads: &[ChangeHash]) -> Vec<ChangeHash> {
         let in_queue: HashSet<_> = self.queue.iter().map(|change| change.hash()).collect();
         let mut missing = HashSet::new();
@@ -1532,8 +2698,31 @@ impl ReadDoc for Automerge {
     }
 }
 
+/// One more than the depth of the deepest dependency of `change`, all of which must be in
+/// `history_index`
+fn change_depth(
+    history_index: &HashMap<ChangeHash, usize>,
+    depths: &[u64],
+    change: &Change,
+) -> u64 {
+    change
+        .deps()
+        .iter()
+        .map(|dep| depths[history_index[dep]] + 1)
+        .max()
+        .unwrap_or(0)
+}
+
 impl Default for Automerge {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Counters are copied by their current value, anything else as it is
+fn flat_scalar(value: &ScalarValue) -> ScalarValue {
+    match value {
+        ScalarValue::Counter(c) => ScalarValue::counter(i64::from(c)),
+        other => other.clone(),
+    }
+}
@@ -3,7 +3,8 @@ use pretty_assertions::assert_eq;
 
 use super::*;
 use crate::op_tree::B;
-use crate::transaction::Transactable;
+use crate::transaction::{SpliceResult, Transactable};
+use crate::types::{self, OpIds};
 use crate::*;
 use std::convert::TryInto;
 
@@ -1113,6 +1114,61 @@ fn delete_nothing_in_list_returns_error() {
     assert!(tx.delete(ROOT, 0,).is_err());
 }
 
+#[test]
+fn missing_list_element_error_carries_obj_and_prop() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let list = tx.put_object(ROOT, "list", ObjType::List).unwrap();
+    tx.insert(&list, 0, "a").unwrap();
+    match tx.delete(&list, 3) {
+        Err(AutomergeError::MissingProp { obj, prop }) => {
+            assert_eq!(obj, list);
+            assert_eq!(prop, Prop::Seq(3));
+        }
+        other => panic!("expected MissingProp, got {:?}", other),
+    }
+    match tx.put(&list, 1, "b") {
+        Err(AutomergeError::MissingProp { obj, prop }) => {
+            assert_eq!(obj, list);
+            assert_eq!(prop, Prop::Seq(1));
+        }
+        other => panic!("expected MissingProp, got {:?}", other),
+    }
+    let err = tx.increment(&list, 5, 1).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("property `5` does not exist in object `{}`", list)
+    );
+}
+
+#[test]
+fn unknown_object_error_carries_obj_id() {
+    let mut doc1 = Automerge::new();
+    let mut tx = doc1.transaction();
+    let map = tx.put_object(ROOT, "map", ObjType::Map).unwrap();
+    tx.commit();
+
+    // `map` refers to an actor which doc2 has never seen
+    let mut doc2 = Automerge::new();
+    let mut tx = doc2.transaction();
+    match tx.put(&map, "key", 1) {
+        Err(AutomergeError::InvalidObj(obj)) => assert_eq!(obj, map),
+        other => panic!("expected InvalidObj, got {:?}", other),
+    }
+    tx.commit();
+
+    // `map` refers to a known actor but an object which doesn't exist
+    let mut doc3 = doc1.fork();
+    let mut tx = doc1.transaction();
+    let list = tx.put_object(ROOT, "list", ObjType::List).unwrap();
+    tx.commit();
+    let mut tx = doc3.transaction();
+    match tx.insert(&list, 0, "a") {
+        Err(AutomergeError::InvalidObj(obj)) => assert_eq!(obj, list),
+        other => panic!("expected InvalidObj, got {:?}", other),
+    }
+}
+
 #[test]
 fn loaded_doc_changes_have_hash() {
     let mut doc = Automerge::new();
@@ -1302,15 +1358,15 @@ fn compute_list_indexes_correctly_when_list_element_is_split_across_tree_nodes()
     assert_eq!(
         doc1.get_all(&list, 0).unwrap(),
         vec![
-            (max.into(), ExId::Id(max + 2, actor1.clone(), 0)),
-            (max.into(), ExId::Id(max + 2, actor2.clone(), 1))
+            (max.into(), ExId::Id(max + 2, actor2.clone(), 1)),
+            (max.into(), ExId::Id(max + 2, actor1.clone(), 0))
         ]
     );
     assert_eq!(
         doc2.get_all(&list, 0).unwrap(),
         vec![
-            (max.into(), ExId::Id(max + 2, actor1, 0)),
-            (max.into(), ExId::Id(max + 2, actor2, 1))
+            (max.into(), ExId::Id(max + 2, actor2, 1)),
+            (max.into(), ExId::Id(max + 2, actor1, 0))
         ]
     );
     assert!(doc1.get(&list, 1).unwrap().is_none());
@@ -1558,3 +1614,532 @@ fn get_changes_heads_empty() {
     let heads = doc.get_heads();
     assert_eq!(doc.get_changes(&heads).unwrap(), Vec::<&Change>::new());
 }
+
+#[test]
+fn splice_reports_affected_region() {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    let result = doc
+        .splice(&list, 0, 0, vec![1.into(), 2.into(), 3.into()])
+        .unwrap();
+    assert_eq!(
+        result,
+        SpliceResult {
+            removed: 0,
+            inserted: 3,
+            at: 0
+        }
+    );
+
+    let result = doc.splice(&list, 1, 1, vec![4.into(), 5.into()]).unwrap();
+    assert_eq!(
+        result,
+        SpliceResult {
+            removed: 1,
+            inserted: 2,
+            at: 1
+        }
+    );
+
+    // deleting past the end of the list only removes the remaining elements
+    let result = doc.splice(&list, 2, 10, vec![]).unwrap();
+    assert_eq!(
+        result,
+        SpliceResult {
+            removed: 2,
+            inserted: 0,
+            at: 2
+        }
+    );
+    assert_eq!(doc.length(&list), 2);
+}
+
+#[test]
+fn splice_text_reports_affected_region_in_text_encoding() {
+    let mut doc = AutoCommit::new().with_encoding(TextEncoding::Utf16);
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    let result = doc.splice_text(&text, 0, 0, "a🐻c").unwrap();
+    assert_eq!(
+        result,
+        SpliceResult {
+            removed: 0,
+            inserted: 4,
+            at: 0
+        }
+    );
+
+    // deleting from the middle of the bear moves the splice back to the start of it
+    let result = doc.splice_text(&text, 2, 1, "b").unwrap();
+    assert_eq!(
+        result,
+        SpliceResult {
+            removed: 2,
+            inserted: 1,
+            at: 1
+        }
+    );
+    assert_eq!(doc.text(&text).unwrap(), "abc");
+}
+
+#[test]
+fn splice_at_the_length_of_a_sequence_appends() {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.splice(&list, 0, 0, vec![1.into(), 2.into()]).unwrap();
+    let result = doc.splice(&list, 2, 5, vec![3.into()]).unwrap();
+    assert_eq!(
+        result,
+        SpliceResult {
+            removed: 0,
+            inserted: 1,
+            at: 2
+        }
+    );
+    assert_eq!(doc.length(&list), 3);
+    assert_eq!(doc.get(&list, 2).unwrap().unwrap().0, Value::int(3));
+}
+
+#[test]
+fn splice_past_the_end_of_a_sequence_is_an_error() {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.splice(&list, 0, 0, vec![1.into(), 2.into()]).unwrap();
+    assert!(matches!(
+        doc.splice(&list, 3, 0, vec![3.into()]),
+        Err(AutomergeError::IndexOutOfBounds { len: 2, pos: 3 })
+    ));
+    // nothing is deleted when the position is out of bounds
+    assert!(matches!(
+        doc.splice(&list, 3, 1, vec![]),
+        Err(AutomergeError::IndexOutOfBounds { len: 2, pos: 3 })
+    ));
+    assert_eq!(doc.length(&list), 2);
+
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "ab").unwrap();
+    assert!(matches!(
+        doc.splice_text(&text, 5, 0, "c"),
+        Err(AutomergeError::IndexOutOfBounds { len: 2, pos: 5 })
+    ));
+    assert_eq!(doc.text(&text).unwrap(), "ab");
+}
+
+#[test]
+fn verify_integrity_passes_for_documents_built_with_the_api() {
+    let mut doc1 = AutoCommit::new();
+    let list = doc1.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc1.splice(&list, 0, 0, vec![1.into(), 2.into(), 3.into()])
+        .unwrap();
+    let text = doc1.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc1.splice_text(&text, 0, 0, "hello").unwrap();
+    doc1.put(ROOT, "counter", ScalarValue::counter(1)).unwrap();
+
+    let mut doc2 = doc1.fork();
+    doc1.delete(&list, 1).unwrap();
+    doc1.increment(ROOT, "counter", 2).unwrap();
+    doc2.put(&list, 1, "two").unwrap();
+    doc2.splice_text(&text, 5, 0, " world").unwrap();
+    doc2.put(ROOT, "counter", "overwritten").unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    assert_eq!(doc1.document().verify_integrity(), Ok(()));
+
+    let loaded = Automerge::load(&doc1.save()).unwrap();
+    assert_eq!(loaded.verify_integrity(), Ok(()));
+}
+
+#[test]
+fn verify_integrity_reports_missing_heads() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "a", 1).unwrap();
+    tx.commit();
+
+    let bogus = ChangeHash([7; 32]);
+    doc.deps.push(bogus);
+    assert_eq!(
+        doc.verify_integrity(),
+        Err(IntegrityError::MissingHead(bogus))
+    );
+}
+
+#[test]
+fn verify_integrity_reports_asymmetric_succ_and_pred() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "a", 1).unwrap();
+    tx.put(ROOT, "a", 2).unwrap();
+    tx.commit();
+
+    let (_, second) = doc.get(ROOT, "a").unwrap().unwrap();
+    doc.ops_mut()
+        .change_vis(&types::ObjId::root(), 1, |op| op.pred = OpIds::empty());
+    match doc.verify_integrity() {
+        Err(IntegrityError::SuccWithoutPred { op, succ }) => {
+            assert_eq!(op, doc.ops().id_to_exid(OpId::new(1, 0)));
+            assert_eq!(succ, second);
+        }
+        other => panic!("expected SuccWithoutPred, got {:?}", other),
+    }
+}
+
+#[test]
+fn changes_by_returns_an_actors_changes_in_seq_order() {
+    let actor1 = ActorId::from([1]);
+    let actor2 = ActorId::from([2]);
+    let mut doc1 = AutoCommit::new().with_actor(actor1.clone());
+    let mut doc2 = AutoCommit::new().with_actor(actor2.clone());
+
+    for i in 0..3 {
+        doc1.put(ROOT, "doc1", i).unwrap();
+        doc1.commit();
+        doc2.put(ROOT, "doc2", i).unwrap();
+        doc2.commit();
+        doc1.merge(&mut doc2).unwrap();
+        doc2.merge(&mut doc1).unwrap();
+    }
+
+    let check = |doc: &Automerge| {
+        for actor in [&actor1, &actor2] {
+            let changes = doc.changes_by(actor).collect::<Vec<_>>();
+            assert_eq!(changes.len(), 3);
+            for (i, change) in changes.iter().enumerate() {
+                assert_eq!(change.actor_id(), actor);
+                assert_eq!(change.seq(), i as u64 + 1);
+            }
+        }
+        assert_eq!(doc.changes_by(&ActorId::from([3])).count(), 0);
+    };
+    check(doc1.document());
+    check(&Automerge::load(&doc2.save()).unwrap());
+}
+
+#[test]
+fn len_changes_and_len_ops_count_transactions_and_merges() {
+    let mut doc1 = AutoCommit::new();
+    assert_eq!((doc1.len_changes(), doc1.len_ops()), (0, 0));
+
+    doc1.put(ROOT, "a", 1).unwrap();
+    doc1.put(ROOT, "b", 2).unwrap();
+    doc1.commit();
+    assert_eq!((doc1.len_changes(), doc1.len_ops()), (1, 2));
+
+    // overwritten ops are still counted
+    doc1.put(ROOT, "a", 3).unwrap();
+    doc1.commit();
+    assert_eq!((doc1.len_changes(), doc1.len_ops()), (2, 3));
+
+    let mut doc2 = doc1.fork();
+    let list = doc2.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc2.splice(&list, 0, 0, vec![1.into(), 2.into()]).unwrap();
+    doc2.commit();
+    assert_eq!((doc2.len_changes(), doc2.len_ops()), (3, 6));
+
+    doc1.merge(&mut doc2).unwrap();
+    assert_eq!((doc1.len_changes(), doc1.len_ops()), (3, 6));
+
+    // merging again doesn't count anything twice
+    doc1.merge(&mut doc2).unwrap();
+    assert_eq!((doc1.len_changes(), doc1.len_ops()), (3, 6));
+
+    let loaded = Automerge::load(&doc1.save()).unwrap();
+    assert_eq!((loaded.len_changes(), loaded.len_ops()), (3, 6));
+}
+
+#[test]
+fn push_appends_in_order_within_a_transaction() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let list = tx.put_object(ROOT, "list", ObjType::List).unwrap();
+    tx.insert(&list, 0, "first").unwrap();
+    tx.push(&list, "second").unwrap();
+    let third = tx.push(&list, "third").unwrap();
+    let map = tx.push_object(&list, ObjType::Map).unwrap();
+    tx.put(&map, "key", "fourth").unwrap();
+    assert!(matches!(
+        tx.push(&map, "nope"),
+        Err(AutomergeError::InvalidOp(ObjType::Map))
+    ));
+    tx.commit();
+
+    assert_eq!(doc.length(&list), 4);
+    for (index, expected) in ["first", "second", "third"].iter().enumerate() {
+        assert_eq!(
+            doc.get(&list, index).unwrap().unwrap().0,
+            Value::str(expected)
+        );
+    }
+    assert_eq!(doc.get(&list, 2).unwrap().unwrap().1, third);
+    assert_eq!(doc.get(&list, 3).unwrap().unwrap().1, map);
+}
+
+#[test]
+fn actor_seed_gives_reproducible_actor_ids() {
+    let mut doc1 = Automerge::new_with_actor_seed(7);
+    let mut doc2 = Automerge::new_with_actor_seed(7);
+    for doc in [&mut doc1, &mut doc2] {
+        let mut tx = doc.transaction();
+        tx.put(ROOT, "key", "value").unwrap();
+        tx.commit();
+    }
+    let actor1 = doc1.get_changes(&[]).unwrap()[0].actor_id().clone();
+    let actor2 = doc2.get_changes(&[]).unwrap()[0].actor_id().clone();
+    assert_eq!(actor1, actor2);
+    assert_eq!(actor1, ActorId::from_seed(7));
+    assert_ne!(ActorId::from_seed(7), ActorId::from_seed(8));
+}
+
+#[test]
+fn get_all_returns_the_winner_first() {
+    let mut doc1 = AutoCommit::new().with_actor(ActorId::from([1]));
+    let mut doc2 = doc1.fork().with_actor(ActorId::from([2]));
+    let mut doc3 = doc1.fork().with_actor(ActorId::from([3]));
+    doc1.put(ROOT, "key", "one").unwrap();
+    doc2.put(ROOT, "key", "two").unwrap();
+    doc3.put(ROOT, "key", "three").unwrap();
+    doc1.merge(&mut doc3).unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    let heads = doc1.get_heads();
+    doc1.put(ROOT, "other", 1).unwrap();
+
+    for values in [
+        doc1.get_all(ROOT, "key").unwrap(),
+        doc1.get_all_at(ROOT, "key", &heads).unwrap(),
+    ] {
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[0], doc1.get(ROOT, "key").unwrap().unwrap());
+        assert_eq!(values[0].0, Value::str("three"));
+        assert_eq!(values[1].0, Value::str("two"));
+        assert_eq!(values[2].0, Value::str("one"));
+    }
+    assert_eq!(
+        doc1.get_at(ROOT, "key", &heads).unwrap().unwrap().0,
+        Value::str("three")
+    );
+}
+
+#[test]
+fn get_or_create_object_creates_reuses_and_refuses_to_clobber() {
+    let mut doc = AutoCommit::new();
+
+    // absent, so it is created
+    let map = doc.get_or_create_object(ROOT, "map", ObjType::Map).unwrap();
+    assert_eq!(
+        doc.get(ROOT, "map").unwrap().unwrap(),
+        (Value::Object(ObjType::Map), map.clone())
+    );
+    doc.put(&map, "key", "value").unwrap();
+
+    // present with the right type, so the existing object is returned
+    assert_eq!(
+        doc.get_or_create_object(ROOT, "map", ObjType::Map).unwrap(),
+        map
+    );
+    assert_eq!(doc.length(&map), 1);
+
+    // present with another type, so nothing is changed
+    assert!(matches!(
+        doc.get_or_create_object(ROOT, "map", ObjType::List),
+        Err(AutomergeError::Conflict {
+            expected: ObjType::List,
+            found: ValueType::Object(ObjType::Map),
+            ..
+        })
+    ));
+    doc.put(ROOT, "scalar", 1).unwrap();
+    assert!(matches!(
+        doc.get_or_create_object(ROOT, "scalar", ObjType::Map),
+        Err(AutomergeError::Conflict {
+            expected: ObjType::Map,
+            found: ValueType::Int,
+            ..
+        })
+    ));
+    assert_eq!(doc.get(ROOT, "map").unwrap().unwrap().1, map);
+    assert_eq!(doc.get(ROOT, "scalar").unwrap().unwrap().0, Value::int(1));
+}
+
+#[test]
+fn put_text_and_insert_text_create_text_objects() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let text = tx.put_text(ROOT, "text", "hello").unwrap();
+    let list = tx.put_object(ROOT, "list", ObjType::List).unwrap();
+    let item = tx.insert_text(&list, 0, "world").unwrap();
+    tx.put(ROOT, "string", "hello").unwrap();
+    tx.commit();
+
+    assert_eq!(doc.object_type(&text).unwrap(), ObjType::Text);
+    assert_eq!(doc.text(&text).unwrap(), "hello");
+    assert_eq!(doc.object_type(&item).unwrap(), ObjType::Text);
+    assert_eq!(doc.text(&item).unwrap(), "world");
+    assert_eq!(doc.get(&list, 0).unwrap().unwrap().1, item);
+    assert_eq!(
+        doc.get(ROOT, "string").unwrap().unwrap().0,
+        Value::str("hello")
+    );
+}
+
+#[test]
+fn change_frontier_gives_the_heads_a_change_was_made_on() {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(ROOT, "base", 0).unwrap();
+    let base = doc1.commit().unwrap();
+    let mut doc2 = doc1.fork();
+    doc1.put(ROOT, "doc1", 1).unwrap();
+    let c1 = doc1.commit().unwrap();
+    doc2.put(ROOT, "doc2", 2).unwrap();
+    let c2 = doc2.commit().unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    doc1.put(ROOT, "merged", 3).unwrap();
+    let c3 = doc1.commit().unwrap();
+
+    assert_eq!(doc1.change_frontier(&base), Some(vec![]));
+    assert_eq!(doc1.change_frontier(&c1), Some(vec![base]));
+    assert_eq!(doc1.change_frontier(&c2), Some(vec![base]));
+    let mut expected = vec![c1, c2];
+    expected.sort();
+    assert_eq!(doc1.change_frontier(&c3), Some(expected.clone()));
+    assert_eq!(doc2.change_frontier(&c3), None);
+
+    let mut before = doc1.fork_at(&expected).unwrap();
+    assert_eq!(before.get_heads(), expected);
+    assert!(before.get(ROOT, "doc1").unwrap().is_some());
+    assert!(before.get(ROOT, "doc2").unwrap().is_some());
+    assert!(before.get(ROOT, "merged").unwrap().is_none());
+}
+
+#[test]
+fn delete_checked_reports_whether_anything_was_deleted() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "key", "value").unwrap();
+    assert!(doc.delete_checked(ROOT, "key").unwrap());
+    assert!(!doc.delete_checked(ROOT, "key").unwrap());
+    assert!(!doc.delete_checked(ROOT, "missing").unwrap());
+
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.splice(&list, 0, 0, vec![1.into(), 2.into()]).unwrap();
+    assert!(doc.delete_checked(&list, 1).unwrap());
+    assert_eq!(doc.length(&list), 1);
+    // deleting past the end of a list is still an error
+    assert!(matches!(
+        doc.delete_checked(&list, 1),
+        Err(AutomergeError::MissingProp { .. })
+    ));
+
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "ab").unwrap();
+    assert!(doc.delete_checked(&text, 0).unwrap());
+    assert!(!doc.delete_checked(&text, 1).unwrap());
+    assert_eq!(doc.text(&text).unwrap(), "b");
+}
+
+#[test]
+fn interning_shares_the_storage_of_equal_strings() {
+    // long enough that `SmolStr` puts it on the heap
+    let tag = "a-tag-which-is-longer-than-twenty-three-bytes";
+    let distinct_allocations = |doc: &Automerge| {
+        doc.ops()
+            .iter()
+            .filter_map(|(_, _, op)| match &op.action {
+                OpType::Put(ScalarValue::Str(s)) => Some(s.as_str().as_ptr()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>()
+            .len()
+    };
+    let fill = |doc: &mut Automerge| {
+        let mut tx = doc.transaction();
+        let list = tx.put_object(ROOT, "list", ObjType::List).unwrap();
+        for i in 0..10_000 {
+            tx.insert(&list, i, tag).unwrap();
+        }
+        tx.commit();
+        list
+    };
+
+    let mut plain = Automerge::new();
+    fill(&mut plain);
+    assert_eq!(distinct_allocations(&plain), 10_000);
+
+    let mut interned = Automerge::new().with_interning(true);
+    let list = fill(&mut interned);
+    assert_eq!(distinct_allocations(&interned), 1);
+    assert_eq!(interned.length(&list), 10_000);
+    assert_eq!(
+        interned.get(&list, 9_999).unwrap().unwrap().0,
+        Value::str(tag)
+    );
+
+    // forks keep interning, loading doesn't
+    for mut fork in [
+        interned.fork(),
+        interned.fork_at(&interned.get_heads()).unwrap(),
+    ] {
+        fill(&mut fork);
+        assert_eq!(distinct_allocations(&fork), 1);
+    }
+    assert_eq!(
+        distinct_allocations(&Automerge::load(&interned.save()).unwrap()),
+        10_000
+    );
+
+    // enabling interning on a loaded document interns what is already there
+    let mut loaded = Automerge::load(&plain.save()).unwrap();
+    assert_eq!(distinct_allocations(&loaded), 10_000);
+    loaded.set_interning(true);
+    assert_eq!(distinct_allocations(&loaded), 1);
+}
+
+#[test]
+fn insert_many_inserts_values_in_order() {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, "first").unwrap();
+    doc.insert(&list, 1, "last").unwrap();
+
+    let ids = doc.insert_many(&list, 1, 0..1000_i64).unwrap();
+    assert_eq!(ids.len(), 1000);
+    assert_eq!(doc.length(&list), 1002);
+    for (i, (index, value, id)) in doc.list_range(&list, 1..1001).enumerate() {
+        assert_eq!(index, i + 1);
+        assert_eq!(value, Value::int(i as i64));
+        assert_eq!(id, ids[i]);
+    }
+    assert_eq!(doc.get(&list, 1001).unwrap().unwrap().0, Value::str("last"));
+
+    assert!(doc
+        .insert_many(&list, 0, Vec::<i64>::new())
+        .unwrap()
+        .is_empty());
+    assert!(matches!(
+        doc.insert_many(ROOT, 0, [1]),
+        Err(AutomergeError::InvalidOp(ObjType::Map))
+    ));
+}
+
+#[test]
+fn exid_to_opid_errors_say_why() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let map = tx.put_object(ROOT, "map", ObjType::Map).unwrap();
+    tx.commit();
+    assert!(doc.exid_to_opid(&map).is_ok());
+    assert!(matches!(
+        doc.exid_to_opid(&ROOT),
+        Err(AutomergeError::RootHasNoOpId)
+    ));
+
+    let mut other = Automerge::new();
+    let mut tx = other.transaction();
+    let elsewhere = tx.put_object(ROOT, "map", ObjType::Map).unwrap();
+    tx.commit();
+    assert!(matches!(
+        doc.exid_to_opid(&elsewhere),
+        Err(AutomergeError::UnknownActor(actor)) if &actor == other.get_actor()
+    ));
+    // the public API reports the object it couldn't find
+    assert!(matches!(
+        doc.get(&elsewhere, "key"),
+        Err(AutomergeError::InvalidObj(_))
+    ));
+}
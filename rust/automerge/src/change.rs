@@ -16,6 +16,21 @@ pub struct Change {
     len: usize,
 }
 
+/// The metadata of a [`Change`], everything about it except its operations
+///
+/// This is built from fields the change already has parsed, see [`Change::meta`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeMeta<'a> {
+    pub actor: &'a ActorId,
+    pub seq: u64,
+    pub start_op: NonZeroU64,
+    pub max_op: u64,
+    pub timestamp: i64,
+    pub message: Option<&'a str>,
+    pub deps: &'a [ChangeHash],
+    pub hash: ChangeHash,
+}
+
 impl Change {
     pub(crate) fn new(stored: StoredChange<'static, Verified>) -> Self {
         let len = stored.iter_ops().count();
@@ -88,6 +103,20 @@ impl Change {
         self.stored.timestamp()
     }
 
+    /// Get the metadata of this change without touching its operations
+    pub fn meta(&self) -> ChangeMeta<'_> {
+        ChangeMeta {
+            actor: self.actor_id(),
+            seq: self.seq(),
+            start_op: self.start_op(),
+            max_op: self.max_op(),
+            timestamp: self.timestamp(),
+            message: self.message().map(String::as_str),
+            deps: self.deps(),
+            hash: self.hash(),
+        }
+    }
+
     pub fn bytes(&mut self) -> Cow<'_, [u8]> {
         if let CompressionState::NotCompressed = self.compression {
             if let Some(compressed) = self.stored.compress() {
@@ -0,0 +1,469 @@
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU64;
+
+use crate::exid::ExId;
+use crate::legacy;
+use crate::types::{ElemId, ListEncoding};
+use crate::{query, ActorId, Automerge, AutomergeError, Change, ChangeHash, ObjType, ScalarValue};
+
+/// The key an operation built with [`ChangeBuilder`] applies to
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpKey {
+    /// A key in a map or table
+    Map(String),
+    /// The start of a sequence, only valid for inserts
+    Head,
+    /// The element of a sequence created by the insert operation with this ID
+    Elem(ExId),
+}
+
+impl From<&str> for OpKey {
+    fn from(key: &str) -> Self {
+        OpKey::Map(key.to_string())
+    }
+}
+
+impl From<String> for OpKey {
+    fn from(key: String) -> Self {
+        OpKey::Map(key)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BuilderOp {
+    obj: ExId,
+    key: OpKey,
+    insert: bool,
+    action: legacy::OpType,
+    pred: Vec<ExId>,
+}
+
+/// Assemble a [`Change`] operation by operation
+///
+/// This is a low level API intended for protocol tests, fuzzers and tooling which need to produce
+/// changes which are not the result of editing a document. Each method which adds an operation
+/// returns the ID the operation will have, which can be used to refer to objects, sequence
+/// elements and preds created earlier in the same change.
+///
+/// [`Self::build`] checks the operations against the document the change is intended for and
+/// returns a hashable [`Change`] which can be encoded with [`Change::raw_bytes`] or applied with
+/// [`Automerge::apply_changes`].
+#[derive(Debug, Clone)]
+pub struct ChangeBuilder {
+    actor: ActorId,
+    seq: u64,
+    start_op: NonZeroU64,
+    deps: Vec<ChangeHash>,
+    time: i64,
+    message: Option<String>,
+    ops: Vec<BuilderOp>,
+}
+
+impl ChangeBuilder {
+    /// Create a builder for the `seq`th change by `actor`, the first operation will have the
+    /// counter `start_op`
+    pub fn new(actor: ActorId, seq: u64, start_op: NonZeroU64) -> Self {
+        ChangeBuilder {
+            actor,
+            seq,
+            start_op,
+            deps: Vec::new(),
+            time: 0,
+            message: None,
+            ops: Vec::new(),
+        }
+    }
+
+    /// Create a builder for the next change `actor` would make on top of the current heads of
+    /// `doc`
+    pub fn for_doc(doc: &Automerge, actor: ActorId) -> Self {
+        let seq = doc.changes_by(&actor).count() as u64 + 1;
+        let start_op = NonZeroU64::new(doc.max_op() + 1).unwrap();
+        ChangeBuilder::new(actor, seq, start_op).with_deps(doc.get_heads())
+    }
+
+    /// Set the hashes of the changes this change depends on, replacing any set before
+    pub fn with_deps(mut self, deps: Vec<ChangeHash>) -> Self {
+        self.deps = deps;
+        self
+    }
+
+    /// Set the timestamp of the change, in milliseconds since the unix epoch, defaults to 0
+    pub fn with_timestamp(mut self, time: i64) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Set the message of the change, defaults to none
+    pub fn with_message(mut self, message: String) -> Self {
+        self.message = Some(message);
+        self
+    }
+
+    /// Set `key` in `obj` to a scalar value, overwriting the operations in `pred`
+    pub fn put<K: Into<OpKey>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: &ExId,
+        key: K,
+        value: V,
+        pred: &[ExId],
+    ) -> ExId {
+        self.push(
+            obj,
+            key.into(),
+            false,
+            legacy::OpType::Put(value.into()),
+            pred,
+        )
+    }
+
+    /// Set `key` in `obj` to a new object, overwriting the operations in `pred`
+    pub fn put_object<K: Into<OpKey>>(
+        &mut self,
+        obj: &ExId,
+        key: K,
+        obj_type: ObjType,
+        pred: &[ExId],
+    ) -> ExId {
+        self.push(obj, key.into(), false, legacy::OpType::Make(obj_type), pred)
+    }
+
+    /// Insert a scalar value into the sequence `obj` after the element `after`
+    pub fn insert<V: Into<ScalarValue>>(&mut self, obj: &ExId, after: OpKey, value: V) -> ExId {
+        self.push(obj, after, true, legacy::OpType::Put(value.into()), &[])
+    }
+
+    /// Insert a new object into the sequence `obj` after the element `after`
+    pub fn insert_object(&mut self, obj: &ExId, after: OpKey, obj_type: ObjType) -> ExId {
+        self.push(obj, after, true, legacy::OpType::Make(obj_type), &[])
+    }
+
+    /// Delete the operations in `pred` from `key` in `obj`
+    pub fn delete<K: Into<OpKey>>(&mut self, obj: &ExId, key: K, pred: &[ExId]) -> ExId {
+        self.push(obj, key.into(), false, legacy::OpType::Delete, pred)
+    }
+
+    /// Increment the counters set by the operations in `pred`
+    pub fn increment<K: Into<OpKey>>(
+        &mut self,
+        obj: &ExId,
+        key: K,
+        by: i64,
+        pred: &[ExId],
+    ) -> ExId {
+        self.push(obj, key.into(), false, legacy::OpType::Increment(by), pred)
+    }
+
+    fn next_id(&self) -> ExId {
+        // The actor index is only a cache hint, `Automerge::exid_to_opid` falls back to looking
+        // the actor up when it does not match
+        ExId::Id(
+            self.start_op.get() + self.ops.len() as u64,
+            self.actor.clone(),
+            usize::MAX,
+        )
+    }
+
+    fn push(
+        &mut self,
+        obj: &ExId,
+        key: OpKey,
+        insert: bool,
+        action: legacy::OpType,
+        pred: &[ExId],
+    ) -> ExId {
+        let id = self.next_id();
+        self.ops.push(BuilderOp {
+            obj: obj.clone(),
+            key,
+            insert,
+            action,
+            pred: pred.to_vec(),
+        });
+        id
+    }
+
+    /// Check the operations against `doc` and produce the change
+    ///
+    /// # Errors
+    ///
+    /// * [`AutomergeError::DuplicateSeqNumber`] if `doc` already has a change with this actor and
+    ///   seq
+    /// * [`AutomergeError::DuplicateOpId`] if `doc` already has operations by this actor with a
+    ///   counter at or after the start op
+    /// * [`AutomergeError::InvalidObj`] if an operation refers to an object which is neither in
+    ///   `doc` nor created earlier in the change
+    /// * [`AutomergeError::InvalidOp`] if a map key is used with a sequence or vice versa
+    /// * [`AutomergeError::InvalidElemId`] if an operation refers to a sequence element which
+    ///   is neither in the object nor inserted into it earlier in the change
+    /// * [`AutomergeError::InvalidPred`] if a pred is not an operation in the object, either in
+    ///   `doc` or earlier in the change
+    pub fn build(self, doc: &Automerge) -> Result<Change, AutomergeError> {
+        let start_op = self.start_op.get();
+        for change in doc.changes_by(&self.actor) {
+            if change.seq() == self.seq {
+                return Err(AutomergeError::DuplicateSeqNumber(self.seq, self.actor));
+            }
+            if change.max_op() >= start_op {
+                return Err(AutomergeError::DuplicateOpId(start_op, self.actor));
+            }
+        }
+
+        // The counter of `id` if it is one of the operations in this change
+        let local = |id: &ExId| match id {
+            ExId::Id(ctr, actor, _) if *actor == self.actor && *ctr >= start_op => Some(*ctr),
+            _ => None,
+        };
+        let mut objects: HashMap<u64, ObjType> = HashMap::new();
+        let mut elems: HashSet<(ExId, u64)> = HashSet::new();
+        // The object each operation of this change so far is in
+        let mut local_objs: HashMap<u64, ExId> = HashMap::new();
+        let mut operations = Vec::with_capacity(self.ops.len());
+        for (counter, op) in (start_op..).zip(self.ops.iter()) {
+            let (obj_type, doc_obj) = match local(&op.obj) {
+                Some(ctr) => match objects.get(&ctr) {
+                    Some(obj_type) => (*obj_type, None),
+                    None => return Err(AutomergeError::InvalidObj(op.obj.clone())),
+                },
+                None => {
+                    let (obj, obj_type) = doc.exid_to_obj(&op.obj)?;
+                    (obj_type, Some(obj))
+                }
+            };
+
+            let key = match &op.key {
+                OpKey::Map(k) if !obj_type.is_sequence() => legacy::Key::Map(k.into()),
+                OpKey::Head if obj_type.is_sequence() && op.insert => legacy::Key::head(),
+                OpKey::Elem(elem) if obj_type.is_sequence() => {
+                    let found = match (local(elem), doc_obj) {
+                        (Some(ctr), _) => elems.contains(&(op.obj.clone(), ctr)),
+                        (None, Some(obj)) => doc
+                            .exid_to_opid(elem)
+                            .ok()
+                            .and_then(|opid| {
+                                doc.ops()
+                                    .search(
+                                        &obj,
+                                        query::ElemIdPos::new(ElemId(opid), ListEncoding::List),
+                                    )
+                                    .index()
+                            })
+                            .is_some(),
+                        (None, None) => false,
+                    };
+                    if !found {
+                        return Err(AutomergeError::InvalidElemId(elem.clone()));
+                    }
+                    legacy::Key::Seq(legacy::ElementId::Id(to_legacy_opid(elem)))
+                }
+                _ => return Err(AutomergeError::InvalidOp(obj_type)),
+            };
+
+            let mut pred = Vec::with_capacity(op.pred.len());
+            for p in &op.pred {
+                let valid = match (local(p), &doc_obj) {
+                    (Some(ctr), _) => local_objs.get(&ctr) == Some(&op.obj),
+                    (None, Some(obj)) => doc
+                        .exid_to_opid(p)
+                        .ok()
+                        .and_then(|opid| {
+                            doc.ops().search(obj, query::OpIdSearch::new(opid)).index()
+                        })
+                        .is_some(),
+                    (None, None) => false,
+                };
+                if !valid {
+                    return Err(AutomergeError::InvalidPred(p.clone()));
+                }
+                pred.push(to_legacy_opid(p));
+            }
+
+            if let legacy::OpType::Make(obj_type) = op.action {
+                objects.insert(counter, obj_type);
+            }
+            if op.insert {
+                elems.insert((op.obj.clone(), counter));
+            }
+            local_objs.insert(counter, op.obj.clone());
+            operations.push(legacy::Op {
+                action: op.action.clone(),
+                obj: match &op.obj {
+                    ExId::Root => legacy::ObjectId::Root,
+                    id => legacy::ObjectId::Id(to_legacy_opid(id)),
+                },
+                key,
+                pred: pred.into(),
+                insert: op.insert,
+            });
+        }
+
+        Ok(Change::from(crate::ExpandedChange {
+            operations,
+            actor_id: self.actor,
+            hash: None,
+            seq: self.seq,
+            start_op: self.start_op,
+            time: self.time,
+            message: self.message,
+            deps: self.deps,
+            extra_bytes: Vec::new(),
+        }))
+    }
+}
+
+fn to_legacy_opid(id: &ExId) -> legacy::OpId {
+    match id {
+        ExId::Root => unreachable!("the root object is not an operation"),
+        ExId::Id(ctr, actor, _) => legacy::OpId(*ctr, actor.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{transaction::Transactable, AutoCommit, ReadDoc, Value, ROOT};
+
+    #[test]
+    fn built_changes_can_be_applied() {
+        let mut doc = AutoCommit::new();
+        let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+        doc.insert(&list, 0, "a").unwrap();
+        let a = doc.list_range(&list, ..).next().unwrap().2;
+        doc.commit();
+
+        let actor = ActorId::random();
+        let mut builder = ChangeBuilder::for_doc(doc.document(), actor.clone())
+            .with_timestamp(10)
+            .with_message("built".to_string());
+        let map = builder.put_object(&ROOT, "map", ObjType::Map, &[]);
+        builder.put(&map, "key", "value", &[]);
+        let b = builder.insert(&list, OpKey::Elem(a), "b");
+        builder.insert(&list, OpKey::Elem(b), "c");
+        let change = builder.build(doc.document()).unwrap();
+
+        assert_eq!(change.actor_id(), &actor);
+        assert_eq!(change.message(), Some(&"built".to_string()));
+        assert_eq!(change.deps(), doc.get_heads().as_slice());
+        let decoded = Change::from_bytes(change.raw_bytes().to_vec()).unwrap();
+        assert_eq!(decoded.hash(), change.hash());
+
+        doc.apply_changes(vec![change]).unwrap();
+        let (value, _) = doc.get(ROOT, "map").unwrap().unwrap();
+        assert_eq!(value, Value::Object(ObjType::Map));
+        let values = doc
+            .list_range(&list, ..)
+            .map(|(_, v, _)| v.into_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn references_must_exist() {
+        let doc = Automerge::new();
+        let actor = ActorId::random();
+        let missing = ExId::Id(1, ActorId::random(), 0);
+
+        let mut builder = ChangeBuilder::for_doc(&doc, actor.clone());
+        builder.put(&missing, "key", 1, &[]);
+        assert!(matches!(
+            builder.build(&doc),
+            Err(AutomergeError::InvalidObj(_))
+        ));
+
+        let mut builder = ChangeBuilder::for_doc(&doc, actor.clone());
+        let list = builder.put_object(&ROOT, "list", ObjType::List, &[]);
+        builder.insert(&list, OpKey::Elem(missing.clone()), 1);
+        assert!(matches!(
+            builder.build(&doc),
+            Err(AutomergeError::InvalidElemId(_))
+        ));
+
+        let mut builder = ChangeBuilder::for_doc(&doc, actor.clone());
+        let list = builder.put_object(&ROOT, "list", ObjType::List, &[]);
+        builder.put(&list, "key", 1, &[]);
+        assert!(matches!(
+            builder.build(&doc),
+            Err(AutomergeError::InvalidOp(ObjType::List))
+        ));
+
+        let mut builder = ChangeBuilder::for_doc(&doc, actor);
+        builder.put(&ROOT, "key", 1, &[missing]);
+        assert!(matches!(
+            builder.build(&doc),
+            Err(AutomergeError::InvalidPred(_))
+        ));
+    }
+
+    #[test]
+    fn preds_must_be_ops_in_the_object() {
+        let mut doc = AutoCommit::new();
+        doc.put(ROOT, "key", 1).unwrap();
+        let map = doc.put_object(ROOT, "map", ObjType::Map).unwrap();
+        doc.put(&map, "key", 2).unwrap();
+        doc.put(ROOT, "gone", 3).unwrap();
+        doc.delete(ROOT, "gone").unwrap();
+        doc.commit();
+        let (_, in_root) = doc.get(ROOT, "key").unwrap().unwrap();
+        let (_, in_map) = doc.get(&map, "key").unwrap().unwrap();
+        let actor = ActorId::random();
+
+        // The ID of the delete, which is at most max_op but is not an op in the document
+        let fabricated = match &in_root {
+            ExId::Id(_, actor, idx) => ExId::Id(doc.document().max_op(), actor.clone(), *idx),
+            ExId::Root => unreachable!(),
+        };
+        let mut builder = ChangeBuilder::for_doc(doc.document(), actor.clone());
+        builder.put(&ROOT, "key", 3, &[fabricated]);
+        assert!(matches!(
+            builder.build(doc.document()),
+            Err(AutomergeError::InvalidPred(_))
+        ));
+
+        let mut builder = ChangeBuilder::for_doc(doc.document(), actor.clone());
+        builder.put(&ROOT, "key", 3, std::slice::from_ref(&in_map));
+        assert!(matches!(
+            builder.build(doc.document()),
+            Err(AutomergeError::InvalidPred(_))
+        ));
+
+        let mut builder = ChangeBuilder::for_doc(doc.document(), actor.clone());
+        let local = builder.put(&ROOT, "other", 3, &[]);
+        builder.put(&map, "other", 4, &[local]);
+        assert!(matches!(
+            builder.build(doc.document()),
+            Err(AutomergeError::InvalidPred(_))
+        ));
+
+        let mut builder = ChangeBuilder::for_doc(doc.document(), actor);
+        builder.put(&ROOT, "key", 3, &[in_root]);
+        builder.put(&map, "key", 4, &[in_map]);
+        let change = builder.build(doc.document()).unwrap();
+        doc.apply_changes(vec![change]).unwrap();
+        assert_eq!(doc.get(&map, "key").unwrap().unwrap().0, Value::from(4));
+    }
+
+    #[test]
+    fn op_ids_must_not_be_reused() {
+        let mut doc = Automerge::new();
+        let actor = ActorId::random();
+        let mut builder = ChangeBuilder::for_doc(&doc, actor.clone());
+        builder.put(&ROOT, "key", 1, &[]);
+        builder.put(&ROOT, "other", 2, &[]);
+        doc.apply_change(builder.build(&doc).unwrap()).unwrap();
+
+        let mut builder = ChangeBuilder::new(actor.clone(), 2, NonZeroU64::new(2).unwrap())
+            .with_deps(doc.get_heads());
+        builder.put(&ROOT, "key", 3, &[]);
+        assert!(matches!(
+            builder.build(&doc),
+            Err(AutomergeError::DuplicateOpId(2, _))
+        ));
+
+        let mut builder = ChangeBuilder::new(actor, 1, NonZeroU64::new(3).unwrap());
+        builder.put(&ROOT, "key", 3, &[]);
+        assert!(matches!(
+            builder.build(&doc),
+            Err(AutomergeError::DuplicateSeqNumber(1, _))
+        ));
+    }
+}
@@ -0,0 +1,61 @@
+//! The raw ops of a document, only built with the `debug-internals` feature.
+//!
+//! These types mirror how the document is stored internally. They are meant for debuggers and
+//! inspectors and may change in any release, including minor and patch releases.
+
+use crate::exid::ExId;
+use crate::op_set::OpSet;
+use crate::types::{Key, ObjId, Op};
+use crate::OpType;
+
+/// The key an op was made at, see [`DebugOp::key`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugKey {
+    /// A property of a map or table
+    Map(String),
+    /// The element of a list or text created by the op with this ID
+    Elem(ExId),
+    /// The start of a list or text, only used by ops which insert
+    Head,
+}
+
+/// One op of a document as returned by [`Automerge::debug_ops`](crate::Automerge::debug_ops)
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugOp {
+    /// The ID of the op, which is also the ID of the object or element it creates
+    pub id: ExId,
+    /// The object the op is in
+    pub obj: ExId,
+    /// The property or element the op updates, or the element it inserts after
+    pub key: DebugKey,
+    /// Whether the op inserts a new element after `key` rather than updating it
+    pub insert: bool,
+    /// What the op does
+    pub action: OpType,
+    /// The ops this op overwrites
+    pub pred: Vec<ExId>,
+    /// The ops which overwrite or delete this op
+    ///
+    /// Deletes are not kept as ops of their own once they are applied, so their IDs only show up
+    /// here.
+    pub succ: Vec<ExId>,
+}
+
+impl DebugOp {
+    pub(crate) fn new(ops: &OpSet, obj: &ObjId, op: &Op) -> Self {
+        let key = match op.key {
+            Key::Map(prop) => DebugKey::Map(ops.m.props[prop].clone()),
+            Key::Seq(elem) if elem.is_head() => DebugKey::Head,
+            Key::Seq(elem) => DebugKey::Elem(ops.id_to_exid(elem.0)),
+        };
+        Self {
+            id: ops.id_to_exid(op.id),
+            obj: ops.id_to_exid(obj.0),
+            key,
+            insert: op.insert,
+            action: op.action.clone(),
+            pred: op.pred.iter().map(|id| ops.id_to_exid(*id)).collect(),
+            succ: op.succ.into_iter().map(|id| ops.id_to_exid(*id)).collect(),
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use crate::change::LoadError as LoadChangeError;
+use crate::exid::ExId;
 use crate::storage::load::Error as LoadError;
-use crate::types::{ActorId, ScalarValue};
-use crate::value::DataType;
+use crate::types::{ActorId, Prop, ScalarValue};
+use crate::value::{DataType, ValueType};
 use crate::{ChangeHash, ObjType};
 use thiserror::Error;
 
@@ -9,32 +10,52 @@ use thiserror::Error;
 pub enum AutomergeError {
     #[error(transparent)]
     ChangeGraph(#[from] crate::change_graph::MissingDep),
+    #[error("there is already a `{found}` at `{prop}` where a `{expected}` was expected")]
+    Conflict {
+        prop: Prop,
+        expected: ObjType,
+        found: ValueType,
+    },
     #[error("failed to load compressed data: {0}")]
     Deflate(#[source] std::io::Error),
+    #[error("op ids from counter {0} are already used by actor {1}")]
+    DuplicateOpId(u64, ActorId),
     #[error("duplicate seq {0} found for actor {1}")]
     DuplicateSeqNumber(u64, ActorId),
+    #[error("element `{0}` has already been deleted")]
+    ElemDeleted(ExId),
     #[error("key must not be an empty string")]
     EmptyStringKey,
     #[error("general failure")]
     Fail,
+    #[error("index {pos} is out of bounds for a sequence of length {len}")]
+    IndexOutOfBounds { len: usize, pos: usize },
     #[error("invalid actor ID `{0}`")]
     InvalidActorId(String),
     #[error(transparent)]
     InvalidChangeHashBytes(#[from] InvalidChangeHashSlice),
     #[error("invalid UTF-8 character at {0}")]
     InvalidCharacter(usize),
+    #[error("element `{0}` does not exist in the sequence")]
+    InvalidElemId(ExId),
     #[error("invalid hash {0}")]
     InvalidHash(ChangeHash),
     #[error("index {0} is out of bounds")]
     InvalidIndex(usize),
+    #[error("object `{0}` does not exist in this document")]
+    InvalidObj(ExId),
     #[error("invalid obj id `{0}`")]
     InvalidObjId(String),
     #[error("invalid obj id format `{0}`")]
     InvalidObjIdFormat(String),
     #[error("invalid op for object of type `{0}`")]
     InvalidOp(ObjType),
+    #[error("pred `{0}` is not an earlier operation")]
+    InvalidPred(ExId),
     #[error("seq {0} is out of bounds")]
     InvalidSeq(u64),
+    #[error("cannot insert a `{0}` into a text object, text elements must be characters")]
+    InvalidTextValue(ObjType),
     #[error("invalid type of value, expected `{expected}` but received `{unexpected}`")]
     InvalidValueType {
         expected: String,
@@ -50,10 +71,16 @@ pub enum AutomergeError {
     MissingHash(ChangeHash),
     #[error("change's deps should already be in the document")]
     MissingDeps,
+    #[error("property `{prop}` does not exist in object `{obj}`")]
+    MissingProp { obj: ExId, prop: Prop },
     #[error("compressed chunk was not a change")]
     NonChangeCompressed,
-    #[error("id was not an object id")]
-    NotAnObject,
+    #[error("change {0} is not an ancestor of the heads it was compared with")]
+    NotAnAncestor(ChangeHash),
+    #[error("the root object was not created by an operation")]
+    RootHasNoOpId,
+    #[error("actor `{0}` is not in this document")]
+    UnknownActor(ActorId),
 }
 
 impl PartialEq for AutomergeError {
@@ -69,6 +96,29 @@ impl From<AutomergeError> for wasm_bindgen::JsValue {
     }
 }
 
+/// A violation of one of the invariants checked by [`crate::Automerge::verify_integrity`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityError {
+    #[error("the root object has a parent `{parent}`")]
+    RootHasParent { parent: ExId },
+    #[error("object `{obj}` has no parent")]
+    MissingParent { obj: ExId },
+    #[error("object `{obj}` was not created by an op in its parent `{parent}`")]
+    NotCreatedInParent { obj: ExId, parent: ExId },
+    #[error("op `{op}` has a predecessor `{pred}` which does not exist")]
+    MissingPred { op: ExId, pred: ExId },
+    #[error("op `{op}` has a predecessor `{pred}` which does not list it as a successor")]
+    PredWithoutSucc { op: ExId, pred: ExId },
+    #[error("op `{op}` has a successor `{succ}` which does not list it as a predecessor")]
+    SuccWithoutPred { op: ExId, succ: ExId },
+    #[error("head {0} does not correspond to a change in this document")]
+    MissingHead(ChangeHash),
+    #[error("change {change} depends on {dep} which is not in this document")]
+    MissingDep { change: ChangeHash, dep: ChangeHash },
+    #[error("change {0} is not reachable from the heads of this document")]
+    UnreachableChange(ChangeHash),
+}
+
 #[derive(Error, Debug)]
 #[error("Invalid actor ID: {0}")]
 pub struct InvalidActorId(pub String);
@@ -2,6 +2,7 @@ use crate::storage::parse;
 use crate::ActorId;
 use serde::Serialize;
 use serde::Serializer;
+use std::cmp::Ordering;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
@@ -130,6 +131,31 @@ impl PartialEq for ExId {
 
 impl Eq for ExId {}
 
+/// IDs are ordered by the lamport timestamp of the operation which created them, that is by
+/// counter and then by actor, and [`ExId::Root`] comes before every other ID. This is the order
+/// the document itself uses to resolve conflicts and it is stable for the IDs of a single
+/// document. IDs created concurrently in different forks are still ordered by counter and actor
+/// but that order doesn't say anything about when they were created, and it may not match the
+/// order they end up in once the forks are merged.
+impl Ord for ExId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (ExId::Root, ExId::Root) => Ordering::Equal,
+            (ExId::Root, ExId::Id(..)) => Ordering::Less,
+            (ExId::Id(..), ExId::Root) => Ordering::Greater,
+            (ExId::Id(ctr1, actor1, _), ExId::Id(ctr2, actor2, _)) => {
+                ctr1.cmp(ctr2).then_with(|| actor1.cmp(actor2))
+            }
+        }
+    }
+}
+
+impl PartialOrd for ExId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl fmt::Display for ExId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
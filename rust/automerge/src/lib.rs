@@ -244,10 +244,13 @@ mod autocommit;
 mod automerge;
 mod autoserde;
 mod change;
+mod change_builder;
 mod change_graph;
 mod clock;
 mod columnar;
 mod convert;
+#[cfg(feature = "debug-internals")]
+mod debug_ops;
 mod error;
 mod exid;
 mod indexed_cache;
@@ -259,6 +262,7 @@ mod list_range_at;
 mod map_range;
 mod map_range_at;
 pub mod marks;
+mod merge_strategy;
 pub mod op_observer;
 mod op_set;
 pub mod op_tree;
@@ -274,12 +278,17 @@ mod value;
 mod values;
 #[cfg(feature = "optree-visualisation")]
 mod visualisation;
+mod walk;
 
 pub use crate::automerge::{Automerge, OnPartialLoad};
 pub use autocommit::{AutoCommit, AutoCommitWithObs};
 pub use autoserde::AutoSerde;
-pub use change::{Change, LoadError as LoadChangeError};
+pub use change::{Change, ChangeMeta, LoadError as LoadChangeError};
+pub use change_builder::{ChangeBuilder, OpKey};
+#[cfg(feature = "debug-internals")]
+pub use debug_ops::{DebugKey, DebugOp};
 pub use error::AutomergeError;
+pub use error::IntegrityError;
 pub use error::InvalidActorId;
 pub use error::InvalidChangeHashSlice;
 pub use exid::{ExId as ObjId, ObjIdFromBytesError};
@@ -290,15 +299,20 @@ pub use list_range::ListRange;
 pub use list_range_at::ListRangeAt;
 pub use map_range::MapRange;
 pub use map_range_at::MapRangeAt;
+pub use merge_strategy::{MergeStrategies, MergeStrategy};
 pub use op_observer::{
-    OpObserver, Patch, PatchAction, ToggleObserver, VecOpObserver, VecOpObserver16,
+    OpObserver, Patch, PatchAction, SubtreeObserver, ToggleObserver, VecOpObserver, VecOpObserver16,
 };
-pub use parents::{Parent, Parents};
-pub use read::ReadDoc;
+pub use parents::{Parent, Parents, StableProp};
+pub use read::{AnnotatedValue, ReadDoc, TextCell};
 pub use sequence_tree::SequenceTree;
-pub use types::{ActorId, ChangeHash, ObjType, OpType, ParseChangeHashError, Prop, TextEncoding};
-pub use value::{ScalarValue, Value};
+pub use storage::load::{LoadProgress, LoadWarning};
+pub use types::{
+    ActorId, ChangeHash, LineEnding, ObjType, OpType, ParseChangeHashError, Prop, TextEncoding,
+};
+pub use value::{FromValue, ScalarValue, Value, ValueFormat, ValueType};
 pub use values::Values;
+pub use walk::{SubtreeStats, TreeVisitor};
 
 /// The object ID for the root map of a document
 pub const ROOT: ObjId = ObjId::Root;
@@ -2,10 +2,11 @@ use smol_str::SmolStr;
 use std::fmt;
 use std::fmt::Display;
 
-use crate::types::OpId;
+use crate::types::{Op, OpId, OpType};
 use crate::value::ScalarValue;
 use crate::Automerge;
 use std::borrow::Cow;
+use std::collections::HashMap;
 
 /// Marks let you store out-of-bound information about sequences.
 ///
@@ -63,12 +64,25 @@ impl<'a> Mark<'a> {
     }
 }
 
+/// The `(start, end, value)` of one span of a mark, see [`crate::ReadDoc::marks_by_name`]
+pub type MarkSpan = (usize, usize, ScalarValue);
+
 #[derive(Debug, Clone, PartialEq, Default)]
 pub(crate) struct MarkStateMachine<'a> {
     state: Vec<(OpId, Mark<'a>)>,
 }
 
 impl<'a> MarkStateMachine<'a> {
+    /// Apply the mark operation `op` at index `pos`, returning a span of a mark which is known
+    /// to end at `pos`
+    pub(crate) fn process(&mut self, pos: usize, op: &'a Op, doc: &Automerge) -> Option<Mark<'a>> {
+        match &op.action {
+            OpType::MarkBegin(_, data) => self.mark_begin(op.id, pos, data, doc),
+            OpType::MarkEnd(_) => self.mark_end(op.id, pos, doc),
+            _ => None,
+        }
+    }
+
     pub(crate) fn mark_begin(
         &mut self,
         id: OpId,
@@ -129,6 +143,26 @@ impl<'a> MarkStateMachine<'a> {
         result
     }
 
+    /// The marks which have not ended yet, cut off at `end`
+    ///
+    /// For each name this is only the mark which wins, and nothing if that is a null mark.
+    pub(crate) fn open_marks(&self, end: usize) -> Vec<Mark<'a>> {
+        let mut names = Vec::new();
+        let mut result = Vec::new();
+        for (_, mark) in self.state.iter().rev() {
+            if names.contains(&mark.name()) {
+                continue;
+            }
+            names.push(mark.name());
+            if !mark.value().is_null() {
+                let mut mark = mark.clone();
+                mark.end = end;
+                result.push(mark);
+            }
+        }
+        result
+    }
+
     fn find(&self, target: OpId, doc: &Automerge) -> Result<usize, usize> {
         let metadata = &doc.ops().m;
         self.state
@@ -163,6 +197,120 @@ impl<'a> MarkStateMachine<'a> {
     }
 }
 
+/// Compute the marks of a sequence from its mark operations, in order, each with the index it is
+/// at in the sequence.
+///
+/// Two marks with the same name and value where one ends at the index the other starts are
+/// joined into one if text inserted at that index would get the mark, that is if the end of the
+/// first or the start of the second expands. Otherwise there is a real boundary between them and
+/// they are returned separately.
+pub(crate) fn marks_from_ops<'a>(ops: &[(usize, &'a Op)], doc: &Automerge) -> Vec<Mark<'a>> {
+    let mut state = MarkStateMachine::default();
+    let marks = ops
+        .iter()
+        .filter_map(|(pos, op)| state.process(*pos, op, doc));
+    let mut result = Anchors::new(ops).join(marks);
+    sort_marks(&mut result);
+    result
+}
+
+/// The marks whose start or end expands at each index of a sequence
+///
+/// This is built once from the mark operations of a sequence so that finding out whether a mark
+/// expands at an index doesn't scan all of them.
+pub(crate) struct Anchors<'a> {
+    /// The marks which start at each index with a start which expands
+    before: HashMap<usize, Vec<&'a MarkData>>,
+    /// The marks which end at each index with an end which expands
+    after: HashMap<usize, Vec<&'a MarkData>>,
+}
+
+impl<'a> Anchors<'a> {
+    pub(crate) fn new(ops: &[(usize, &'a Op)]) -> Self {
+        let begins = ops
+            .iter()
+            .filter_map(|(_, op)| match &op.action {
+                OpType::MarkBegin(_, data) => Some((op.id, data)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let mut anchors = Anchors {
+            before: HashMap::new(),
+            after: HashMap::new(),
+        };
+        for (pos, op) in ops {
+            match &op.action {
+                OpType::MarkBegin(true, data) => anchors.before.entry(*pos).or_default().push(data),
+                OpType::MarkEnd(true) => {
+                    if let Some(data) = begins.get(&op.id.prev()) {
+                        anchors.after.entry(*pos).or_default().push(*data);
+                    }
+                }
+                _ => {}
+            }
+        }
+        anchors
+    }
+
+    fn starts_expanding(&self, index: usize, data: &MarkData) -> bool {
+        self.before
+            .get(&index)
+            .map_or(false, |marks| marks.contains(&data))
+    }
+
+    fn ends_expanding(&self, index: usize, data: &MarkData) -> bool {
+        self.after
+            .get(&index)
+            .map_or(false, |marks| marks.contains(&data))
+    }
+
+    /// How `mark` expands, going by the operations at its start and end
+    pub(crate) fn expand(&self, mark: &Mark<'_>) -> ExpandMark {
+        ExpandMark::from(
+            self.starts_expanding(mark.start, &mark.data),
+            self.ends_expanding(mark.end, &mark.data),
+        )
+    }
+
+    /// Join each span to the last span with the same name and value if it starts where that one
+    /// ends and an element inserted there would get the mark, see [`marks_from_ops`]
+    pub(crate) fn join<I: Iterator<Item = Mark<'a>>>(&self, marks: I) -> Vec<Mark<'a>> {
+        let mut result: Vec<Mark<'a>> = Vec::new();
+        // the index in `result` of the last span of each value of each name
+        let mut last: HashMap<SmolStr, Vec<usize>> = HashMap::new();
+        for mark in marks {
+            let spans = last.entry(mark.data.name.clone()).or_default();
+            match spans
+                .iter_mut()
+                .find(|i| result[**i].value() == mark.value())
+            {
+                Some(i)
+                    if result[*i].end == mark.start
+                        && (self.ends_expanding(mark.start, &mark.data)
+                            || self.starts_expanding(mark.start, &mark.data)) =>
+                {
+                    result[*i].end = mark.end
+                }
+                Some(i) => {
+                    *i = result.len();
+                    result.push(mark);
+                }
+                None => {
+                    spans.push(result.len());
+                    result.push(mark);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Sort marks by start, then end, then name, the order every method which returns marks uses.
+/// The sort is stable so marks which only differ in value stay in the order they were found in.
+pub(crate) fn sort_marks(marks: &mut [Mark<'_>]) {
+    marks.sort_by(|a, b| (a.start, a.end, a.name()).cmp(&(b.start, b.end, b.name())));
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct MarkData {
     pub name: SmolStr,
@@ -177,6 +325,10 @@ impl Display for MarkData {
 
 /// ExpandMark allows you to decide whether new text inserted at the start/end of your
 /// mark should also inherit the mark.
+///
+/// It only applies at the ends of a mark, text inserted strictly inside a mark always gets the
+/// mark, see [`ExpandMark::inherits_on_split`].
+///
 /// See <https://www.inkandswitch.com/peritext/> for details and
 /// suggestions of which value to use for which operations when building a rich text editor.
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -208,4 +360,14 @@ impl ExpandMark {
     pub fn after(&self) -> bool {
         matches!(self, Self::After | Self::Both)
     }
+
+    /// Whether text inserted strictly inside a mark, splitting the marked run in two, gets the
+    /// mark.
+    ///
+    /// This is always true. A mark is anchored to the elements at its start and end, an insert
+    /// in between doesn't move either anchor so both halves of the run and the inserted text
+    /// stay marked whichever `ExpandMark` was used.
+    pub fn inherits_on_split(&self) -> bool {
+        true
+    }
 }
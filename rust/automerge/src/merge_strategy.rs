@@ -0,0 +1,205 @@
+use std::cmp::Ordering;
+
+use crate::{ObjType, ScalarValue};
+
+/// How to settle a conflict on a numeric map property, see [`MergeStrategies`]
+///
+/// There is no `Sum` strategy. Two replicas which both settle the same conflict each write a
+/// deciding value, those values conflict with each other in turn and summing them again would
+/// count everything twice. Use a [`ScalarValue::Counter`] for values which should be summed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the largest of the conflicting values
+    Max,
+    /// Keep the smallest of the conflicting values
+    Min,
+}
+
+impl MergeStrategy {
+    /// Pick one of `values`, or `None` if any of them is not a number
+    ///
+    /// The result only depends on the set of values and not on their order, so every replica
+    /// with the same conflict picks the same value.
+    pub(crate) fn decide<'a>(&self, values: &[&'a ScalarValue]) -> Option<&'a ScalarValue> {
+        let mut decided: Option<&ScalarValue> = None;
+        for value in values {
+            match value {
+                ScalarValue::Int(_) | ScalarValue::Uint(_) => {}
+                ScalarValue::F64(n) if !n.is_nan() => {}
+                _ => return None,
+            }
+            let better = match decided {
+                None => true,
+                Some(best) => {
+                    let ordering = compare(value, best);
+                    match self {
+                        Self::Max => ordering == Ordering::Greater,
+                        Self::Min => ordering == Ordering::Less,
+                    }
+                }
+            };
+            if better {
+                decided = Some(value);
+            }
+        }
+        decided
+    }
+}
+
+/// Compare two numbers, which must be `Int`, `Uint` or non NaN `F64` values
+///
+/// Integers are compared exactly, only a comparison with a float goes through `f64`. Equal
+/// numbers of different types are told apart by the type so no two different values are equal
+/// and the order of the values passed to [`MergeStrategy::decide`] never matters.
+fn compare(a: &ScalarValue, b: &ScalarValue) -> Ordering {
+    fn number(value: &ScalarValue) -> (Option<i128>, f64, u8) {
+        match value {
+            ScalarValue::Int(n) => (Some(*n as i128), *n as f64, 0),
+            ScalarValue::Uint(n) => (Some(*n as i128), *n as f64, 1),
+            ScalarValue::F64(n) => (None, *n, 2),
+            _ => unreachable!("only numbers are compared"),
+        }
+    }
+    let (a_int, a_float, a_rank) = number(a);
+    let (b_int, b_float, b_rank) = number(b);
+    let ordering = match (a_int, b_int) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        // neither is NaN
+        _ => a_float.partial_cmp(&b_float).unwrap_or(Ordering::Equal),
+    };
+    ordering.then(a_rank.cmp(&b_rank))
+}
+
+/// A set of rules for settling conflicts left by a merge, used by
+/// [`Automerge::merge_with_strategies`](crate::Automerge::merge_with_strategies)
+///
+/// Each rule applies a [`MergeStrategy`] to the properties of maps of one [`ObjType`] whose name
+/// matches a pattern, where `*` in the pattern matches any run of characters. Only map and
+/// table objects have property names so rules for other object types never match. If more than
+/// one rule matches a property the one registered first is used.
+///
+/// ```
+/// # use automerge::{MergeStrategies, MergeStrategy, ObjType};
+/// let mut strategies = MergeStrategies::new();
+/// strategies.register(ObjType::Map, "version", MergeStrategy::Max);
+/// strategies.register(ObjType::Map, "*_min", MergeStrategy::Min);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MergeStrategies {
+    rules: Vec<(ObjType, String, MergeStrategy)>,
+}
+
+impl MergeStrategies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `strategy` for properties of objects of type `obj_type` whose name matches `pattern`
+    pub fn register<S: Into<String>>(
+        &mut self,
+        obj_type: ObjType,
+        pattern: S,
+        strategy: MergeStrategy,
+    ) -> &mut Self {
+        self.rules.push((obj_type, pattern.into(), strategy));
+        self
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The object types which have rules, each only once
+    pub(crate) fn obj_types(&self) -> Vec<ObjType> {
+        let mut types = Vec::new();
+        for (obj_type, _, _) in &self.rules {
+            if !types.contains(obj_type) {
+                types.push(*obj_type);
+            }
+        }
+        types
+    }
+
+    pub(crate) fn find(&self, obj_type: ObjType, key: &str) -> Option<MergeStrategy> {
+        self.rules
+            .iter()
+            .find(|(t, pattern, _)| *t == obj_type && matches(pattern, key))
+            .map(|(_, _, strategy)| *strategy)
+    }
+}
+
+/// Whether `key` matches `pattern`, where `*` matches any run of characters
+fn matches(pattern: &str, key: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one part
+    let first = parts.next().unwrap();
+    let mut rest = match key.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let (last, middle) = match parts.split_last() {
+        Some(split) => split,
+        None => return rest.is_empty(),
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_matching() {
+        assert!(matches("version", "version"));
+        assert!(!matches("version", "versions"));
+        assert!(matches("*", ""));
+        assert!(matches("*_max", "score_max"));
+        assert!(!matches("*_max", "score_min"));
+        assert!(matches("a*b*c", "abc"));
+        assert!(matches("a*b*c", "a_b_b_c"));
+        assert!(!matches("a*b*c", "a_c"));
+        // the end can't reuse characters the start matched
+        assert!(!matches("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn decide_ignores_order() {
+        let values = [
+            ScalarValue::Int(3),
+            ScalarValue::Uint(7),
+            ScalarValue::F64(-1.5),
+        ];
+        let forward = values.iter().collect::<Vec<_>>();
+        let backward = values.iter().rev().collect::<Vec<_>>();
+        for strategy in [MergeStrategy::Max, MergeStrategy::Min] {
+            assert_eq!(strategy.decide(&forward), strategy.decide(&backward));
+        }
+        assert_eq!(MergeStrategy::Max.decide(&forward), Some(&values[1]));
+        assert_eq!(MergeStrategy::Min.decide(&forward), Some(&values[2]));
+        // integers past 2^53 are still told apart
+        let big = [
+            ScalarValue::Int(1 << 53),
+            ScalarValue::Int((1 << 53) + 1),
+            ScalarValue::Uint((1 << 53) + 1),
+        ];
+        let forward = big.iter().collect::<Vec<_>>();
+        let backward = big.iter().rev().collect::<Vec<_>>();
+        for strategy in [MergeStrategy::Max, MergeStrategy::Min] {
+            assert_eq!(strategy.decide(&forward), strategy.decide(&backward));
+        }
+        assert_eq!(MergeStrategy::Max.decide(&forward), Some(&big[2]));
+        assert_eq!(MergeStrategy::Min.decide(&backward), Some(&big[0]));
+        let strings = [ScalarValue::Int(1), ScalarValue::from("x")];
+        assert_eq!(
+            MergeStrategy::Max.decide(&strings.iter().collect::<Vec<_>>()),
+            None
+        );
+    }
+}
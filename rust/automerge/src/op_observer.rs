@@ -7,10 +7,12 @@ use crate::Value;
 
 mod compose;
 mod patch;
+mod subtree_observer;
 mod toggle_observer;
 mod vec_observer;
 pub use compose::compose;
 pub use patch::{Patch, PatchAction};
+pub use subtree_observer::SubtreeObserver;
 pub use toggle_observer::ToggleObserver;
 pub use vec_observer::{HasPatches, TextRepresentation, VecOpObserver, VecOpObserver16};
 
@@ -0,0 +1,228 @@
+use crate::{marks::Mark, ObjId, OpObserver, Prop, ReadDoc, Value};
+
+use crate::op_observer::BranchableObserver;
+use crate::op_observer::{HasPatches, TextRepresentation};
+
+/// An observer which only passes on changes to one object and its descendants
+///
+/// Every change is forwarded to the wrapped observer if the object it was made in is the watched
+/// object or has the watched object as an ancestor, all other changes are dropped.
+///
+/// If the watched object is removed from the document, either because it is deleted or because
+/// it is overwritten, the change to its parent which removed it is passed on and after that no
+/// more changes are forwarded.
+///
+/// This is created with [`crate::Automerge::observe_subtree`].
+#[derive(Debug, Clone)]
+pub struct SubtreeObserver<T> {
+    root: ObjId,
+    removed: bool,
+    observer: T,
+}
+
+impl<T> SubtreeObserver<T> {
+    pub(crate) fn new(root: ObjId, observer: T) -> Self {
+        SubtreeObserver {
+            root,
+            removed: false,
+            observer,
+        }
+    }
+
+    /// The object being watched
+    pub fn root(&self) -> &ObjId {
+        &self.root
+    }
+
+    /// Whether the watched object has been removed from the document
+    pub fn removed(&self) -> bool {
+        self.removed
+    }
+
+    /// The wrapped observer
+    pub fn observer(&mut self) -> &mut T {
+        &mut self.observer
+    }
+
+    /// Take the wrapped observer out of this one
+    pub fn into_inner(self) -> T {
+        self.observer
+    }
+
+    /// Whether a change to `obj` should be passed on to the wrapped observer
+    fn in_subtree<R: ReadDoc>(&self, doc: &R, obj: &ObjId) -> bool {
+        if self.removed {
+            return false;
+        }
+        *obj == self.root
+            || doc
+                .parents(obj)
+                .map(|mut parents| parents.any(|p| p.obj == self.root))
+                .unwrap_or(false)
+    }
+
+    /// Whether a change to the properties of `obj` for which `affected` returns true removes the
+    /// watched object from the document. If it does then the change should be passed on and
+    /// nothing after it.
+    fn removes_root<R: ReadDoc, F: Fn(&Prop) -> bool>(
+        &mut self,
+        doc: &R,
+        obj: &ObjId,
+        affected: F,
+    ) -> bool {
+        if self.removed {
+            return false;
+        }
+        self.removed = doc
+            .parents(&self.root)
+            .map(|mut parents| parents.any(|p| p.obj == *obj && affected(&p.prop)))
+            .unwrap_or(false);
+        self.removed
+    }
+}
+
+impl<T: OpObserver> OpObserver for SubtreeObserver<T> {
+    fn insert<R: ReadDoc>(
+        &mut self,
+        doc: &R,
+        obj: ObjId,
+        index: usize,
+        tagged_value: (Value<'_>, ObjId),
+        conflict: bool,
+    ) {
+        if self.in_subtree(doc, &obj) {
+            self.observer
+                .insert(doc, obj, index, tagged_value, conflict)
+        }
+    }
+
+    fn splice_text<R: ReadDoc>(&mut self, doc: &R, obj: ObjId, index: usize, value: &str) {
+        if self.in_subtree(doc, &obj) {
+            self.observer.splice_text(doc, obj, index, value)
+        }
+    }
+
+    fn delete_seq<R: ReadDoc>(&mut self, doc: &R, obj: ObjId, index: usize, length: usize) {
+        if self.in_subtree(doc, &obj)
+            || self.removes_root(
+                doc,
+                &obj,
+                |p| matches!(p, Prop::Seq(i) if (index..index + length).contains(i)),
+            )
+        {
+            self.observer.delete_seq(doc, obj, index, length)
+        }
+    }
+
+    fn delete_map<R: ReadDoc>(&mut self, doc: &R, obj: ObjId, key: &str) {
+        if self.in_subtree(doc, &obj)
+            || self.removes_root(doc, &obj, |p| matches!(p, Prop::Map(k) if k == key))
+        {
+            self.observer.delete_map(doc, obj, key)
+        }
+    }
+
+    fn put<R: ReadDoc>(
+        &mut self,
+        doc: &R,
+        obj: ObjId,
+        prop: Prop,
+        tagged_value: (Value<'_>, ObjId),
+        conflict: bool,
+    ) {
+        let overwrites_root = tagged_value.1 != self.root;
+        if self.in_subtree(doc, &obj)
+            || (overwrites_root && self.removes_root(doc, &obj, |p| *p == prop))
+        {
+            self.observer.put(doc, obj, prop, tagged_value, conflict)
+        }
+    }
+
+    fn expose<R: ReadDoc>(
+        &mut self,
+        doc: &R,
+        obj: ObjId,
+        prop: Prop,
+        tagged_value: (Value<'_>, ObjId),
+        conflict: bool,
+    ) {
+        if self.in_subtree(doc, &obj) || self.removes_root(doc, &obj, |p| *p == prop) {
+            self.observer.expose(doc, obj, prop, tagged_value, conflict)
+        }
+    }
+
+    fn flag_conflict<R: ReadDoc>(&mut self, doc: &R, obj: ObjId, prop: Prop) {
+        if self.in_subtree(doc, &obj) {
+            self.observer.flag_conflict(doc, obj, prop)
+        }
+    }
+
+    fn increment<R: ReadDoc>(
+        &mut self,
+        doc: &R,
+        obj: ObjId,
+        prop: Prop,
+        tagged_value: (i64, ObjId),
+    ) {
+        if self.in_subtree(doc, &obj) {
+            self.observer.increment(doc, obj, prop, tagged_value)
+        }
+    }
+
+    fn mark<'a, R: ReadDoc, M: Iterator<Item = Mark<'a>>>(
+        &mut self,
+        doc: &'a R,
+        obj: ObjId,
+        mark: M,
+    ) {
+        if self.in_subtree(doc, &obj) {
+            self.observer.mark(doc, obj, mark)
+        }
+    }
+
+    fn unmark<R: ReadDoc>(&mut self, doc: &R, obj: ObjId, name: &str, start: usize, end: usize) {
+        if self.in_subtree(doc, &obj) {
+            self.observer.unmark(doc, obj, name, start, end)
+        }
+    }
+
+    fn text_as_seq(&self) -> bool {
+        self.observer.text_as_seq()
+    }
+}
+
+impl<T: BranchableObserver> BranchableObserver for SubtreeObserver<T> {
+    fn merge(&mut self, other: &Self) {
+        self.observer.merge(&other.observer);
+        self.removed |= other.removed;
+    }
+
+    fn branch(&self) -> Self {
+        SubtreeObserver {
+            root: self.root.clone(),
+            removed: self.removed,
+            observer: self.observer.branch(),
+        }
+    }
+}
+
+impl<T: HasPatches> HasPatches for SubtreeObserver<T> {
+    type Patches = T::Patches;
+
+    fn take_patches(&mut self) -> Self::Patches {
+        self.observer.take_patches()
+    }
+
+    fn with_text_rep(mut self, text_rep: TextRepresentation) -> Self {
+        self.observer = self.observer.with_text_rep(text_rep);
+        self
+    }
+
+    fn set_text_rep(&mut self, text_rep: TextRepresentation) {
+        self.observer.set_text_rep(text_rep)
+    }
+
+    fn get_text_rep(&self) -> TextRepresentation {
+        self.observer.get_text_rep()
+    }
+}
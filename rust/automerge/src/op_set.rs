@@ -1,15 +1,17 @@
 use crate::clock::Clock;
+use crate::error::IntegrityError;
 use crate::exid::ExId;
 use crate::indexed_cache::IndexedCache;
 use crate::op_tree::{self, OpTree};
 use crate::parents::Parents;
 use crate::query::{self, OpIdVisSearch, TreeQuery};
 use crate::types::{self, ActorId, Key, ListEncoding, ObjId, Op, OpId, OpIds, OpType, Prop};
-use crate::ObjType;
+use crate::{ObjType, ScalarValue};
 use fxhash::FxBuildHasher;
+use smol_str::SmolStr;
 use std::borrow::Borrow;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::RangeBounds;
 
 mod load;
@@ -25,6 +27,8 @@ pub(crate) struct OpSetInternal {
     length: usize,
     /// Metadata about the operations in this opset.
     pub(crate) m: OpSetMetadata,
+    /// The strings seen so far if string interning is enabled, see `set_interning`
+    strings: Option<HashSet<SmolStr>>,
 }
 
 impl OpSetInternal {
@@ -42,9 +46,31 @@ impl OpSetInternal {
                 actors: IndexedCache::new(),
                 props: IndexedCache::new(),
             },
+            strings: None,
         }
     }
 
+    /// Make every string value which is stored on the heap share its storage with any other
+    /// string value with the same contents, both for the ops already in the opset and any which
+    /// are inserted later.
+    pub(crate) fn set_interning(&mut self, enabled: bool) {
+        if !enabled {
+            self.strings = None;
+        } else if self.strings.is_none() {
+            let mut strings = HashSet::new();
+            for tree in self.trees.values_mut() {
+                for op in tree.internal.ops.iter_mut() {
+                    intern(&mut strings, op);
+                }
+            }
+            self.strings = Some(strings);
+        }
+    }
+
+    pub(crate) fn interning(&self) -> bool {
+        self.strings.is_some()
+    }
+
     pub(crate) fn id_to_exid(&self, id: OpId) -> ExId {
         if id == types::ROOT {
             ExId::Root
@@ -82,10 +108,41 @@ impl OpSetInternal {
         self.trees.get(obj).map(|o| o.iter()).into_iter().flatten()
     }
 
+    /// Like [`Self::iter_ops`] but skipping the first `pos` ops of the object without visiting
+    /// them
+    pub(crate) fn iter_ops_from(&self, obj: &ObjId, pos: usize) -> impl Iterator<Item = &Op> {
+        self.trees
+            .get(obj)
+            .map(|o| {
+                let mut iter = o.iter();
+                if pos > 0 {
+                    iter.nth(pos - 1);
+                }
+                iter
+            })
+            .into_iter()
+            .flatten()
+    }
+
     pub(crate) fn parents(&self, obj: ObjId) -> Parents<'_> {
         Parents { obj, ops: self }
     }
 
+    /// `obj` and every object which was ever created inside it, including deleted ones
+    pub(crate) fn descendants(&self, obj: &ObjId) -> Vec<ObjId> {
+        let mut found = Vec::new();
+        let mut stack = vec![*obj];
+        while let Some(current) = stack.pop() {
+            stack.extend(
+                self.iter_ops(&current)
+                    .filter(|op| matches!(op.action, OpType::Make(_)))
+                    .map(|op| ObjId(op.id)),
+            );
+            found.push(current);
+        }
+        found
+    }
+
     pub(crate) fn parent_object(&self, obj: &ObjId) -> Option<Parent> {
         let parent = self.trees.get(obj)?.parent?;
         let query = self.search(&parent, OpIdVisSearch::new(obj.0));
@@ -239,7 +296,10 @@ impl OpSetInternal {
     }
 
     #[tracing::instrument(skip(self, index))]
-    pub(crate) fn insert(&mut self, index: usize, obj: &ObjId, element: Op) {
+    pub(crate) fn insert(&mut self, index: usize, obj: &ObjId, mut element: Op) {
+        if let Some(strings) = &mut self.strings {
+            intern(strings, &mut element);
+        }
         if let OpType::Make(typ) = element.action {
             self.trees.insert(
                 element.id.into(),
@@ -265,6 +325,72 @@ impl OpSetInternal {
         self.trees.get(id).map(|tree| tree.objtype)
     }
 
+    /// Check that every object is created by an op in its parent object and that the `succ` and
+    /// `pred` of every op refer to each other.
+    ///
+    /// Objects are checked in causal order and ops in the order they appear in the object so the
+    /// first violation reported is the same for any two opsets containing the same ops.
+    pub(crate) fn verify_integrity(&self) -> Result<(), IntegrityError> {
+        let mut created_in = HashMap::new();
+        for (obj, _, ops) in self.iter_objs() {
+            for op in ops {
+                if let OpType::Make(_) = op.action {
+                    created_in.insert(ObjId(op.id), *obj);
+                }
+            }
+        }
+
+        for (obj, _, ops) in self.iter_objs() {
+            let tree = &self.trees[obj];
+            if obj.is_root() {
+                if let Some(parent) = tree.parent {
+                    return Err(IntegrityError::RootHasParent {
+                        parent: self.id_to_exid(parent.0),
+                    });
+                }
+            } else {
+                let parent = tree.parent.ok_or_else(|| IntegrityError::MissingParent {
+                    obj: self.id_to_exid(obj.0),
+                })?;
+                if created_in.get(obj) != Some(&parent) {
+                    return Err(IntegrityError::NotCreatedInParent {
+                        obj: self.id_to_exid(obj.0),
+                        parent: self.id_to_exid(parent.0),
+                    });
+                }
+            }
+
+            let by_id: HashMap<OpId, &Op> = ops.map(|op| (op.id, op)).collect();
+            for op in tree.iter() {
+                for pred in &op.pred {
+                    let pred_op = by_id.get(pred).ok_or_else(|| IntegrityError::MissingPred {
+                        op: self.id_to_exid(op.id),
+                        pred: self.id_to_exid(*pred),
+                    })?;
+                    if !pred_op.succ.contains(&op.id) {
+                        return Err(IntegrityError::PredWithoutSucc {
+                            op: self.id_to_exid(op.id),
+                            pred: self.id_to_exid(*pred),
+                        });
+                    }
+                }
+                // delete ops are not stored in the opset, so we can only check the successors
+                // which are
+                for succ in &op.succ {
+                    if let Some(succ_op) = by_id.get(succ) {
+                        if !succ_op.pred.contains(&op.id) {
+                            return Err(IntegrityError::SuccWithoutPred {
+                                op: self.id_to_exid(op.id),
+                                succ: self.id_to_exid(*succ),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Return a graphviz representation of the opset.
     ///
     /// # Arguments
@@ -288,6 +414,21 @@ impl OpSetInternal {
     }
 }
 
+/// Replace the string in `op` with an equal one from `strings`, or add it to `strings` if there
+/// isn't one. Strings short enough to be stored inline in the `SmolStr` are left alone, they
+/// wouldn't save anything.
+fn intern(strings: &mut HashSet<SmolStr>, op: &mut Op) {
+    if let OpType::Put(ScalarValue::Str(s)) = &mut op.action {
+        if s.is_heap_allocated() {
+            if let Some(existing) = strings.get(s) {
+                *s = existing.clone();
+            } else {
+                strings.insert(s.clone());
+            }
+        }
+    }
+}
+
 impl Default for OpSetInternal {
     fn default() -> Self {
         Self::new()
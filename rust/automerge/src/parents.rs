@@ -1,6 +1,6 @@
 use crate::op_set;
 use crate::op_set::OpSet;
-use crate::types::{ListEncoding, ObjId};
+use crate::types::{Key, ListEncoding, ObjId};
 use crate::{exid::ExId, Prop};
 
 /// An iterator over the "parents" of an object
@@ -21,6 +21,11 @@ impl<'a> Parents<'a> {
     /// Return the path this `Parents` represents
     ///
     /// This is _not_ in reverse order.
+    ///
+    /// The index in each [`Prop::Seq`] is the index the element has in this version of the
+    /// document. Once concurrent changes which insert or delete elements before it are merged the
+    /// element moves, so the index goes stale. Use [`Self::stable_path`] for a path which still
+    /// identifies the same elements after that.
     pub fn path(self) -> Vec<(ExId, Prop)> {
         let mut path = self
             .map(|Parent { obj, prop, .. }| (obj, prop))
@@ -29,6 +34,26 @@ impl<'a> Parents<'a> {
         path
     }
 
+    /// Like [`Self::path`] but list elements are identified by the ID of the operation which
+    /// inserted them as well as by their current index
+    ///
+    /// This is _not_ in reverse order.
+    pub fn stable_path(mut self) -> Vec<(ExId, StableProp)> {
+        let mut path = Vec::new();
+        while let Some(op_set::Parent { obj, key, .. }) = self.next_parent() {
+            let prop = match key {
+                Key::Map(prop) => StableProp::Map(self.ops.m.props.get(prop).to_string()),
+                Key::Seq(elem) => StableProp::Seq {
+                    elem: self.ops.id_to_exid(elem.0),
+                    index: self.export_key(obj, key).to_index().unwrap(),
+                },
+            };
+            path.push((self.ops.id_to_exid(obj.0), prop));
+        }
+        path.reverse();
+        path
+    }
+
     /// Like `path` but returns `None` if the target is not visible
     pub fn visible_path(self) -> Option<Vec<(ExId, Prop)>> {
         let mut path = Vec::new();
@@ -41,28 +66,32 @@ impl<'a> Parents<'a> {
         path.reverse();
         Some(path)
     }
+
+    fn next_parent(&mut self) -> Option<op_set::Parent> {
+        if self.obj.is_root() {
+            None
+        } else {
+            let parent = self.ops.parent_object(&self.obj)?;
+            self.obj = parent.obj;
+            Some(parent)
+        }
+    }
+
+    fn export_key(&self, obj: ObjId, key: Key) -> Prop {
+        self.ops.export_key(obj, key, ListEncoding::List).unwrap()
+    }
 }
 
 impl<'a> Iterator for Parents<'a> {
     type Item = Parent;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.obj.is_root() {
-            None
-        } else if let Some(op_set::Parent { obj, key, visible }) = self.ops.parent_object(&self.obj)
-        {
-            self.obj = obj;
-            Some(Parent {
-                obj: self.ops.id_to_exid(self.obj.0),
-                prop: self
-                    .ops
-                    .export_key(self.obj, key, ListEncoding::List)
-                    .unwrap(),
-                visible,
-            })
-        } else {
-            None
-        }
+        let op_set::Parent { obj, key, visible } = self.next_parent()?;
+        Some(Parent {
+            obj: self.ops.id_to_exid(obj.0),
+            prop: self.export_key(obj, key),
+            visible,
+        })
     }
 }
 
@@ -81,9 +110,23 @@ pub struct Parent {
     pub visible: bool,
 }
 
+/// A property in a path returned by [`Parents::stable_path`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StableProp {
+    /// A key in a map
+    Map(String),
+    /// An element of a list or text object
+    Seq {
+        /// The ID of the operation which inserted the element, this doesn't change
+        elem: ExId,
+        /// The index of the element when the path was created
+        index: usize,
+    },
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Parent;
+    use super::{Parent, StableProp};
     use crate::{transaction::Transactable, Prop, ReadDoc};
 
     #[test]
@@ -118,4 +161,47 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn stable_path_survives_concurrent_inserts() {
+        let mut doc1 = crate::AutoCommit::new();
+        let list = doc1
+            .put_object(crate::ROOT, "list", crate::ObjType::List)
+            .unwrap();
+        doc1.insert(&list, 0, "a").unwrap();
+        let obj = doc1.insert_object(&list, 1, crate::ObjType::Map).unwrap();
+        let mut doc2 = doc1.fork();
+
+        let path = doc1.parents(&obj).unwrap().stable_path();
+        assert_eq!(
+            path,
+            vec![
+                (crate::ROOT, StableProp::Map("list".to_string())),
+                (
+                    list.clone(),
+                    StableProp::Seq {
+                        elem: obj.clone(),
+                        index: 1
+                    }
+                ),
+            ]
+        );
+
+        // an insert before the element moves it, the index in the old path goes stale but the
+        // element ID doesn't change
+        doc2.insert(&list, 0, "b").unwrap();
+        doc1.merge(&mut doc2).unwrap();
+        let moved = doc1.parents(&obj).unwrap().stable_path();
+        assert_eq!(
+            moved[1],
+            (
+                list.clone(),
+                StableProp::Seq {
+                    elem: obj.clone(),
+                    index: 2
+                }
+            )
+        );
+        assert_eq!(doc1.parents(&obj).unwrap().path()[1], (list, Prop::Seq(2)));
+    }
 }
@@ -19,6 +19,7 @@ mod list_vals;
 mod list_vals_at;
 mod map_range;
 mod map_range_at;
+mod mark_ops;
 mod nth;
 mod nth_at;
 mod opid;
@@ -41,6 +42,7 @@ pub(crate) use list_vals::ListVals;
 pub(crate) use list_vals_at::ListValsAt;
 pub(crate) use map_range::MapRange;
 pub(crate) use map_range_at::MapRangeAt;
+pub(crate) use mark_ops::MarkOps;
 pub(crate) use nth::Nth;
 pub(crate) use nth_at::NthAt;
 pub(crate) use opid::OpIdSearch;
@@ -170,6 +172,8 @@ pub(crate) struct Index {
     visible_text: TextWidth,
     /// Set of opids found in this node and below.
     ops: HashSet<OpId, FxBuildHasher>,
+    /// The number of mark operations in this node and below.
+    marks: usize,
 }
 
 impl Index {
@@ -178,6 +182,7 @@ impl Index {
             visible: Default::default(),
             visible_text: TextWidth { utf8: 0, utf16: 0 },
             ops: Default::default(),
+            marks: 0,
         }
     }
 
@@ -199,6 +204,11 @@ impl Index {
         self.ops.contains(opid)
     }
 
+    /// Whether there are any mark operations in this node or below it
+    pub(crate) fn has_marks(&self) -> bool {
+        self.marks > 0
+    }
+
     pub(crate) fn change_vis<'a>(
         &mut self,
         change_vis: ChangeVisibility<'a>,
@@ -235,6 +245,9 @@ impl Index {
 
     pub(crate) fn insert(&mut self, op: &Op) {
         self.ops.insert(op.id);
+        if op.is_mark() {
+            self.marks += 1;
+        }
         if op.visible() {
             let key = op.elemid_or_key();
             if let Some(n) = self.visible.get(&key) {
@@ -248,6 +261,9 @@ impl Index {
 
     pub(crate) fn remove(&mut self, op: &Op) {
         self.ops.remove(&op.id);
+        if op.is_mark() {
+            self.marks -= 1;
+        }
         if op.visible() {
             let key = op.elemid_or_key();
             match self.visible.get(&key).copied() {
@@ -274,6 +290,7 @@ impl Index {
                 .or_insert(*other_len);
         }
         self.visible_text.merge(&other.visible_text);
+        self.marks += other.marks;
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::error::AutomergeError;
+use crate::marks::ExpandMark;
 use crate::op_tree::OpTreeNode;
 use crate::query::{OpTree, QueryResult, TreeQuery};
 use crate::types::{ElemId, Key, ListEncoding, Op, HEAD};
@@ -21,6 +22,8 @@ pub(crate) struct InsertNth {
     last_seen: Option<Key>,
     last_insert: Option<ElemId>,
     last_valid_insert: Option<Key>,
+    /// If set the marks at `target` are treated as if they were created with this
+    expand: Option<ExpandMark>,
 }
 
 impl InsertNth {
@@ -40,9 +43,15 @@ impl InsertNth {
             last_seen: None,
             last_insert: None,
             last_valid_insert,
+            expand: None,
         }
     }
 
+    pub(crate) fn with_expand(mut self, expand: Option<ExpandMark>) -> Self {
+        self.expand = expand;
+        self
+    }
+
     pub(crate) fn pos(&self) -> usize {
         self.valid.unwrap_or(self.n)
     }
@@ -59,6 +68,10 @@ impl<'a> TreeQuery<'a> for InsertNth {
     }
 
     fn can_shortcut_search(&mut self, tree: &'a OpTree) -> bool {
+        // the shortcut doesn't look at the marks after the last insert
+        if self.expand.is_some() {
+            return false;
+        }
         if let Some((index, pos)) = &tree.last_insert {
             if let Some(op) = tree.internal.get(*pos) {
                 if *index + op.width(self.encoding) == self.target {
@@ -113,7 +126,11 @@ impl<'a> TreeQuery<'a> for InsertNth {
             self.last_insert = element.elemid();
         }
         /*-------------------*/
-        if self.valid.is_some() && element.valid_mark_anchor() {
+        let valid_anchor = match self.expand {
+            Some(expand) => element.valid_mark_anchor_with(expand),
+            None => element.valid_mark_anchor(),
+        };
+        if self.valid.is_some() && valid_anchor {
             self.last_valid_insert = Some(element.elemid_or_key());
             self.valid = None;
         }
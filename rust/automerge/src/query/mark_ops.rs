@@ -0,0 +1,94 @@
+use crate::op_tree::OpTreeNode;
+use crate::query::{QueryResult, TreeQuery};
+use crate::types::{Key, ListEncoding, Op};
+use std::fmt::Debug;
+
+/// Find the mark operations in a sequence and the index each of them is at.
+///
+/// Nodes which don't contain any mark operations are skipped using the visible length in their
+/// index, so the cost of this depends on the number of marks rather than the length of the
+/// sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MarkOps<'a> {
+    encoding: ListEncoding,
+    seen: usize,
+    last_width: usize,
+    /// The key of the last element counted in `seen`, see [`super::Nth`]
+    last_seen: Option<Key>,
+    /// Stop after the mark operations at this index, see [`Self::until`]
+    end: Option<usize>,
+    pub(crate) ops: Vec<(usize, &'a Op)>,
+}
+
+impl<'a> MarkOps<'a> {
+    pub(crate) fn new(encoding: ListEncoding) -> Self {
+        MarkOps {
+            encoding,
+            seen: 0,
+            last_width: 0,
+            last_seen: None,
+            end: None,
+            ops: Vec::new(),
+        }
+    }
+
+    /// Only find the mark operations at indexes up to and including `end`, the rest of the
+    /// sequence isn't searched
+    pub(crate) fn until(encoding: ListEncoding, end: usize) -> Self {
+        MarkOps {
+            end: Some(end),
+            ..Self::new(encoding)
+        }
+    }
+
+    fn past_end(&self) -> bool {
+        self.end.map_or(false, |end| self.seen > end)
+    }
+}
+
+impl<'a> TreeQuery<'a> for MarkOps<'a> {
+    fn query_node(&mut self, child: &OpTreeNode, ops: &[Op]) -> QueryResult {
+        if child.index.has_marks() {
+            return QueryResult::Descend;
+        }
+        let mut num_vis = child.index.visible_len(self.encoding);
+        // a visible element split across nodes has already been counted
+        if let Some(last_seen) = self.last_seen {
+            if child.index.has_visible(&last_seen) {
+                num_vis -= self.last_width;
+            }
+        }
+        self.seen += num_vis;
+        let last_elemid = ops[child.last()].elemid_or_key();
+        if child.index.has_visible(&last_elemid) {
+            if Some(last_elemid) != self.last_seen {
+                self.last_width = ops[child.last()].width(self.encoding);
+            }
+            self.last_seen = Some(last_elemid);
+        } else if self.last_seen.is_some() && Some(last_elemid) != self.last_seen {
+            self.last_seen = None;
+        }
+        if self.past_end() {
+            return QueryResult::Finish;
+        }
+        QueryResult::Next
+    }
+
+    fn query_element(&mut self, element: &'a Op) -> QueryResult {
+        if element.insert {
+            self.last_seen = None;
+        }
+        if element.visible() && self.last_seen.is_none() {
+            self.last_width = element.width(self.encoding);
+            self.seen += self.last_width;
+            self.last_seen = Some(element.elemid_or_key());
+        }
+        if element.is_mark() && element.visible_or_mark() {
+            if self.past_end() {
+                return QueryResult::Finish;
+            }
+            self.ops.push((self.seen, element));
+        }
+        QueryResult::Next
+    }
+}
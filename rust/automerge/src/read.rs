@@ -1,9 +1,21 @@
+use crate::walk::{self, SubtreeStats, TreeVisitor};
 use crate::{
-    error::AutomergeError, exid::ExId, keys::Keys, keys_at::KeysAt, list_range::ListRange,
-    list_range_at::ListRangeAt, map_range::MapRange, map_range_at::MapRangeAt, marks::Mark,
-    parents::Parents, values::Values, Change, ChangeHash, ObjType, Prop, Value,
+    error::AutomergeError,
+    exid::ExId,
+    keys::Keys,
+    keys_at::KeysAt,
+    list_range::ListRange,
+    list_range_at::ListRangeAt,
+    map_range::MapRange,
+    map_range_at::MapRangeAt,
+    marks::{self, Mark, MarkSpan},
+    parents::Parents,
+    values::Values,
+    ActorId, Change, ChangeHash, FromValue, LineEnding, ObjType, Prop, ScalarValue, Value,
+    ValueType,
 };
 
+use std::collections::BTreeMap;
 use std::ops::RangeBounds;
 
 /// Methods for reading values from an automerge document
@@ -42,6 +54,10 @@ pub trait ReadDoc {
     ///
     /// For a map this returns the keys of the map.
     /// For a list this returns the element ids (opids) encoded as strings.
+    ///
+    /// Map keys are kept in sorted order by the document itself, so they come out sorted without
+    /// any sorting being done. The iterator is lazy, each key is only looked up when it is
+    /// reached.
     fn keys<O: AsRef<ExId>>(&self, obj: O) -> Keys<'_, '_>;
 
     /// Get the keys of the object `obj` as at `heads`
@@ -100,6 +116,24 @@ pub trait ReadDoc {
         heads: &[ChangeHash],
     ) -> ListRangeAt<'_, R>;
 
+    /// Get every key in a map along with the type of the value at that key
+    ///
+    /// Nested objects are not read, only their [`ObjType`] is returned. Returns
+    /// [`AutomergeError::InvalidOp`] if `obj` is not a map or table.
+    fn entries_typed<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<Vec<(String, ValueType)>, AutomergeError>;
+
+    /// Get every key in a map along with the type of the value at that key as at `heads`
+    ///
+    /// See [`Self::entries_typed`]
+    fn entries_typed_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Vec<(String, ValueType)>, AutomergeError>;
+
     /// Iterate over the values in a map, list, or text object
     ///
     /// The returned iterator yields `(value, exid)` tuples, where the second element
@@ -114,8 +148,37 @@ pub trait ReadDoc {
     /// See [`Self::values`]
     fn values_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> Values<'_>;
 
+    /// Visit `obj` and everything inside it with `visitor`
+    ///
+    /// Map keys are visited in sorted order and list elements in list order, nested objects are
+    /// visited depth first as they are reached.
+    fn walk<O: AsRef<ExId>, V: TreeVisitor>(
+        &self,
+        obj: O,
+        visitor: &mut V,
+    ) -> Result<(), AutomergeError> {
+        let obj = obj.as_ref();
+        let obj_type = self.object_type(obj)?;
+        walk::walk_object(self, None, obj, obj_type, visitor)
+    }
+
+    /// Count the values and objects in `obj` and everything inside it and estimate how many
+    /// bytes they take up, see [`SubtreeStats`]
+    ///
+    /// This only reads the objects in the subtree, so it can be used to decide whether an object
+    /// is worth reading out of a large document.
+    fn subtree_stats<O: AsRef<ExId>>(&self, obj: O) -> Result<SubtreeStats, AutomergeError> {
+        let mut stats = SubtreeStats::default();
+        self.walk(obj, &mut stats)?;
+        Ok(stats)
+    }
+
     /// Get the length of the given object.
     ///
+    /// For lists and text this is read from the index of the object's op tree, which is kept up
+    /// to date as ops are inserted and deleted, so it doesn't scan the sequence. For maps the
+    /// keys are counted.
+    ///
     /// If the given object is not in this document this method will return `0`
     fn length<O: AsRef<ExId>>(&self, obj: O) -> usize;
 
@@ -126,12 +189,63 @@ pub trait ReadDoc {
     /// See [`Self::length`]
     fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize;
 
+    /// Get the length of the text object `obj` in UTF-16 code units
+    ///
+    /// [`Self::length`] counts in the [`TextEncoding`](crate::TextEncoding) the document was
+    /// created with, this is the length in UTF-16 whatever that is, which is what indexes into
+    /// a JavaScript string count. Like [`Self::length`] it is read from the index of the op tree
+    /// rather than by scanning the text. Returns [`AutomergeError::InvalidOp`] if `obj` is not
+    /// text.
+    fn text_length_utf16<O: AsRef<ExId>>(&self, obj: O) -> Result<usize, AutomergeError>;
+
+    /// Get the length of the text object `obj` in unicode scalar values, i.e. Rust `char`s
+    ///
+    /// See [`Self::text_length_utf16`]. Neither this nor [`Self::length`] counts grapheme
+    /// clusters, an emoji with a skin tone modifier or a letter followed by a combining accent
+    /// is more than one `char`.
+    fn text_length_chars<O: AsRef<ExId>>(&self, obj: O) -> Result<usize, AutomergeError>;
+
     /// Get the type of this object, if it is an object.
     fn object_type<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjType, AutomergeError>;
 
+    /// Get every object of type `obj_type` in the document, ordered by the ID of the object
+    ///
+    /// Objects which have been deleted or overwritten, including ones inside them, are not
+    /// returned. The root is returned when `obj_type` is [`ObjType::Map`].
+    fn objects_of_type(&self, obj_type: ObjType) -> Vec<ExId>;
+
+    /// Get the index of the element `elem` in the sequence `obj`, as at `heads` if given
+    ///
+    /// `elem` is the ID of the operation which inserted the element, which is the ID returned
+    /// for it by [`Self::list_range`] unless it has been overwritten. As element IDs don't change
+    /// this can be used to find where an anchor stored earlier is now. Returns `None` if the
+    /// element has been deleted (or, with `heads`, had not been inserted yet) and
+    /// [`AutomergeError::InvalidElemId`] if `elem` is not an element of `obj` at all.
+    fn index_of<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        elem: &ExId,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Option<usize>, AutomergeError>;
+
     /// Get all marks on a current sequence
+    ///
+    /// Marks with the same name and value where one ends where the other starts are returned as
+    /// a single span if an element inserted between them would get the mark, which depends on the
+    /// [`ExpandMark`](crate::marks::ExpandMark) they were created with.
+    ///
+    /// The marks are sorted by `start`, then by `end`, then by name, so overlapping marks come
+    /// back in a stable order which can be used to paint them. All the other methods which
+    /// return a list of marks use the same order.
     fn marks<O: AsRef<ExId>>(&self, obj: O) -> Result<Vec<Mark<'_>>, AutomergeError>;
 
+    /// Whether a mark called `name` covers any part of the sequence `obj`
+    ///
+    /// Only the ops of marks called `name` are looked at, and it stops at the first element
+    /// which has the mark. Unlike [`Self::marks`] spans which cover nothing, such as those left
+    /// by [`Transactable::unmark`](crate::transaction::Transactable::unmark), don't count.
+    fn has_mark<O: AsRef<ExId>>(&self, obj: O, name: &str) -> Result<bool, AutomergeError>;
+
     /// Get all marks on a sequence at a given heads
     fn marks_at<O: AsRef<ExId>>(
         &self,
@@ -139,9 +253,138 @@ pub trait ReadDoc {
         heads: &[ChangeHash],
     ) -> Result<Vec<Mark<'_>>, AutomergeError>;
 
+    /// Get the marks on a sequence grouped by name, as `(start, end, value)` spans
+    ///
+    /// This is [`Self::marks`] with the spans of each name collected together. Conflicting marks
+    /// have already been resolved, so the spans of one name never overlap, and they are sorted
+    /// by position.
+    fn marks_by_name<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<BTreeMap<String, Vec<MarkSpan>>, AutomergeError> {
+        let mut result: BTreeMap<String, Vec<_>> = BTreeMap::new();
+        for mark in self.marks(obj)? {
+            result.entry(mark.name().to_string()).or_default().push((
+                mark.start,
+                mark.end,
+                mark.value().clone(),
+            ));
+        }
+        Ok(result)
+    }
+
+    /// Get the marks which overlap the range `start..end` of a sequence, clipped to that range
+    ///
+    /// A mark which starts before `start` is returned with `start` as its start and one which
+    /// ends after `end` with `end` as its end. Marks which only touch the range are not returned.
+    ///
+    /// The documents in this crate only look at the marks up to `end` to do this, the provided
+    /// implementation works out every mark of `obj` and clips them.
+    fn marks_in_range<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<Mark<'static>>, AutomergeError> {
+        let mut result = self
+            .marks(obj)?
+            .into_iter()
+            .filter(|m| m.start < end && start < m.end)
+            .map(|m| {
+                let mut m = m.into_owned();
+                m.start = m.start.max(start);
+                m.end = m.end.min(end);
+                m
+            })
+            .collect::<Vec<_>>();
+        // clipping can move marks which started before `start` out of order
+        marks::sort_marks(&mut result);
+        Ok(result)
+    }
+
+    /// Get the marks which would be on a sequence after a splice, without performing the splice.
+    ///
+    /// `del` elements are removed at `pos` and then `insert_len` elements are inserted there, all
+    /// counted in the same units as the positions returned by [`Self::marks`]. The [`ExpandMark`]
+    /// each mark was created with decides whether elements inserted at its start or end inherit
+    /// it, exactly as they would for a real splice.
+    ///
+    /// [`ExpandMark`]: crate::marks::ExpandMark
+    fn marks_after_splice<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        pos: usize,
+        del: usize,
+        insert_len: usize,
+    ) -> Result<Vec<Mark<'static>>, AutomergeError>;
+
+    /// Get the name and value of every mark covering the element at `index`.
+    ///
+    /// At `index == length` there is no element, instead this returns the marks an element
+    /// appended to the sequence would get, which depends on the [`ExpandMark`] of any marks
+    /// ending there. An `index` past the end returns [`AutomergeError::IndexOutOfBounds`].
+    ///
+    /// [`ExpandMark`]: crate::marks::ExpandMark
+    fn marks_at_index<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        index: usize,
+    ) -> Result<BTreeMap<String, ScalarValue>, AutomergeError>;
+
     /// Get the string represented by the given text object.
     fn text<O: AsRef<ExId>>(&self, obj: O) -> Result<String, AutomergeError>;
 
+    /// Get every element of the text object `obj`, including the ones which have been deleted
+    ///
+    /// This is an advanced API for tools such as diff algorithms which need to line up
+    /// positions across versions of the text. Each element is identified by the ID of the op
+    /// which inserted it, as used by [`Self::index_of`], and the elements are in sequence order
+    /// with deleted ones where they used to be. Which deleted elements are returned depends on
+    /// the history the document holds, e.g. none are left after
+    /// [`Automerge::flatten`](crate::Automerge::flatten). Returns [`AutomergeError::InvalidOp`] if
+    /// `obj` is not text.
+    fn text_with_tombstones<O: AsRef<ExId>>(&self, obj: O)
+        -> Result<Vec<TextCell>, AutomergeError>;
+
+    /// Get the part of the string represented by the given text object between `start` and
+    /// `end`, without building the whole string.
+    ///
+    /// Positions are in the same units as [`Self::length`]. `end` is clamped to the length of
+    /// the text and if `start` is not before `end` the result is empty. A character which is only
+    /// partly inside the range, which can happen with UTF-16 positions, is included whole.
+    fn text_slice<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        start: usize,
+        end: usize,
+    ) -> Result<String, AutomergeError>;
+
+    /// Get the string represented by the given text object with every line ending, whether
+    /// `\n`, `\r\n` or a lone `\r`, replaced by `eol`
+    ///
+    /// This only changes the returned string, the document is not modified so the element ids
+    /// and indexes of the text are the same as for [`Self::text`].
+    fn text_normalized<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        eol: LineEnding,
+    ) -> Result<String, AutomergeError> {
+        let text = self.text(obj)?;
+        let mut result = String::with_capacity(text.len());
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' => {
+                    chars.next_if_eq(&'\n');
+                    result.push_str(eol.as_str());
+                }
+                '\n' => result.push_str(eol.as_str()),
+                c => result.push(c),
+            }
+        }
+        Ok(result)
+    }
+
     /// Get the string represented by the given text object as at `heads`, see
     /// [`Self::text`]
     fn text_at<O: AsRef<ExId>>(
@@ -179,17 +422,112 @@ pub trait ReadDoc {
         heads: &[ChangeHash],
     ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError>;
 
+    /// Like [`Self::get`] but returns `Ok(None)` if `obj` is not in the document
+    ///
+    /// This is for optional lookups where a missing object is as good as a missing property, e.g.
+    /// following a path through the document which may not exist yet. Other errors, like using a
+    /// key with a list, are still returned.
+    fn try_value<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        match self.get(obj, prop) {
+            Err(AutomergeError::InvalidObj(_)) => Ok(None),
+            result => result,
+        }
+    }
+
     /// Get all conflicting values out of the document at this prop that conflict.
     ///
     /// If there are multiple conflicting values for a given key this method
     /// will return all of them, with each value tagged by the ID of the
     /// operation which created it.
+    ///
+    /// The first value is always the one [`Self::get`] returns, the rest are
+    /// in descending order of the ID of the operation which created them.
     fn get_all<O: AsRef<ExId>, P: Into<Prop>>(
         &self,
         obj: O,
         prop: P,
     ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError>;
 
+    /// Get the value at `prop` in `obj` which was set by `actor`
+    ///
+    /// This looks through all the conflicting values [`Self::get_all`] returns, not just the one
+    /// which wins, and returns the one set by an op of `actor`. Returns `None` if none of the
+    /// current values were set by `actor`, including when a later op of another actor replaced
+    /// the value `actor` set.
+    fn value_by_actor<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        actor: &ActorId,
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        Ok(self
+            .get_all(obj, prop)?
+            .into_iter()
+            .find(|(_, id)| matches!(id, ExId::Id(_, a, _) if a == actor)))
+    }
+
+    /// Get the value at `prop` in `obj` converted to `T`
+    ///
+    /// ```
+    /// # use automerge::{transaction::Transactable, AutoCommit, ReadDoc, ROOT};
+    /// let mut doc = AutoCommit::new();
+    /// doc.put(ROOT, "count", 3).unwrap();
+    /// let count: Option<i64> = doc.get_as(ROOT, "count").unwrap();
+    /// assert_eq!(count, Some(3));
+    /// ```
+    ///
+    /// Returns `None` if there is no value and [`AutomergeError::InvalidValueType`] if the value
+    /// can't be converted, use [`Self::get`] for objects.
+    fn get_as<T: FromValue, O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<T>, AutomergeError> {
+        self.get(obj, prop)?
+            .map(|(value, _)| {
+                T::from_value(value).map_err(|value| AutomergeError::InvalidValueType {
+                    expected: T::EXPECTED.to_string(),
+                    unexpected: value.value_type().to_string(),
+                })
+            })
+            .transpose()
+    }
+
+    /// Get the bytes at `prop` in `obj`
+    ///
+    /// This is [`Self::get_as`] for `Vec<u8>`, it returns [`AutomergeError::InvalidValueType`]
+    /// if the value is not bytes.
+    fn get_bytes<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<Vec<u8>>, AutomergeError> {
+        self.get_as(obj, prop)
+    }
+
+    /// Get the value at `prop` in `obj` along with whether it is conflicted
+    ///
+    /// The value is the same one [`Self::get`] returns, this saves calling [`Self::get_all`] as
+    /// well to find out if there are other values.
+    fn get_annotated<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<AnnotatedValue<'_>>, AutomergeError> {
+        let mut values = self.get_all(obj, prop)?.into_iter();
+        let count = values.len();
+        Ok(values.next().map(|(value, id)| AnnotatedValue {
+            value,
+            id,
+            conflicted: count > 1,
+            count,
+        }))
+    }
+
     /// Get all possibly conflicting values for a key as at `heads`
     ///
     /// See `[Self::get_all]`
@@ -200,6 +538,41 @@ pub trait ReadDoc {
         heads: &[ChangeHash],
     ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError>;
 
+    /// Get the value at `prop` in `obj` as at `heads` along with whether it was conflicted then
+    ///
+    /// This is [`Self::get_annotated`] for a historical state, the value is the one
+    /// [`Self::get_at`] returns. A conflict which has since been resolved is still reported.
+    fn get_annotated_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Option<AnnotatedValue<'_>>, AutomergeError> {
+        let mut values = self.get_all_at(obj, prop, heads)?.into_iter();
+        let count = values.len();
+        Ok(values.next().map(|(value, id)| AnnotatedValue {
+            value,
+            id,
+            conflicted: count > 1,
+            count,
+        }))
+    }
+
+    /// Get how much each actor has contributed to the counter at `prop` in `obj`
+    ///
+    /// The actor which created the counter contributes its initial value and every actor which
+    /// incremented it contributes the sum of its increments. The components are sorted by actor
+    /// and add up to the value of the counter. If the property is conflicted this is for the
+    /// value which [`Self::get`] returns.
+    ///
+    /// Returns [`AutomergeError::MissingProp`] if there is no value at `prop` and
+    /// [`AutomergeError::InvalidValueType`] if it is not a counter.
+    fn counter_components<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(ActorId, i64)>, AutomergeError>;
+
     /// Get the hashes of the changes in this document that aren't transitive dependencies of the
     /// given `heads`.
     fn get_missing_deps(&self, heads: &[ChangeHash]) -> Vec<ChangeHash>;
@@ -207,3 +580,25 @@ pub trait ReadDoc {
     /// Get a change by its hash.
     fn get_change_by_hash(&self, hash: &ChangeHash) -> Option<&Change>;
 }
+
+/// An element of a text object returned by [`ReadDoc::text_with_tombstones`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextCell {
+    /// An element which is in the text, with its string and its ID
+    Visible(String, ExId),
+    /// An element which has been deleted, with its ID
+    Deleted(ExId),
+}
+
+/// A value returned by [`ReadDoc::get_annotated`] and [`ReadDoc::get_annotated_at`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotatedValue<'a> {
+    /// The value which won, the same one [`ReadDoc::get`] (or [`ReadDoc::get_at`]) returns
+    pub value: Value<'a>,
+    /// The ID of the operation which created the value
+    pub id: ExId,
+    /// Whether there are other values for the property which conflict with this one
+    pub conflicted: bool,
+    /// The number of values for the property, including this one
+    pub count: usize,
+}
@@ -246,6 +246,10 @@ impl<'a, O: OpReadState> Change<'a, O> {
         &self.bytes[self.extra_bytes.clone()]
     }
 
+    pub(crate) fn has_unknown_columns(&self) -> bool {
+        self.ops_meta.has_unknown_columns()
+    }
+
     pub(crate) fn checksum_valid(&self) -> bool {
         self.header.checksum_valid()
     }
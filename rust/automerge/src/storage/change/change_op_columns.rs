@@ -125,9 +125,16 @@ pub(crate) struct ChangeOpsColumns {
     pred: OpIdListRange,
     expand: MaybeBooleanRange,
     mark_name: RleRange<smol_str::SmolStr>,
+    /// Whether there were any columns in the chunk which this version of automerge does not know
+    /// about
+    unknown_columns: bool,
 }
 
 impl ChangeOpsColumns {
+    pub(crate) fn has_unknown_columns(&self) -> bool {
+        self.unknown_columns
+    }
+
     pub(crate) fn iter<'a>(&self, data: &'a [u8]) -> ChangeOpsIter<'a> {
         ChangeOpsIter {
             failed: false,
@@ -184,6 +191,7 @@ impl ChangeOpsColumns {
             pred,
             expand,
             mark_name,
+            unknown_columns: false,
         }
     }
 
@@ -247,6 +255,7 @@ impl ChangeOpsColumns {
             pred,
             expand,
             mark_name,
+            unknown_columns: false,
         }
     }
 
@@ -519,6 +528,7 @@ impl TryFrom<Columns> for ChangeOpsColumns {
             pred,
             expand: expand.unwrap_or_else(|| (0..0).into()),
             mark_name: mark_name.unwrap_or_else(|| (0..0).into()),
+            unknown_columns: !other.is_empty(),
         })
     }
 }
@@ -74,6 +74,10 @@ impl Columns {
         self.columns.push(col)
     }
 
+    pub(crate) fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
     pub(crate) fn parse<'a, I: Iterator<Item = &'a RawColumn<compression::Uncompressed>>>(
         data_size: usize,
         cols: I,
@@ -329,6 +329,10 @@ impl<'a> Document<'a> {
         }
     }
 
+    pub(crate) fn has_unknown_columns(&self) -> bool {
+        self.op_metadata.has_unknown_columns() || self.change_metadata.has_unknown_columns()
+    }
+
     pub(crate) fn checksum_valid(&self) -> bool {
         self.header.checksum_valid()
     }
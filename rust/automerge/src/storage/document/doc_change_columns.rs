@@ -61,11 +61,16 @@ pub(crate) struct DocChangeColumns {
     message: RleRange<smol_str::SmolStr>,
     deps: DepsRange,
     extra: ValueRange,
-    #[allow(dead_code)]
     other: Columns,
 }
 
 impl DocChangeColumns {
+    /// Whether there were any columns in the chunk which this version of automerge does not know
+    /// about
+    pub(crate) fn has_unknown_columns(&self) -> bool {
+        !self.other.is_empty()
+    }
+
     pub(crate) fn iter<'a>(&self, data: &'a [u8]) -> DocChangeColumnIter<'a> {
         DocChangeColumnIter {
             actors: self.actor.decoder(data),
@@ -54,7 +54,6 @@ pub(crate) struct DocOpColumns {
     action: RleRange<u64>,
     val: ValueRange,
     succ: OpIdListRange,
-    #[allow(dead_code)]
     other: Columns,
     expand: MaybeBooleanRange,
     mark_name: RleRange<smol_str::SmolStr>,
@@ -101,6 +100,12 @@ pub(crate) trait AsDocOp<'a> {
 }
 
 impl DocOpColumns {
+    /// Whether there were any columns in the chunk which this version of automerge does not know
+    /// about
+    pub(crate) fn has_unknown_columns(&self) -> bool {
+        !self.other.is_empty()
+    }
+
     pub(crate) fn encode<'a, I, C, O>(ops: I, out: &mut Vec<u8>) -> DocOpColumns
     where
         I: Iterator<Item = C> + Clone + ExactSizeIterator,
@@ -11,6 +11,58 @@ pub(crate) use reconstruct_document::{
     reconstruct_document, DocObserver, LoadedObject, Reconstructed, VerificationMode,
 };
 
+/// How far [`crate::Automerge::load_with_progress`] has got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadProgress {
+    /// The number of bytes of the input in chunks which have been completely loaded
+    pub bytes: usize,
+    /// The length of the input
+    pub total_bytes: usize,
+    /// The number of operations decoded from the document chunk so far
+    pub ops: usize,
+    /// The number of changes loaded so far
+    pub changes: usize,
+}
+
+/// A [`DocObserver`] which passes everything on to `observer` and calls `progress` with the number
+/// of ops loaded every `interval` ops
+pub(crate) struct WithProgress<O, F> {
+    observer: O,
+    progress: F,
+    interval: usize,
+    ops: usize,
+}
+
+impl<O, F: FnMut(usize)> WithProgress<O, F> {
+    pub(crate) fn new(observer: O, interval: usize, progress: F) -> Self {
+        Self {
+            observer,
+            progress,
+            interval,
+            ops: 0,
+        }
+    }
+}
+
+impl<O: DocObserver, F: FnMut(usize)> DocObserver for WithProgress<O, F> {
+    type Output = (O::Output, usize);
+
+    fn op_loaded(&mut self) {
+        self.ops += 1;
+        if self.ops % self.interval == 0 {
+            (self.progress)(self.ops);
+        }
+    }
+
+    fn object_loaded(&mut self, object: LoadedObject) {
+        self.observer.object_loaded(object)
+    }
+
+    fn finish(self, metadata: crate::op_tree::OpSetMetadata) -> Self::Output {
+        (self.observer.finish(metadata), self.ops)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[allow(unreachable_pub)]
 pub enum Error {
@@ -26,6 +78,10 @@ pub enum Error {
     InflateDocument(Box<dyn std::error::Error + Send + Sync + 'static>),
     #[error("bad checksum")]
     BadChecksum,
+    #[error("a chunk contained columns which are not supported by this version of automerge")]
+    UnknownColumns,
+    #[error("a change contained extra bytes which are not supported by this version of automerge")]
+    ExtraBytes,
 }
 
 pub(crate) enum LoadedChanges<'a> {
@@ -112,6 +168,76 @@ fn load_next_change<'a>(
     Ok(remaining)
 }
 
+/// Something in a saved document which was skipped over or not fully understood when loading
+/// it, see [`crate::Automerge::load_with_warnings`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LoadWarning {
+    /// The chunk at `chunk` has columns written by a later version of automerge, they were
+    /// skipped
+    #[error(
+        "chunk {chunk} contained columns which are not supported by this version of automerge"
+    )]
+    UnknownColumns { chunk: usize },
+    /// The change in the chunk at `chunk` has extra data written by a later version of
+    /// automerge, it is kept with the change but otherwise ignored
+    #[error("the change in chunk {chunk} contained extra bytes which are not supported by this version of automerge")]
+    ExtraBytes { chunk: usize },
+}
+
+impl From<LoadWarning> for Error {
+    fn from(w: LoadWarning) -> Self {
+        match w {
+            LoadWarning::UnknownColumns { .. } => Error::UnknownColumns,
+            LoadWarning::ExtraBytes { .. } => Error::ExtraBytes,
+        }
+    }
+}
+
+/// Find every chunk in `data` which has data this version of automerge does not understand.
+///
+/// Returns an error if any chunk can't be parsed or has an invalid checksum.
+pub(crate) fn find_warnings(mut data: parse::Input<'_>) -> Result<Vec<LoadWarning>, Error> {
+    let mut warnings = Vec::new();
+    let mut index = 0;
+    while !data.is_empty() {
+        let (remaining, chunk) =
+            storage::Chunk::parse(data).map_err(|e| Error::Parse(Box::new(e)))?;
+        if !chunk.checksum_valid() {
+            return Err(Error::BadChecksum);
+        }
+        match chunk {
+            storage::Chunk::Document(d) => {
+                if d.has_unknown_columns() {
+                    warnings.push(LoadWarning::UnknownColumns { chunk: index });
+                }
+            }
+            storage::Chunk::Change(change) | storage::Chunk::CompressedChange(change, _) => {
+                if change.has_unknown_columns() {
+                    warnings.push(LoadWarning::UnknownColumns { chunk: index });
+                }
+                if !change.extra_bytes().is_empty() {
+                    warnings.push(LoadWarning::ExtraBytes { chunk: index });
+                }
+            }
+        }
+        data = remaining.reset();
+        index += 1;
+    }
+    Ok(warnings)
+}
+
+/// Check that every chunk in `data` is one we know how to load in its entirety.
+///
+/// Normal loading skips over data which may have been written by a later version of automerge,
+/// this returns an error if there is any such data, if any chunk has an invalid checksum, or if
+/// there is any data after the last chunk which is not a valid chunk.
+pub(crate) fn check_strict(data: parse::Input<'_>) -> Result<(), Error> {
+    match find_warnings(data)?.into_iter().next() {
+        Some(warning) => Err(warning.into()),
+        None => Ok(()),
+    }
+}
+
 struct NullObserver;
 impl DocObserver for NullObserver {
     type Output = ();
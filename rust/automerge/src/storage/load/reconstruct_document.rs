@@ -64,6 +64,8 @@ pub(crate) struct LoadedObject {
 pub(crate) trait DocObserver {
     type Output;
 
+    /// An operation has been decoded, this is called once for every operation in the document
+    fn op_loaded(&mut self) {}
     /// The operations for an object have been loaded
     fn object_loaded(&mut self, object: LoadedObject);
     /// The document has finished loading. The `metadata` is the `OpSetMetadata` which was used to
@@ -146,6 +148,7 @@ pub(crate) fn reconstruct_document<'a, O: DocObserver>(
         check_opid(&metadata, *obj.opid())?;
         let op = import_op(&mut metadata, doc_op)?;
         tracing::trace!(?op, ?obj, "loading document op");
+        observer.op_loaded();
 
         if let OpType::Make(obj_type) = op.action {
             create_ops.insert(
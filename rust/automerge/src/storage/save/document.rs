@@ -1,11 +1,13 @@
 use std::{borrow::Cow, collections::BTreeMap, iter::Iterator};
 
+use itertools::Itertools;
+
 use crate::{
     indexed_cache::IndexedCache,
     storage::{
         change::DEFLATE_MIN_SIZE, convert::op_as_docop, AsChangeMeta, CompressConfig, Document,
     },
-    types::{ActorId, ObjId, Op},
+    types::{ActorId, Key, ObjId, Op},
     Change, ChangeHash,
 };
 
@@ -29,18 +31,112 @@ where
     O: Iterator<Item = (&'a ObjId, &'a Op)> + Clone + ExactSizeIterator,
 {
     let actor_lookup = actors.encode_index();
-    let doc_ops = ops.map(|(obj, op)| op_as_docop(&actor_lookup, props, obj, op));
+    encode_document(
+        changes,
+        ops,
+        actors,
+        actors.sorted().cache,
+        &actor_lookup,
+        props,
+        heads,
+        config,
+    )
+}
+
+/// Like [`save_document`] but the actor table only contains the actors which `changes` and `ops`
+/// refer to, rather than every actor in `actors`
+///
+/// This means the output only depends on the changes and ops, not on which other actors happen
+/// to have been cached. Properties are written by name for each op so there is no equivalent
+/// table for them.
+///
+/// # Panics
+///
+/// See [`save_document`]
+#[tracing::instrument(skip(changes, ops, actors, props, config))]
+pub(crate) fn save_document_used_actors<'a, I, O>(
+    changes: I,
+    ops: O,
+    actors: &'a IndexedCache<ActorId>,
+    props: &IndexedCache<String>,
+    heads: &[ChangeHash],
+    config: Option<CompressConfig>,
+) -> Vec<u8>
+where
+    I: Iterator<Item = &'a Change> + Clone + 'a,
+    O: Iterator<Item = (&'a ObjId, &'a Op)> + Clone + ExactSizeIterator,
+{
+    let mut used = vec![false; actors.len()];
+    for change in changes.clone() {
+        used[actors.lookup(change.actor_id()).unwrap()] = true;
+    }
+    for (obj, op) in ops.clone() {
+        used[op.id.actor()] = true;
+        if !obj.is_root() {
+            used[obj.opid().actor()] = true;
+        }
+        if let Key::Seq(elem) = op.key {
+            if !elem.is_head() {
+                used[elem.0.actor()] = true;
+            }
+        }
+        for succ in op.succ.iter() {
+            used[succ.actor()] = true;
+        }
+    }
+    let actor_table: Vec<ActorId> = actors
+        .cache
+        .iter()
+        .zip(used.iter())
+        .filter(|(_, used)| **used)
+        .map(|(actor, _)| actor.clone())
+        .sorted()
+        .collect();
+    // Unused actors are never looked up so their entry doesn't matter
+    let actor_lookup: Vec<usize> = actors
+        .cache
+        .iter()
+        .map(|actor| actor_table.binary_search(actor).unwrap_or(usize::MAX))
+        .collect();
+    encode_document(
+        changes,
+        ops,
+        actors,
+        actor_table,
+        &actor_lookup,
+        props,
+        heads,
+        config,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn encode_document<'a, I, O>(
+    changes: I,
+    ops: O,
+    actors: &'a IndexedCache<ActorId>,
+    actor_table: Vec<ActorId>,
+    actor_lookup: &[usize],
+    props: &IndexedCache<String>,
+    heads: &[ChangeHash],
+    config: Option<CompressConfig>,
+) -> Vec<u8>
+where
+    I: Iterator<Item = &'a Change> + Clone + 'a,
+    O: Iterator<Item = (&'a ObjId, &'a Op)> + Clone + ExactSizeIterator,
+{
+    let doc_ops = ops.map(|(obj, op)| op_as_docop(actor_lookup, props, obj, op));
 
     let hash_graph = HashGraph::new(changes.clone());
     let changes = changes.map(|c| ChangeWithGraph {
         actors,
-        actor_lookup: &actor_lookup,
+        actor_lookup,
         change: c,
         graph: &hash_graph,
     });
 
     let doc = Document::new(
-        actors.sorted().cache,
+        actor_table,
         hash_graph.heads_with_indices(heads.to_vec()),
         doc_ops,
         changes,
@@ -70,8 +70,10 @@
 use itertools::Itertools;
 use serde::ser::SerializeMap;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 use crate::{
+    columnar::encoding::leb128::ulebsize,
     storage::{parse, Change as StoredChange, ReadChangeOpError},
     Automerge, AutomergeError, Change, ChangeHash, OpObserver, ReadDoc,
 };
@@ -158,6 +160,9 @@ impl SyncDoc for Automerge {
             Vec::new()
         };
 
+        sync_state.pending_local = changes_to_send.len();
+        sync_state.pending_remote = our_need.len();
+
         let heads_unchanged = sync_state.last_sent_heads == our_heads;
 
         let heads_equal = if let Some(their_heads) = sync_state.their_heads.as_ref() {
@@ -319,8 +324,15 @@ impl Automerge {
             have: message_have,
         } = message;
 
-        let changes_is_empty = message_changes.is_empty();
-        if !changes_is_empty {
+        let received = message_changes.len();
+        let changes_is_empty = received == 0;
+        // another peer may already have sent us some of these, skip them before applying
+        let message_changes = message_changes
+            .into_iter()
+            .filter(|change| self.get_change_by_hash(&change.hash()).is_none())
+            .collect::<Vec<_>>();
+        sync_state.duplicate_changes += received - message_changes.len();
+        if !message_changes.is_empty() {
             self.apply_changes_with(message_changes, op_observer)?;
             sync_state.shared_heads = advance_heads(
                 &before_heads.iter().collect(),
@@ -344,7 +356,11 @@ impl Automerge {
             .iter()
             .filter(|head| self.get_change_by_hash(head).is_some())
             .collect::<Vec<_>>();
+        sync_state.pending_remote = message_heads.len() - known_heads.len();
         if known_heads.len() == message_heads.len() {
+            if message_heads == self.get_heads() {
+                sync_state.pending_local = 0;
+            }
             sync_state.shared_heads = message_heads.clone();
             sync_state.in_flight = false;
             // If the remote peer has lost all its data, reset our state to perform a full resync
@@ -529,6 +545,65 @@ impl Message {
 
         buf
     }
+
+    /// Count what is in this message, for logging
+    ///
+    /// This doesn't encode the message or look at the ops in the changes.
+    pub fn summary(&self) -> MessageSummary {
+        let hashes_len =
+            |hashes: &[ChangeHash]| ulebsize(hashes.len() as u64) as usize + hashes.len() * 32;
+        let have_len = self
+            .have
+            .iter()
+            .map(|h| {
+                let bloom_len = h.bloom.to_bytes().len();
+                hashes_len(&h.last_sync) + ulebsize(bloom_len as u64) as usize + bloom_len
+            })
+            .sum::<usize>();
+        let changes_len = self
+            .changes
+            .iter()
+            .map(|c| ulebsize(c.raw_bytes().len() as u64) as usize + c.raw_bytes().len())
+            .sum::<usize>();
+        MessageSummary {
+            heads: self.heads.len(),
+            need: self.need.len(),
+            have: self.have.len(),
+            changes: self.changes.len(),
+            encoded_size: 1
+                + hashes_len(&self.heads)
+                + hashes_len(&self.need)
+                + ulebsize(self.have.len() as u64) as usize
+                + have_len
+                + ulebsize(self.changes.len() as u64) as usize
+                + changes_len,
+        }
+    }
+}
+
+/// The counts of what is in a [`Message`], see [`Message::summary`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageSummary {
+    /// The number of heads of the sender
+    pub heads: usize,
+    /// The number of changes the sender is requesting
+    pub need: usize,
+    /// The number of bloom filters summarising what the sender has
+    pub have: usize,
+    /// The number of changes in the message
+    pub changes: usize,
+    /// The length of the message once it is encoded, in bytes
+    pub encoded_size: usize,
+}
+
+impl fmt::Display for MessageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sync message: {} heads, {} need, {} have, {} changes, {} bytes",
+            self.heads, self.need, self.have, self.changes, self.encoded_size
+        )
+    }
 }
 
 fn encode_many<'a, I, It, F>(out: &mut Vec<u8>, data: I, f: F)
@@ -645,6 +720,16 @@ mod tests {
             assert!(i.is_empty());
             assert_eq!(msg, decoded);
         }
+
+        #[test]
+        fn summary_matches_encoded_message(msg in gen_sync_message()) {
+            let summary = msg.summary();
+            assert_eq!(summary.heads, msg.heads.len());
+            assert_eq!(summary.need, msg.need.len());
+            assert_eq!(summary.have, msg.have.len());
+            assert_eq!(summary.changes, msg.changes.len());
+            assert_eq!(summary.encoded_size, msg.encode().len());
+        }
     }
 
     #[test]
@@ -932,6 +1017,80 @@ mod tests {
         assert_eq!(doc1.get_heads(), doc2.get_heads());
     }
 
+    #[test]
+    fn pending_counts_track_the_handshake() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        for i in 0..3 {
+            doc1.put(crate::ROOT, "key", i).unwrap();
+            doc1.commit();
+        }
+
+        // doc1 announces its heads, doc2 learns it is missing something
+        let m1 = doc1.sync().generate_sync_message(&mut s1).unwrap();
+        assert_eq!(s1.pending_local(), 0);
+        doc2.sync().receive_sync_message(&mut s2, m1).unwrap();
+        assert_eq!(s2.pending_remote(), 1);
+
+        // doc2 asks for the changes, doc1 now knows exactly what to send
+        let m2 = doc2.sync().generate_sync_message(&mut s2).unwrap();
+        doc1.sync().receive_sync_message(&mut s1, m2).unwrap();
+        let m3 = doc1.sync().generate_sync_message(&mut s1).unwrap();
+        assert_eq!(s1.pending_local(), 3);
+
+        // the changes arrive and doc2 acknowledges them
+        doc2.sync().receive_sync_message(&mut s2, m3).unwrap();
+        assert_eq!(s2.pending_remote(), 0);
+        let m4 = doc2.sync().generate_sync_message(&mut s2).unwrap();
+        doc1.sync().receive_sync_message(&mut s1, m4).unwrap();
+        assert_eq!(s1.pending_local(), 0);
+
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        assert_eq!(s1.pending_local(), 0);
+        assert_eq!(s1.pending_remote(), 0);
+        assert_eq!(s2.pending_local(), 0);
+        assert_eq!(s2.pending_remote(), 0);
+    }
+
+    #[test]
+    fn changes_already_received_from_another_peer_are_skipped() {
+        let mut source = crate::AutoCommit::new();
+        source.put(crate::ROOT, "key", "value").unwrap();
+        let mut peer_a = source.fork();
+        let mut peer_b = source.fork();
+        let mut hub = crate::AutoCommit::new();
+        let (mut hub_a, mut hub_b) = (State::new(), State::new());
+        let (mut a_hub, mut b_hub) = (State::new(), State::new());
+
+        // the hub asks both peers for everything before either has answered
+        let to_a = hub.sync().generate_sync_message(&mut hub_a).unwrap();
+        let to_b = hub.sync().generate_sync_message(&mut hub_b).unwrap();
+        peer_a
+            .sync()
+            .receive_sync_message(&mut a_hub, to_a)
+            .unwrap();
+        peer_b
+            .sync()
+            .receive_sync_message(&mut b_hub, to_b)
+            .unwrap();
+        let from_a = peer_a.sync().generate_sync_message(&mut a_hub).unwrap();
+        let from_b = peer_b.sync().generate_sync_message(&mut b_hub).unwrap();
+        assert_eq!(from_a.changes.len(), 1);
+        assert_eq!(from_b.changes.len(), 1);
+
+        hub.sync().receive_sync_message(&mut hub_a, from_a).unwrap();
+        hub.sync().receive_sync_message(&mut hub_b, from_b).unwrap();
+        assert_eq!(hub.get_changes(&[]).unwrap().len(), 1);
+        assert_eq!(hub_a.duplicate_changes(), 0);
+        assert_eq!(hub_b.duplicate_changes(), 1);
+        assert_eq!(hub.get_heads(), source.get_heads());
+
+        sync(&mut hub, &mut peer_b, &mut hub_b, &mut b_hub);
+        assert_eq!(hub.get_heads(), peer_b.get_heads());
+    }
+
     fn sync(
         a: &mut crate::AutoCommit,
         b: &mut crate::AutoCommit,
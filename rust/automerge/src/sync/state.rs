@@ -27,6 +27,13 @@ impl From<parse::leb128::Error> for DecodeError {
 /// This should be persisted using [`Self::encode`] when you know you will be interacting with the
 /// same peer in multiple sessions. [`Self::encode`] only encodes state which should be reused
 /// across connections.
+///
+/// # Constructing a `State`
+///
+/// The counters behind [`Self::pending_local`], [`Self::pending_remote`] and
+/// [`Self::duplicate_changes`] are private, so a `State` can't be built with a struct literal
+/// outside this crate. This is a breaking change from when every field was public: start from
+/// [`Self::new`] and set the public fields instead.
 #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct State {
     /// The hashes which we know both peers have
@@ -50,6 +57,13 @@ pub struct State {
     /// there are in fact changes to send). If it is `true` then we don't. This flag is cleared
     /// in `receive_sync_message`.
     pub in_flight: bool,
+
+    /// Backs [`Self::pending_local`]
+    pub(crate) pending_local: usize,
+    /// Backs [`Self::pending_remote`]
+    pub(crate) pending_remote: usize,
+    /// Backs [`Self::duplicate_changes`]
+    pub(crate) duplicate_changes: usize,
 }
 
 /// A summary of the changes that the sender of the message already has.
@@ -69,6 +83,34 @@ impl State {
         Default::default()
     }
 
+    /// The number of our changes which the peer still needs
+    ///
+    /// This is updated as sync messages are generated and received. Until the peer has told us
+    /// what it has (i.e. until the first round trip of the handshake has completed) this is only
+    /// an estimate, and it is not persisted by [`Self::encode`].
+    pub fn pending_local(&self) -> usize {
+        self.pending_local
+    }
+
+    /// The number of change hashes the peer has told us about which we don't have yet
+    ///
+    /// These are the peer's heads we are missing, or the missing dependencies of changes it has
+    /// sent. This counts hashes, not changes: each missing hash may stand for any number of
+    /// changes behind it, so all this says is whether we are still waiting for the peer. It is
+    /// `0` once we have everything the peer has.
+    pub fn pending_remote(&self) -> usize {
+        self.pending_remote
+    }
+
+    /// The number of changes received from this peer which we already had
+    ///
+    /// When syncing with several peers the same change often arrives from more than one of them.
+    /// Such changes are skipped without being applied again and counted here. Like the pending
+    /// counts this is not persisted by [`Self::encode`].
+    pub fn duplicate_changes(&self) -> usize {
+        self.duplicate_changes
+    }
+
     pub fn encode(&self) -> Vec<u8> {
         let mut buf = vec![SYNC_STATE_TYPE];
         encode_hashes(&mut buf, &self.shared_heads);
@@ -104,6 +146,9 @@ impl State {
                 their_have: Some(Vec::new()),
                 sent_hashes: BTreeSet::new(),
                 in_flight: false,
+                pending_local: 0,
+                pending_remote: 0,
+                duplicate_changes: 0,
             },
         ))
     }
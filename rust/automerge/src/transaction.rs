@@ -1,5 +1,6 @@
 mod commit;
 mod inner;
+mod inverse;
 mod manual_transaction;
 pub(crate) mod observation;
 mod result;
@@ -8,9 +9,11 @@ mod transactable;
 pub use self::commit::CommitOptions;
 pub use self::transactable::Transactable;
 pub(crate) use inner::{TransactionArgs, TransactionInner};
+pub use inverse::InverseOp;
 pub use manual_transaction::Transaction;
 pub use observation::{Observation, Observed, UnObserved};
 pub use result::Failure;
+pub use result::SpliceResult;
 pub use result::Success;
 
 pub type Result<O, Obs, E> = std::result::Result<Success<O, Obs>, Failure<E>>;
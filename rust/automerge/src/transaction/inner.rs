@@ -6,7 +6,9 @@ use crate::query::{self, OpIdSearch};
 use crate::storage::Change as StoredChange;
 use crate::types::{Key, ListEncoding, ObjId, OpId, OpIds, TextEncoding};
 use crate::{op_tree::OpSetMetadata, types::Op, Automerge, Change, ChangeHash, OpObserver, Prop};
-use crate::{AutomergeError, ObjType, OpType, ScalarValue};
+use crate::{AutomergeError, ObjType, OpType, ReadDoc, ScalarValue};
+
+use super::SpliceResult;
 
 #[derive(Debug, Clone)]
 pub(crate) struct TransactionInner {
@@ -93,7 +95,7 @@ impl TransactionInner {
             self.message = message;
         }
 
-        if let Some(t) = time {
+        if let Some(t) = time.or_else(|| doc.clock_time()) {
             self.time = t;
         }
 
@@ -291,12 +293,11 @@ impl TransactionInner {
         value: V,
     ) -> Result<(), AutomergeError> {
         let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
-        if !matches!(obj_type, ObjType::List | ObjType::Text) {
-            return Err(AutomergeError::InvalidOp(obj_type));
-        }
         let value = value.into();
         tracing::trace!(obj=?obj, value=?value, "inserting value");
-        self.do_insert(doc, op_observer, obj, index, value.into())?;
+        let action = value.into();
+        check_insert(obj_type, &action)?;
+        self.do_insert(doc, op_observer, obj, index, action)?;
         Ok(())
     }
 
@@ -309,10 +310,49 @@ impl TransactionInner {
         value: ObjType,
     ) -> Result<ExId, AutomergeError> {
         let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
-        if !matches!(obj_type, ObjType::List | ObjType::Text) {
-            return Err(AutomergeError::InvalidOp(obj_type));
-        }
-        let id = self.do_insert(doc, op_observer, obj, index, value.into())?;
+        let action = value.into();
+        check_insert(obj_type, &action)?;
+        let id = self.do_insert(doc, op_observer, obj, index, action)?;
+        let id = doc.id_to_exid(id);
+        Ok(id)
+    }
+
+    pub(crate) fn push<V: Into<ScalarValue>, Obs: OpObserver>(
+        &mut self,
+        doc: &mut Automerge,
+        op_observer: Option<&mut Obs>,
+        ex_obj: &ExId,
+        value: V,
+    ) -> Result<ExId, AutomergeError> {
+        let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
+        let value = value.into();
+        tracing::trace!(obj=?obj, value=?value, "pushing value");
+        let action = value.into();
+        check_insert(obj_type, &action)?;
+        let index = doc
+            .ops()
+            .search(&obj, query::Len::new(ListEncoding::List))
+            .len;
+        let id = self.do_insert(doc, op_observer, obj, index, action)?;
+        let id = doc.id_to_exid(id);
+        Ok(id)
+    }
+
+    pub(crate) fn push_object<Obs: OpObserver>(
+        &mut self,
+        doc: &mut Automerge,
+        op_observer: Option<&mut Obs>,
+        ex_obj: &ExId,
+        value: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
+        let action = value.into();
+        check_insert(obj_type, &action)?;
+        let index = doc
+            .ops()
+            .search(&obj, query::Len::new(ListEncoding::List))
+            .len;
+        let id = self.do_insert(doc, op_observer, obj, index, action)?;
         let id = doc.id_to_exid(id);
         Ok(id)
     }
@@ -426,7 +466,10 @@ impl TransactionInner {
 
         let id = self.next_id();
         let pred = doc.ops().m.sorted_opids(query.ops.iter().map(|o| o.id));
-        let key = query.key()?;
+        let key = query.key().map_err(|_| AutomergeError::MissingProp {
+            obj: doc.ops().id_to_exid(obj.0),
+            prop: Prop::Seq(index),
+        })?;
 
         if query.ops.len() == 1 && query.ops[0].is_noop(&action) {
             return Ok(None);
@@ -473,30 +516,86 @@ impl TransactionInner {
         op_observer: Option<&mut Obs>,
         ex_obj: &ExId,
         prop: P,
-    ) -> Result<(), AutomergeError> {
+    ) -> Result<bool, AutomergeError> {
         let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
         let prop = prop.into();
         if obj_type == ObjType::Text {
             let index = prop.to_index().ok_or(AutomergeError::InvalidOp(obj_type))?;
-            self.inner_splice(
+            let result = self.inner_splice(
                 doc,
                 op_observer,
                 SpliceArgs {
                     obj,
                     index,
                     del: 1,
-                    values: vec![],
+                    values: std::iter::empty(),
                     splice_type: SpliceType::Text("", doc.text_encoding()),
+                    expand: None,
                 },
             )?;
+            Ok(result.removed > 0)
         } else {
-            self.local_op(doc, op_observer, obj, prop, OpType::Delete)?;
+            let id = self.local_op(doc, op_observer, obj, prop, OpType::Delete)?;
+            Ok(id.is_some())
+        }
+    }
+
+    /// Delete the element of a sequence which was inserted by the op `elem`, wherever it is now
+    pub(crate) fn delete_elem<Obs: OpObserver>(
+        &mut self,
+        doc: &mut Automerge,
+        op_observer: Option<&mut Obs>,
+        ex_obj: &ExId,
+        elem: &ExId,
+    ) -> Result<(), AutomergeError> {
+        let index = doc
+            .index_of(ex_obj, elem, None)?
+            .ok_or_else(|| AutomergeError::ElemDeleted(elem.clone()))?;
+        self.delete(doc, op_observer, ex_obj, index)?;
+        Ok(())
+    }
+
+    /// Delete every key of a map or every element of a sequence
+    pub(crate) fn clear<Obs: OpObserver>(
+        &mut self,
+        doc: &mut Automerge,
+        mut op_observer: Option<&mut Obs>,
+        ex_obj: &ExId,
+    ) -> Result<(), AutomergeError> {
+        let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
+        match obj_type {
+            ObjType::Map | ObjType::Table => {
+                let keys = doc.keys(ex_obj).collect::<Vec<_>>();
+                for key in keys {
+                    let observer = op_observer.as_deref_mut();
+                    self.local_op(doc, observer, obj, Prop::Map(key), OpType::Delete)?;
+                }
+            }
+            ObjType::List | ObjType::Text => {
+                let splice_type = if obj_type == ObjType::Text {
+                    SpliceType::Text("", doc.text_encoding())
+                } else {
+                    SpliceType::List
+                };
+                self.inner_splice(
+                    doc,
+                    op_observer,
+                    SpliceArgs {
+                        obj,
+                        index: 0,
+                        del: doc.length(ex_obj),
+                        values: std::iter::empty(),
+                        splice_type,
+                        expand: None,
+                    },
+                )?;
+            }
         }
         Ok(())
     }
 
-    /// Splice new elements into the given sequence. Returns a vector of the OpIds used to insert
-    /// the new elements
+    /// Splice new elements into the given sequence. Returns the region of the sequence which was
+    /// affected
     pub(crate) fn splice<Obs: OpObserver>(
         &mut self,
         doc: &mut Automerge,
@@ -505,12 +604,11 @@ impl TransactionInner {
         index: usize,
         del: usize,
         vals: impl IntoIterator<Item = ScalarValue>,
-    ) -> Result<(), AutomergeError> {
+    ) -> Result<SpliceResult, AutomergeError> {
         let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
         if !matches!(obj_type, ObjType::List | ObjType::Text) {
             return Err(AutomergeError::InvalidOp(obj_type));
         }
-        let values = vals.into_iter().collect();
         self.inner_splice(
             doc,
             op_observer,
@@ -518,12 +616,45 @@ impl TransactionInner {
                 obj,
                 index,
                 del,
-                values,
+                values: vals.into_iter(),
                 splice_type: SpliceType::List,
+                expand: None,
             },
         )
     }
 
+    /// Insert `vals` into a sequence starting at `index`, returning the ids of the new elements
+    pub(crate) fn insert_many<Obs: OpObserver, V: Into<ScalarValue>>(
+        &mut self,
+        doc: &mut Automerge,
+        op_observer: Option<&mut Obs>,
+        ex_obj: &ExId,
+        index: usize,
+        vals: impl IntoIterator<Item = V>,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
+        if !matches!(obj_type, ObjType::List | ObjType::Text) {
+            return Err(AutomergeError::InvalidOp(obj_type));
+        }
+        let start = self.operations.len();
+        self.inner_splice(
+            doc,
+            op_observer,
+            SpliceArgs {
+                obj,
+                index,
+                del: 0,
+                values: vals.into_iter().map(Into::into),
+                splice_type: SpliceType::List,
+                expand: None,
+            },
+        )?;
+        Ok(self.operations[start..]
+            .iter()
+            .map(|(_, op)| doc.id_to_exid(op.id))
+            .collect())
+    }
+
     /// Splice string into a text object
     pub(crate) fn splice_text<Obs: OpObserver>(
         &mut self,
@@ -533,12 +664,29 @@ impl TransactionInner {
         index: usize,
         del: usize,
         text: &str,
-    ) -> Result<(), AutomergeError> {
+    ) -> Result<SpliceResult, AutomergeError> {
+        self.splice_text_with_expand(doc, op_observer, ex_obj, index, del, text, None)
+    }
+
+    /// Splice string into a text object, treating the marks which start or end at `index` as if
+    /// they had been created with `expand` if it is given
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn splice_text_with_expand<Obs: OpObserver>(
+        &mut self,
+        doc: &mut Automerge,
+        op_observer: Option<&mut Obs>,
+        ex_obj: &ExId,
+        index: usize,
+        del: usize,
+        text: &str,
+        expand: Option<ExpandMark>,
+    ) -> Result<SpliceResult, AutomergeError> {
         let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
         if obj_type != ObjType::Text {
             return Err(AutomergeError::InvalidOp(obj_type));
         }
-        let values = text.chars().map(ScalarValue::from).collect();
+        // the characters are converted into values as they are inserted rather than collected up
+        // front so we don't hold a second copy of a large insert in memory
         self.inner_splice(
             doc,
             op_observer,
@@ -546,13 +694,14 @@ impl TransactionInner {
                 obj,
                 index,
                 del,
-                values,
+                values: text.chars().map(ScalarValue::from),
                 splice_type: SpliceType::Text(text, doc.text_encoding()),
+                expand,
             },
         )
     }
 
-    fn inner_splice<Obs: OpObserver>(
+    fn inner_splice<Obs: OpObserver, I: Iterator<Item = ScalarValue>>(
         &mut self,
         doc: &mut Automerge,
         mut op_observer: Option<&mut Obs>,
@@ -562,30 +711,36 @@ impl TransactionInner {
             mut del,
             values,
             splice_type,
-        }: SpliceArgs<'_>,
-    ) -> Result<(), AutomergeError> {
+            expand,
+        }: SpliceArgs<'_, I>,
+    ) -> Result<SpliceResult, AutomergeError> {
         let ex_obj = doc.ops().id_to_exid(obj.0);
         let encoding = splice_type.encoding();
+        let len = doc.ops().search(&obj, query::Len::new(encoding)).len;
+        if index > len {
+            return Err(AutomergeError::IndexOutOfBounds { len, pos: index });
+        }
         // delete `del` items - performing the query for each one
         let mut deleted = 0;
         while deleted < del {
             // TODO: could do this with a single custom query
             let query = doc.ops().search(&obj, query::Nth::new(index, encoding));
 
+            // nothing left to delete, `del` ran past the end of the sequence
+            let step = if let Some(op) = query.ops.last() {
+                op.width(encoding)
+            } else {
+                break;
+            };
+
             // if we delete in the middle of a multi-character
             // move cursor back to the beginning and expand the del width
             let adjusted_index = query.index();
             if adjusted_index < index {
-                del += index - adjusted_index;
+                del = del.saturating_add(index - adjusted_index);
                 index = adjusted_index;
             }
 
-            let step = if let Some(op) = query.ops.last() {
-                op.width(encoding)
-            } else {
-                break;
-            };
-
             let op = self.next_delete(query.key()?, query.pred(doc.ops()));
 
             let ops_pos = query.ops_pos;
@@ -604,22 +759,27 @@ impl TransactionInner {
 
         // do the insert query for the first item and then
         // insert the remaining ops one after the other
-        if !values.is_empty() {
-            let query = doc
-                .ops()
-                .search(&obj, query::InsertNth::new(index, encoding));
+        let mut inserted = 0;
+        let mut values = values.peekable();
+        if values.peek().is_some() {
+            let query = doc.ops().search(
+                &obj,
+                query::InsertNth::new(index, encoding).with_expand(expand),
+            );
             let mut pos = query.pos();
             let mut key = query.key()?;
             let mut cursor = index;
             let mut width = 0;
+            let start = self.operations.len();
 
-            for v in &values {
-                let op = self.next_insert(key, v.clone());
+            for v in values {
+                let op = self.next_insert(key, v);
 
                 doc.ops_mut().insert(pos, &obj, op.clone());
 
                 width = op.width(encoding);
                 cursor += width;
+                inserted += width;
                 pos += 1;
                 key = op.id.into();
 
@@ -635,10 +795,8 @@ impl TransactionInner {
                         obs.splice_text(doc, ex_obj, index, text)
                     }
                     SpliceType::List | SpliceType::Text(..) => {
-                        let start = self.operations.len() - values.len();
-                        for (offset, v) in values.iter().enumerate() {
-                            let op = &self.operations[start + offset].1;
-                            let value = (v.clone().into(), doc.ops().id_to_exid(op.id));
+                        for (offset, (_, op)) in self.operations[start..].iter().enumerate() {
+                            let value = (op.clone_value(), doc.ops().id_to_exid(op.id));
                             obs.insert(doc, ex_obj.clone(), index + offset, value, false)
                         }
                     }
@@ -646,7 +804,11 @@ impl TransactionInner {
             }
         }
 
-        Ok(())
+        Ok(SpliceResult {
+            removed: deleted,
+            inserted,
+            at: index,
+        })
     }
 
     pub(crate) fn mark<Obs: OpObserver>(
@@ -657,7 +819,10 @@ impl TransactionInner {
         mark: Mark<'_>,
         expand: ExpandMark,
     ) -> Result<(), AutomergeError> {
-        let (obj, _obj_type) = doc.exid_to_obj(ex_obj)?;
+        let (obj, obj_type) = doc.exid_to_obj(ex_obj)?;
+        if !obj_type.is_sequence() {
+            return Err(AutomergeError::InvalidOp(obj_type));
+        }
         if let Some(obs) = op_observer {
             let action = OpType::MarkBegin(expand.before(), mark.data.clone().into_owned());
             self.do_insert(doc, Some(obs), obj, mark.start, action)?;
@@ -740,6 +905,16 @@ impl TransactionInner {
     }
 }
 
+/// Check that `action` can be inserted into a sequence of type `obj_type`
+fn check_insert(obj_type: ObjType, action: &OpType) -> Result<(), AutomergeError> {
+    match (obj_type, action) {
+        // text elements are characters, an object in the sequence would break `text()`
+        (ObjType::Text, OpType::Make(value)) => Err(AutomergeError::InvalidTextValue(*value)),
+        (ObjType::List | ObjType::Text, _) => Ok(()),
+        _ => Err(AutomergeError::InvalidOp(obj_type)),
+    }
+}
+
 enum SpliceType<'a> {
     List,
     Text(&'a str, TextEncoding),
@@ -754,12 +929,14 @@ impl<'a> SpliceType<'a> {
     }
 }
 
-struct SpliceArgs<'a> {
+struct SpliceArgs<'a, I> {
     obj: ObjId,
     index: usize,
     del: usize,
-    values: Vec<ScalarValue>,
+    values: I,
     splice_type: SpliceType<'a>,
+    /// Overrides how the marks at `index` expand for the inserted values
+    expand: Option<ExpandMark>,
 }
 
 #[cfg(test)]
@@ -0,0 +1,150 @@
+use crate::exid::ExId;
+use crate::{AutomergeError, ObjType, Prop, ReadDoc, Value};
+
+use super::Transactable;
+
+/// An operation which undoes one operation made in a transaction
+///
+/// These are recorded by a transaction after [`Transaction::record_inverse`] is called and
+/// returned by [`Transaction::inverse`]. They describe what the document looked like before the
+/// operation rather than which ops it created, so applying them with [`Self::apply`] makes new
+/// changes which restore the old values. Objects which were overwritten or deleted are restored
+/// as new empty objects of the same type, their contents are not recorded.
+///
+/// [`Transaction::record_inverse`]: super::Transaction::record_inverse
+/// [`Transaction::inverse`]: super::Transaction::inverse
+#[derive(Debug, Clone, PartialEq)]
+pub enum InverseOp {
+    /// Set `prop` of `obj` back to `value`
+    Put {
+        obj: ExId,
+        prop: Prop,
+        value: Value<'static>,
+    },
+    /// Delete `prop` of `obj`, which didn't exist before
+    Delete { obj: ExId, prop: Prop },
+    /// Increment the counter at `prop` of `obj` by `by`
+    Increment { obj: ExId, prop: Prop, by: i64 },
+    /// Delete `del` elements of the list `obj` at `index` and insert `values` there
+    Splice {
+        obj: ExId,
+        index: usize,
+        del: usize,
+        values: Vec<Value<'static>>,
+    },
+    /// Delete `del` characters of the text `obj` at `index` and insert `text` there
+    SpliceText {
+        obj: ExId,
+        index: usize,
+        del: usize,
+        text: String,
+    },
+}
+
+impl InverseOp {
+    /// Move a splice to `index` and make it delete `del` elements, used once the splice it undoes
+    /// has been performed and we know what it inserted
+    pub(crate) fn with_del(self, index: usize, del: usize) -> Self {
+        match self {
+            Self::Splice { obj, values, .. } => Self::Splice {
+                obj,
+                index,
+                del,
+                values,
+            },
+            Self::SpliceText { obj, text, .. } => Self::SpliceText {
+                obj,
+                index,
+                del,
+                text,
+            },
+            other => other,
+        }
+    }
+
+    /// Perform this operation in `tx`
+    pub fn apply<T: Transactable>(&self, tx: &mut T) -> Result<(), AutomergeError> {
+        match self {
+            Self::Put { obj, prop, value } => match value {
+                Value::Object(obj_type) => tx.put_object(obj, prop.clone(), *obj_type).map(|_| ()),
+                Value::Scalar(value) => tx.put(obj, prop.clone(), value.as_ref().clone()),
+            },
+            Self::Delete { obj, prop } => tx.delete(obj, prop.clone()),
+            Self::Increment { obj, prop, by } => tx.increment(obj, prop.clone(), *by),
+            Self::Splice {
+                obj,
+                index,
+                del,
+                values,
+            } => {
+                tx.splice(obj, *index, *del, std::iter::empty())?;
+                for (i, value) in values.iter().enumerate() {
+                    match value {
+                        Value::Object(obj_type) => {
+                            tx.insert_object(obj, index + i, *obj_type)?;
+                        }
+                        Value::Scalar(value) => {
+                            tx.insert(obj, index + i, value.as_ref().clone())?
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Self::SpliceText {
+                obj,
+                index,
+                del,
+                text,
+            } => tx.splice_text(obj, *index, *del, text).map(|_| ()),
+        }
+    }
+}
+
+/// The inverse of replacing or deleting `prop` in the map `obj`, or of replacing the element at
+/// `prop` in the list `obj`
+pub(crate) fn restore_prop<R: ReadDoc>(
+    doc: &R,
+    obj: &ExId,
+    prop: &Prop,
+) -> Result<InverseOp, AutomergeError> {
+    let obj = obj.clone();
+    let prop = prop.clone();
+    Ok(match doc.get(&obj, prop.clone())? {
+        Some((value, _)) => InverseOp::Put {
+            obj,
+            prop,
+            value: value.into_owned(),
+        },
+        None => InverseOp::Delete { obj, prop },
+    })
+}
+
+/// The inverse of deleting `del` elements of the sequence `obj` at `index` and then inserting
+/// `inserted` elements there
+pub(crate) fn restore_range<R: ReadDoc>(
+    doc: &R,
+    obj: &ExId,
+    index: usize,
+    del: usize,
+    inserted: usize,
+) -> Result<InverseOp, AutomergeError> {
+    let end = index.saturating_add(del);
+    let obj = obj.clone();
+    Ok(match doc.object_type(&obj)? {
+        ObjType::Text => InverseOp::SpliceText {
+            text: doc.text_slice(&obj, index, end)?,
+            obj,
+            index,
+            del: inserted,
+        },
+        _ => InverseOp::Splice {
+            values: doc
+                .list_range(&obj, index..end)
+                .map(|(_, value, _)| value.into_owned())
+                .collect(),
+            obj,
+            index,
+            del: inserted,
+        },
+    })
+}
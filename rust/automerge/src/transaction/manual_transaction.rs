@@ -1,15 +1,20 @@
+use std::collections::BTreeMap;
 use std::ops::RangeBounds;
 
 use crate::exid::ExId;
 use crate::marks::{ExpandMark, Mark};
 use crate::op_observer::BranchableObserver;
 use crate::{
-    Automerge, ChangeHash, KeysAt, ObjType, OpObserver, Prop, ReadDoc, ScalarValue, Value, Values,
+    ActorId, Automerge, ChangeHash, KeysAt, ObjType, OpObserver, Prop, ReadDoc, ScalarValue,
+    TextCell, Value, ValueType, Values,
 };
 use crate::{AutomergeError, Keys};
 use crate::{ListRange, ListRangeAt, MapRange, MapRangeAt};
 
-use super::{observation, CommitOptions, Transactable, TransactionArgs, TransactionInner};
+use super::inverse::{self, InverseOp};
+use super::{
+    observation, CommitOptions, SpliceResult, Transactable, TransactionArgs, TransactionInner,
+};
 
 /// A transaction on a document.
 /// Transactions group operations into a single change so that no other operations can happen
@@ -31,6 +36,8 @@ pub struct Transaction<'a, Obs: observation::Observation> {
     // As with `inner` this is an `Option` so we can `take` it during `commit`
     observation: Option<Obs>,
     doc: &'a mut Automerge,
+    // `Some` once `record_inverse` has been called
+    inverse: Option<Vec<InverseOp>>,
 }
 
 impl<'a, Obs: observation::Observation> Transaction<'a, Obs> {
@@ -39,6 +46,7 @@ impl<'a, Obs: observation::Observation> Transaction<'a, Obs> {
             inner: Some(TransactionInner::new(args)),
             doc,
             observation: Some(obs),
+            inverse: None,
         }
     }
 }
@@ -97,6 +105,33 @@ impl<'a, Obs: observation::Observation> Transaction<'a, Obs> {
         obs.make_result(hash)
     }
 
+    /// Commit and hand back the observation so it can be merged into the one it was branched
+    /// from
+    pub(crate) fn commit_observed(mut self) -> (Obs, Option<ChangeHash>) {
+        let tx = self.inner.take().unwrap();
+        let hash = tx.commit(self.doc, None, None);
+        (self.observation.take().unwrap(), hash)
+    }
+
+    /// Start recording the inverse of every operation made in this transaction from now on, see
+    /// [`Self::inverse`]
+    pub fn record_inverse(&mut self) {
+        if self.inverse.is_none() {
+            self.inverse = Some(Vec::new());
+        }
+    }
+
+    /// Get the operations which undo everything done since [`Self::record_inverse`] was called
+    ///
+    /// The operations are in the order they should be applied in, the inverse of the last
+    /// operation comes first. Committing consumes the transaction so call this just before
+    /// committing and keep the result as an undo entry, which can later be applied in another
+    /// transaction with [`InverseOp::apply`]. Marks are not recorded. Returns an empty vector if
+    /// recording was never started.
+    pub fn inverse(&self) -> Vec<InverseOp> {
+        self.inverse.iter().flatten().rev().cloned().collect()
+    }
+
     /// Undo the operations added in this transaction, returning the number of cancelled
     /// operations.
     pub fn rollback(mut self) -> usize {
@@ -114,6 +149,36 @@ impl<'a, Obs: observation::Observation> Transaction<'a, Obs> {
             f(tx, self.doc, None)
         }
     }
+
+    /// Run `f` to build the inverse of the next operation if we are recording
+    fn undo_with<F>(&self, f: F) -> Result<Option<InverseOp>, AutomergeError>
+    where
+        F: FnOnce(&Self) -> Result<InverseOp, AutomergeError>,
+    {
+        self.inverse.as_ref().map(|_| f(self)).transpose()
+    }
+
+    fn record(&mut self, undo: Option<InverseOp>) {
+        if let (Some(ops), Some(undo)) = (self.inverse.as_mut(), undo) {
+            ops.push(undo);
+        }
+    }
+
+    /// Perform `f`, which inserts elements into the sequence `obj` at `index`, recording the
+    /// deletion of whatever it inserted
+    fn insert_with<F, T>(&mut self, obj: &ExId, index: usize, f: F) -> Result<T, AutomergeError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, AutomergeError>,
+    {
+        let before = self.inverse.as_ref().map(|_| self.length(obj));
+        let result = f(self)?;
+        if let Some(before) = before {
+            let inserted = self.length(obj) - before;
+            let undo = inverse::restore_range(self, obj, index, 0, inserted)?;
+            self.record(Some(undo));
+        }
+        Ok(result)
+    }
 }
 
 impl<'a, Obs: observation::Observation> ReadDoc for Transaction<'a, Obs> {
@@ -159,6 +224,21 @@ impl<'a, Obs: observation::Observation> ReadDoc for Transaction<'a, Obs> {
         self.doc.list_range_at(obj, range, heads)
     }
 
+    fn entries_typed<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<Vec<(String, ValueType)>, AutomergeError> {
+        self.doc.entries_typed(obj)
+    }
+
+    fn entries_typed_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Vec<(String, ValueType)>, AutomergeError> {
+        self.doc.entries_typed_at(obj, heads)
+    }
+
     fn values<O: AsRef<ExId>>(&self, obj: O) -> Values<'_> {
         self.doc.values(obj)
     }
@@ -171,6 +251,14 @@ impl<'a, Obs: observation::Observation> ReadDoc for Transaction<'a, Obs> {
         self.doc.length(obj)
     }
 
+    fn text_length_utf16<O: AsRef<ExId>>(&self, obj: O) -> Result<usize, AutomergeError> {
+        self.doc.text_length_utf16(obj)
+    }
+
+    fn text_length_chars<O: AsRef<ExId>>(&self, obj: O) -> Result<usize, AutomergeError> {
+        self.doc.text_length_chars(obj)
+    }
+
     fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize {
         self.doc.length_at(obj, heads)
     }
@@ -179,10 +267,47 @@ impl<'a, Obs: observation::Observation> ReadDoc for Transaction<'a, Obs> {
         self.doc.object_type(obj)
     }
 
+    fn objects_of_type(&self, obj_type: ObjType) -> Vec<ExId> {
+        self.doc.objects_of_type(obj_type)
+    }
+
+    fn index_of<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        elem: &ExId,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Option<usize>, AutomergeError> {
+        self.doc.index_of(obj, elem, heads)
+    }
+
+    fn marks_at_index<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        index: usize,
+    ) -> Result<BTreeMap<String, ScalarValue>, AutomergeError> {
+        self.doc.marks_at_index(obj, index)
+    }
+
     fn text<O: AsRef<ExId>>(&self, obj: O) -> Result<String, AutomergeError> {
         self.doc.text(obj)
     }
 
+    fn text_with_tombstones<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<Vec<TextCell>, AutomergeError> {
+        self.doc.text_with_tombstones(obj)
+    }
+
+    fn text_slice<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        start: usize,
+        end: usize,
+    ) -> Result<String, AutomergeError> {
+        self.doc.text_slice(obj, start, end)
+    }
+
     fn text_at<O: AsRef<ExId>>(
         &self,
         obj: O,
@@ -195,6 +320,19 @@ impl<'a, Obs: observation::Observation> ReadDoc for Transaction<'a, Obs> {
         self.doc.marks(obj)
     }
 
+    fn has_mark<O: AsRef<ExId>>(&self, obj: O, name: &str) -> Result<bool, AutomergeError> {
+        self.doc.has_mark(obj, name)
+    }
+
+    fn marks_in_range<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<Mark<'static>>, AutomergeError> {
+        self.doc.marks_in_range(obj, start, end)
+    }
+
     fn marks_at<O: AsRef<ExId>>(
         &self,
         obj: O,
@@ -203,6 +341,16 @@ impl<'a, Obs: observation::Observation> ReadDoc for Transaction<'a, Obs> {
         self.doc.marks_at(obj, heads)
     }
 
+    fn marks_after_splice<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        pos: usize,
+        del: usize,
+        insert_len: usize,
+    ) -> Result<Vec<Mark<'static>>, AutomergeError> {
+        self.doc.marks_after_splice(obj, pos, del, insert_len)
+    }
+
     fn get<O: AsRef<ExId>, P: Into<Prop>>(
         &self,
         obj: O,
@@ -237,6 +385,14 @@ impl<'a, Obs: observation::Observation> ReadDoc for Transaction<'a, Obs> {
         self.doc.get_all_at(obj, prop, heads)
     }
 
+    fn counter_components<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(ActorId, i64)>, AutomergeError> {
+        self.doc.counter_components(obj, prop)
+    }
+
     fn parents<O: AsRef<ExId>>(&self, obj: O) -> Result<crate::Parents<'_>, AutomergeError> {
         self.doc.parents(obj)
     }
@@ -274,7 +430,12 @@ impl<'a, Obs: observation::Observation> Transactable for Transaction<'a, Obs> {
         prop: P,
         value: V,
     ) -> Result<(), AutomergeError> {
-        self.do_tx(|tx, doc, obs| tx.put(doc, obs, obj.as_ref(), prop, value))
+        let obj = obj.as_ref();
+        let prop = prop.into();
+        let undo = self.undo_with(|doc| inverse::restore_prop(doc, obj, &prop))?;
+        self.do_tx(|tx, doc, obs| tx.put(doc, obs, obj, prop, value))?;
+        self.record(undo);
+        Ok(())
     }
 
     fn put_object<O: AsRef<ExId>, P: Into<Prop>>(
@@ -283,7 +444,12 @@ impl<'a, Obs: observation::Observation> Transactable for Transaction<'a, Obs> {
         prop: P,
         value: ObjType,
     ) -> Result<ExId, AutomergeError> {
-        self.do_tx(|tx, doc, obs| tx.put_object(doc, obs, obj.as_ref(), prop, value))
+        let obj = obj.as_ref();
+        let prop = prop.into();
+        let undo = self.undo_with(|doc| inverse::restore_prop(doc, obj, &prop))?;
+        let id = self.do_tx(|tx, doc, obs| tx.put_object(doc, obs, obj, prop, value))?;
+        self.record(undo);
+        Ok(id)
     }
 
     fn insert<O: AsRef<ExId>, V: Into<ScalarValue>>(
@@ -292,7 +458,10 @@ impl<'a, Obs: observation::Observation> Transactable for Transaction<'a, Obs> {
         index: usize,
         value: V,
     ) -> Result<(), AutomergeError> {
-        self.do_tx(|tx, doc, obs| tx.insert(doc, obs, obj.as_ref(), index, value))
+        let obj = obj.as_ref();
+        self.insert_with(obj, index, |t| {
+            t.do_tx(|tx, doc, obs| tx.insert(doc, obs, obj, index, value))
+        })
     }
 
     fn insert_object<O: AsRef<ExId>>(
@@ -301,7 +470,46 @@ impl<'a, Obs: observation::Observation> Transactable for Transaction<'a, Obs> {
         index: usize,
         value: ObjType,
     ) -> Result<ExId, AutomergeError> {
-        self.do_tx(|tx, doc, obs| tx.insert_object(doc, obs, obj.as_ref(), index, value))
+        let obj = obj.as_ref();
+        self.insert_with(obj, index, |t| {
+            t.do_tx(|tx, doc, obs| tx.insert_object(doc, obs, obj, index, value))
+        })
+    }
+
+    fn insert_many<O: AsRef<ExId>, V: Into<ScalarValue>, I: IntoIterator<Item = V>>(
+        &mut self,
+        obj: O,
+        index: usize,
+        vals: I,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        let obj = obj.as_ref();
+        self.insert_with(obj, index, |t| {
+            t.do_tx(|tx, doc, obs| tx.insert_many(doc, obs, obj, index, vals))
+        })
+    }
+
+    fn push<O: AsRef<ExId>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        value: V,
+    ) -> Result<ExId, AutomergeError> {
+        let obj = obj.as_ref();
+        let index = self.length(obj);
+        self.insert_with(obj, index, |t| {
+            t.do_tx(|tx, doc, obs| tx.push(doc, obs, obj, value))
+        })
+    }
+
+    fn push_object<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        value: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        let obj = obj.as_ref();
+        let index = self.length(obj);
+        self.insert_with(obj, index, |t| {
+            t.do_tx(|tx, doc, obs| tx.push_object(doc, obs, obj, value))
+        })
     }
 
     fn increment<O: AsRef<ExId>, P: Into<Prop>>(
@@ -310,7 +518,16 @@ impl<'a, Obs: observation::Observation> Transactable for Transaction<'a, Obs> {
         prop: P,
         value: i64,
     ) -> Result<(), AutomergeError> {
-        self.do_tx(|tx, doc, obs| tx.increment(doc, obs, obj.as_ref(), prop, value))
+        let obj = obj.as_ref();
+        let prop = prop.into();
+        let undo = InverseOp::Increment {
+            obj: obj.clone(),
+            prop: prop.clone(),
+            by: value.wrapping_neg(),
+        };
+        self.do_tx(|tx, doc, obs| tx.increment(doc, obs, obj, prop, value))?;
+        self.record(Some(undo));
+        Ok(())
     }
 
     fn delete<O: AsRef<ExId>, P: Into<Prop>>(
@@ -318,19 +535,70 @@ impl<'a, Obs: observation::Observation> Transactable for Transaction<'a, Obs> {
         obj: O,
         prop: P,
     ) -> Result<(), AutomergeError> {
-        self.do_tx(|tx, doc, obs| tx.delete(doc, obs, obj.as_ref(), prop))
+        self.delete_checked(obj, prop).map(|_| ())
+    }
+
+    fn delete_checked<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+    ) -> Result<bool, AutomergeError> {
+        let obj = obj.as_ref();
+        let prop = prop.into();
+        let undo = self.undo_with(|doc| match &prop {
+            Prop::Map(_) => inverse::restore_prop(doc, obj, &prop),
+            Prop::Seq(index) => inverse::restore_range(doc, obj, *index, 1, 0),
+        })?;
+        let deleted = self.do_tx(|tx, doc, obs| tx.delete(doc, obs, obj, prop))?;
+        if deleted {
+            self.record(undo);
+        }
+        Ok(deleted)
+    }
+
+    fn delete_elem<O: AsRef<ExId>>(&mut self, obj: O, elem: &ExId) -> Result<(), AutomergeError> {
+        let obj = obj.as_ref();
+        let undo = self.undo_with(|doc| match doc.index_of(obj, elem, None)? {
+            Some(index) => inverse::restore_range(doc, obj, index, 1, 0),
+            None => Err(AutomergeError::ElemDeleted(elem.clone())),
+        })?;
+        self.do_tx(|tx, doc, obs| tx.delete_elem(doc, obs, obj, elem))?;
+        self.record(undo);
+        Ok(())
+    }
+
+    fn clear<O: AsRef<ExId>>(&mut self, obj: O) -> Result<(), AutomergeError> {
+        let obj = obj.as_ref();
+        let mut undo = Vec::new();
+        if self.inverse.is_some() {
+            match self.object_type(obj)? {
+                ObjType::Map | ObjType::Table => {
+                    for key in self.keys(obj) {
+                        undo.push(inverse::restore_prop(self, obj, &Prop::Map(key))?);
+                    }
+                }
+                _ => undo.push(inverse::restore_range(self, obj, 0, self.length(obj), 0)?),
+            }
+        }
+        self.do_tx(|tx, doc, obs| tx.clear(doc, obs, obj))?;
+        for undo in undo {
+            self.record(Some(undo));
+        }
+        Ok(())
     }
 
-    /// Splice new elements into the given sequence. Returns a vector of the OpIds used to insert
-    /// the new elements
     fn splice<O: AsRef<ExId>, V: IntoIterator<Item = ScalarValue>>(
         &mut self,
         obj: O,
         pos: usize,
         del: usize,
         vals: V,
-    ) -> Result<(), AutomergeError> {
-        self.do_tx(|tx, doc, obs| tx.splice(doc, obs, obj.as_ref(), pos, del, vals))
+    ) -> Result<SpliceResult, AutomergeError> {
+        let obj = obj.as_ref();
+        let undo = self.undo_with(|doc| inverse::restore_range(doc, obj, pos, del, 0))?;
+        let result = self.do_tx(|tx, doc, obs| tx.splice(doc, obs, obj, pos, del, vals))?;
+        self.record(undo.map(|undo| undo.with_del(result.at, result.inserted)));
+        Ok(result)
     }
 
     fn splice_text<O: AsRef<ExId>>(
@@ -339,8 +607,25 @@ impl<'a, Obs: observation::Observation> Transactable for Transaction<'a, Obs> {
         pos: usize,
         del: usize,
         text: &str,
-    ) -> Result<(), AutomergeError> {
-        self.do_tx(|tx, doc, obs| tx.splice_text(doc, obs, obj.as_ref(), pos, del, text))
+    ) -> Result<SpliceResult, AutomergeError> {
+        self.splice_text_with_expand(obj, pos, del, text, None)
+    }
+
+    fn splice_text_with_expand<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: usize,
+        text: &str,
+        expand_override: Option<ExpandMark>,
+    ) -> Result<SpliceResult, AutomergeError> {
+        let obj = obj.as_ref();
+        let undo = self.undo_with(|doc| inverse::restore_range(doc, obj, pos, del, 0))?;
+        let result = self.do_tx(|tx, doc, obs| {
+            tx.splice_text_with_expand(doc, obs, obj, pos, del, text, expand_override)
+        })?;
+        self.record(undo.map(|undo| undo.with_del(result.at, result.inserted)));
+        Ok(result)
     }
 
     fn mark<O: AsRef<ExId>>(
@@ -18,3 +18,20 @@ pub struct Failure<E> {
     /// The number of operations cancelled.
     pub cancelled: usize,
 }
+
+/// The region of a sequence affected by a splice.
+///
+/// For lists all values are counted in elements, for text they are counted in the text encoding
+/// of the document. Use this to figure out which part of a sequence needs to be re-rendered
+/// after a splice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpliceResult {
+    /// The number of elements which were actually removed. This may be less than the number
+    /// requested if the deletion ran past the end of the sequence.
+    pub removed: usize,
+    /// The number of elements which were inserted.
+    pub inserted: usize,
+    /// The index at which elements were removed and inserted. For text this may be before the
+    /// requested position if the splice started in the middle of a multi-unit character.
+    pub at: usize,
+}
@@ -1,6 +1,7 @@
 use crate::exid::ExId;
 use crate::marks::{ExpandMark, Mark};
-use crate::{AutomergeError, ChangeHash, ObjType, Prop, ReadDoc, ScalarValue};
+use crate::transaction::SpliceResult;
+use crate::{AutomergeError, ChangeHash, ObjType, Prop, ReadDoc, ScalarValue, Value};
 
 /// A way of mutating a document within a single change.
 pub trait Transactable: ReadDoc {
@@ -41,6 +42,124 @@ pub trait Transactable: ReadDoc {
         object: ObjType,
     ) -> Result<ExId, AutomergeError>;
 
+    /// Set the value of property `P` in object `obj` and return the value it had before.
+    ///
+    /// The previous value is the one [`ReadDoc::get`] would have returned, including any changes
+    /// made earlier in this transaction. If `value` is a [`Value::Object`] a new empty object of
+    /// that type is created, as with [`Self::put_object`], and its ID is returned as the second
+    /// element. For a scalar the second element is `None`.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`Self::put`] and [`Self::put_object`].
+    fn replace<O: AsRef<ExId>, P: Into<Prop>, V: Into<Value<'static>>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        value: V,
+    ) -> Result<(Option<Value<'static>>, Option<ExId>), AutomergeError> {
+        let prop = prop.into();
+        let previous = self
+            .get(obj.as_ref(), prop.clone())?
+            .map(|(value, _)| value.into_owned());
+        let created = match value.into() {
+            Value::Object(object) => Some(self.put_object(obj, prop, object)?),
+            Value::Scalar(value) => {
+                self.put(obj, prop, value.into_owned())?;
+                None
+            }
+        };
+        Ok((previous, created))
+    }
+
+    /// Get the object of type `object` at `prop` in `obj`, creating it if there is nothing there.
+    ///
+    /// # Returns
+    ///
+    /// The id of the existing or newly created object.
+    ///
+    /// # Errors
+    ///
+    /// As well as the errors [`Self::put_object`] can return this will return
+    /// [`AutomergeError::Conflict`] if there is already a value at `prop` which is not an object
+    /// of type `object`, that value is left as it is.
+    fn get_or_create_object<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        object: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        let prop = prop.into();
+        match self.get(obj.as_ref(), prop.clone())? {
+            Some((Value::Object(existing), id)) if existing == object => Ok(id),
+            Some((existing, _)) => Err(AutomergeError::Conflict {
+                prop,
+                expected: object,
+                found: existing.value_type(),
+            }),
+            None => self.put_object(obj, prop, object),
+        }
+    }
+
+    /// Set the value of property `P` to value `V` unless that is already its value.
+    ///
+    /// If the value which currently wins at `prop` is a scalar equal to `value` no op is created
+    /// and this returns `false`, otherwise this is [`Self::put`] and returns `true`. This is for
+    /// code which writes every field whether or not it changed, where a put of the same value
+    /// would grow the history and conflict with concurrent writes for no reason. Values are
+    /// compared with [`ScalarValue`] equality, so an `Int` and a `Uint` holding the same number
+    /// are different and a NaN float always writes. Conflicting values which don't win are not
+    /// looked at.
+    fn put_if_changed<O: AsRef<ExId>, P: Into<Prop>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        value: V,
+    ) -> Result<bool, AutomergeError> {
+        let prop = prop.into();
+        let value = value.into();
+        if let Some((Value::Scalar(current), _)) = self.get(obj.as_ref(), prop.clone())? {
+            if *current == value {
+                return Ok(false);
+            }
+        }
+        self.put(obj, prop, value)?;
+        Ok(true)
+    }
+
+    /// Set the value of property `P` to `bytes`, read them back with
+    /// [`ReadDoc::get_bytes`](crate::ReadDoc::get_bytes).
+    ///
+    /// This is [`Self::put`] with [`ScalarValue::Bytes`], the bytes are copied into the document
+    /// as a single value.
+    fn put_bytes<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        bytes: &[u8],
+    ) -> Result<(), AutomergeError> {
+        self.put(obj, prop, ScalarValue::Bytes(bytes.to_vec()))
+    }
+
+    /// Set the value of property `P` to a new text object containing `text`.
+    ///
+    /// [`Self::put`] always stores a string as a single scalar value, use this when the string
+    /// should be collaboratively editable instead.
+    ///
+    /// # Returns
+    ///
+    /// The id of the text object which was created.
+    fn put_text<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        text: &str,
+    ) -> Result<ExId, AutomergeError> {
+        let id = self.put_object(obj, prop, ObjType::Text)?;
+        self.splice_text(&id, 0, 0, text)?;
+        Ok(id)
+    }
+
     /// Insert a value into a list at the given index.
     fn insert<O: AsRef<ExId>, V: Into<ScalarValue>>(
         &mut self,
@@ -49,6 +168,21 @@ pub trait Transactable: ReadDoc {
         value: V,
     ) -> Result<(), AutomergeError>;
 
+    /// Insert `vals` into a list one after the other starting at the given index.
+    ///
+    /// This is the same as [`Self::splice`] with nothing deleted but takes anything which
+    /// converts into a [`ScalarValue`].
+    ///
+    /// # Returns
+    ///
+    /// The ids of the inserted elements, in order.
+    fn insert_many<O: AsRef<ExId>, V: Into<ScalarValue>, I: IntoIterator<Item = V>>(
+        &mut self,
+        obj: O,
+        index: usize,
+        vals: I,
+    ) -> Result<Vec<ExId>, AutomergeError>;
+
     /// Insert an object into a list at the given index.
     fn insert_object<O: AsRef<ExId>>(
         &mut self,
@@ -57,7 +191,62 @@ pub trait Transactable: ReadDoc {
         object: ObjType,
     ) -> Result<ExId, AutomergeError>;
 
+    /// Like [`Self::insert_object`] but also returns the index the new object is at
+    ///
+    /// The index is read back from the document, including everything done so far in this
+    /// transaction, so it can be used for further edits straight away. Inserting at or before it
+    /// later in the transaction moves the object along, use [`ReadDoc::index_of`] with the
+    /// returned ID to find it again after that.
+    fn insert_object_at<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        index: usize,
+        object: ObjType,
+    ) -> Result<(ExId, usize), AutomergeError> {
+        let obj = obj.as_ref();
+        let id = self.insert_object(obj, index, object)?;
+        let index = self
+            .index_of(obj, &id, None)?
+            .ok_or_else(|| AutomergeError::InvalidElemId(id.clone()))?;
+        Ok((id, index))
+    }
+
+    /// Insert a new text object containing `text` into a list at the given index, see
+    /// [`Self::put_text`].
+    fn insert_text<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        index: usize,
+        text: &str,
+    ) -> Result<ExId, AutomergeError> {
+        let id = self.insert_object(obj, index, ObjType::Text)?;
+        self.splice_text(&id, 0, 0, text)?;
+        Ok(id)
+    }
+
+    /// Append a value to the end of a list, returning the ID of the new element.
+    ///
+    /// The end is found when the value is inserted, so this includes anything already inserted
+    /// in this transaction.
+    fn push<O: AsRef<ExId>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        value: V,
+    ) -> Result<ExId, AutomergeError>;
+
+    /// Append an object to the end of a list, see [`Self::push`].
+    fn push_object<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        object: ObjType,
+    ) -> Result<ExId, AutomergeError>;
+
     /// Increment the counter at the prop in the object by `value`.
+    ///
+    /// For a list `prop` is the index of the element, so `Prop::Seq(i)` (or just `i`) increments
+    /// the counter stored as element `i`. If the value at `prop` is not a counter this returns
+    /// [`AutomergeError::MissingCounter`] and if there is no value at `prop`, including an index
+    /// past the end of a list, it returns [`AutomergeError::MissingProp`].
     fn increment<O: AsRef<ExId>, P: Into<Prop>>(
         &mut self,
         obj: O,
@@ -72,13 +261,105 @@ pub trait Transactable: ReadDoc {
         prop: P,
     ) -> Result<(), AutomergeError>;
 
+    /// Like [`Self::delete`] but returns whether a value was removed.
+    ///
+    /// Deleting a key which is not in a map returns `false`. Deleting an index past the end of a
+    /// list, including `index == len`, is an error, just as it is for [`Self::delete`]. Text is
+    /// different: deleting at or past the end of a text object returns `Ok(false)` rather than
+    /// an error.
+    fn delete_checked<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+    ) -> Result<bool, AutomergeError>;
+
+    /// Delete the element `elem` of the list or text object `obj`, wherever it is now.
+    ///
+    /// `elem` is the ID of the operation which inserted the element, as used by
+    /// [`ReadDoc::index_of`](crate::ReadDoc::index_of). Unlike deleting by index this can't
+    /// remove the wrong element if other elements have been inserted or deleted concurrently.
+    /// Returns [`AutomergeError::InvalidElemId`] if `elem` is not an element of `obj` and
+    /// [`AutomergeError::ElemDeleted`] if it has already been deleted.
+    fn delete_elem<O: AsRef<ExId>>(&mut self, obj: O, elem: &ExId) -> Result<(), AutomergeError>;
+
+    /// Delete every key of a map or every element of a list or text object.
+    ///
+    /// The object itself stays where it is, it is just left empty.
+    fn clear<O: AsRef<ExId>>(&mut self, obj: O) -> Result<(), AutomergeError>;
+
+    /// Delete `del` elements starting at `pos` and insert `vals` in their place.
+    ///
+    /// `pos` may be anything up to and including the length of the sequence, splicing at the
+    /// length appends. A `pos` past the end returns [`AutomergeError::IndexOutOfBounds`].
+    ///
+    /// If `del` runs past the end of the sequence only the remaining elements are deleted, the
+    /// returned [`SpliceResult`] reports how many were actually removed.
     fn splice<O: AsRef<ExId>, V: IntoIterator<Item = ScalarValue>>(
         &mut self,
         obj: O,
         pos: usize,
         del: usize,
         vals: V,
-    ) -> Result<(), AutomergeError>;
+    ) -> Result<SpliceResult, AutomergeError>;
+
+    /// Like [`Self::splice`] but for any values which convert into a [`ScalarValue`], e.g. a
+    /// `Vec<i64>` or `Vec<String>`
+    fn splice_from<O: AsRef<ExId>, T: Into<ScalarValue>, I: IntoIterator<Item = T>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: usize,
+        items: I,
+    ) -> Result<SpliceResult, AutomergeError> {
+        self.splice(obj, pos, del, items.into_iter().map(Into::into))
+    }
+
+    /// Delete `del` elements starting at `pos` and insert the values `gen` generates in their
+    /// place
+    ///
+    /// `gen` is called with `0`, `1`, `2` and so on until it returns `None` and each value is
+    /// inserted after the previous one as it is generated, so nothing is collected up front.
+    /// Unlike [`Self::splice`] the values may be objects, which are created empty, and strings in
+    /// a text object are spliced in as with [`Self::splice_text`]. Returns the IDs of the
+    /// inserted elements in order, for a string of more than one character that is the first
+    /// one. Each value is inserted with a separate search of the sequence, [`Self::splice`] is
+    /// quicker for values which are already to hand.
+    fn splice_with<'v, O: AsRef<ExId>, F: FnMut(usize) -> Option<Value<'v>>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: usize,
+        mut gen: F,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        let obj = obj.as_ref();
+        let is_text = self.object_type(obj)? == ObjType::Text;
+        let mut index = self.splice(obj, pos, del, std::iter::empty())?.at;
+        let mut ids = Vec::new();
+        let mut i = 0;
+        while let Some(value) = gen(i) {
+            let len = self.length(obj);
+            let id = match value {
+                Value::Object(obj_type) => self.insert_object(obj, index, obj_type)?,
+                // text indexes are in the text encoding of the document, which only
+                // `splice_text` knows about
+                Value::Scalar(value) if is_text && value.is_str() => {
+                    self.splice_text(obj, index, 0, value.to_str().unwrap_or_default())?;
+                    self.get(obj, index)?
+                        .map(|(_, id)| id)
+                        .ok_or(AutomergeError::InvalidIndex(index))?
+                }
+                Value::Scalar(value) => {
+                    let mut inserted = self.insert_many(obj, index, Some(value.into_owned()))?;
+                    inserted.remove(0)
+                }
+            };
+            // in text an element can be more than one index wide
+            index += self.length(obj) - len;
+            ids.push(id);
+            i += 1;
+        }
+        Ok(ids)
+    }
 
     /// Like [`Self::splice`] but for text.
     fn splice_text<O: AsRef<ExId>>(
@@ -87,9 +368,38 @@ pub trait Transactable: ReadDoc {
         pos: usize,
         del: usize,
         text: &str,
-    ) -> Result<(), AutomergeError>;
+    ) -> Result<SpliceResult, AutomergeError>;
+
+    /// Like [`Self::splice_text`] but choosing whether the inserted text gets the marks which
+    /// start or end at `pos`.
+    ///
+    /// When `expand_override` is `Some` every mark which starts or ends at `pos` is treated as if
+    /// it had been created with that [`ExpandMark`], just for this splice. `None` behaves exactly
+    /// like [`Self::splice_text`]. Marks which contain `pos` strictly inside them are not affected,
+    /// inserted text always gets those.
+    ///
+    /// The anchors of all the marks which start or end at `pos` sit between the same two
+    /// characters and the inserted text can only go in one place among them. It goes after each
+    /// start it is allowed to expand and each end it is not, in the order the anchors are in. So
+    /// when one mark ends at `pos` and another starts there no single position may satisfy the
+    /// override for both, e.g. [`ExpandMark::Both`] can't put the text in the mark which ends
+    /// at `pos` if its end comes before the other's start, and which one the text gets then
+    /// depends on the order the marks were made in.
+    fn splice_text_with_expand<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: usize,
+        text: &str,
+        expand_override: Option<ExpandMark>,
+    ) -> Result<SpliceResult, AutomergeError>;
 
     /// Mark a sequence
+    ///
+    /// `obj` may be a text object or a list, marks on a list cover a range of its elements in
+    /// the same way marks on text cover a range of characters and are read back with
+    /// [`ReadDoc::marks`](crate::ReadDoc::marks). Marking a map or table returns
+    /// [`AutomergeError::InvalidOp`].
     fn mark<O: AsRef<ExId>>(
         &mut self,
         obj: O,
@@ -107,6 +417,27 @@ pub trait Transactable: ReadDoc {
         expand: ExpandMark,
     ) -> Result<(), AutomergeError>;
 
+    /// Remove every span of the mark called `name` from a sequence, returning how many spans
+    /// were removed
+    ///
+    /// Each span [`ReadDoc::marks`](crate::ReadDoc::marks) returns for `name` is unmarked with
+    /// [`ExpandMark::Both`], so text typed at either end of a removed span doesn't pick the
+    /// mark back up from it. As with [`Self::unmark`] the mark may still show up in
+    /// [`ReadDoc::marks`](crate::ReadDoc::marks) as spans which cover nothing, these are not
+    /// counted.
+    fn unmark_all<O: AsRef<ExId>>(&mut self, obj: O, name: &str) -> Result<usize, AutomergeError> {
+        let spans = self
+            .marks(obj.as_ref())?
+            .iter()
+            .filter(|m| m.name() == name && m.start < m.end)
+            .map(|m| (m.start, m.end))
+            .collect::<Vec<_>>();
+        for (start, end) in &spans {
+            self.unmark(obj.as_ref(), name, *start, *end, ExpandMark::Both)?;
+        }
+        Ok(spans.len())
+    }
+
     /// The heads this transaction will be based on
     fn base_heads(&self) -> Vec<ChangeHash>;
 }
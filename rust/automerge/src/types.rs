@@ -1,5 +1,6 @@
 use crate::error;
 use crate::legacy as amp;
+use crate::marks::ExpandMark;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cmp::Eq;
@@ -44,11 +45,55 @@ impl fmt::Debug for ActorId {
     }
 }
 
+#[cfg(feature = "deterministic")]
+thread_local! {
+    static NEXT_SEED: std::cell::Cell<u64> = std::cell::Cell::new(0);
+}
+
 impl ActorId {
+    /// Create a new random actor id.
+    ///
+    /// With the `deterministic` feature enabled this is not random, it returns
+    /// [`Self::from_seed`] of a counter kept per thread, see `ActorId::reset_seed`.
+    #[cfg(not(feature = "deterministic"))]
     pub fn random() -> ActorId {
         ActorId(TinyVec::from(*uuid::Uuid::new_v4().as_bytes()))
     }
 
+    /// Create a new random actor id.
+    ///
+    /// With the `deterministic` feature enabled this is not random, it returns
+    /// [`Self::from_seed`] of a counter kept per thread, see [`Self::reset_seed`].
+    #[cfg(feature = "deterministic")]
+    pub fn random() -> ActorId {
+        NEXT_SEED.with(|next| {
+            let seed = next.get();
+            next.set(seed.wrapping_add(1));
+            ActorId::from_seed(seed)
+        })
+    }
+
+    /// Restart the sequence of actor ids returned by [`Self::random`] on this thread from `seed`
+    ///
+    /// Only available with the `deterministic` feature.
+    #[cfg(feature = "deterministic")]
+    pub fn reset_seed(seed: u64) {
+        NEXT_SEED.with(|next| next.set(seed))
+    }
+
+    /// Create an actor id which is always the same for the same `seed`.
+    ///
+    /// This is meant for tests and tooling which need reproducible output. Don't use it for real
+    /// documents: two peers which pick the same seed get the same actor id and their changes will
+    /// collide, which is exactly what [`Self::random`] is there to avoid.
+    pub fn from_seed(seed: u64) -> ActorId {
+        use sha2::{Digest, Sha256};
+        let hash = Sha256::digest(seed.to_be_bytes());
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&hash[..16]);
+        ActorId(TinyVec::from(bytes))
+    }
+
     pub fn to_bytes(&self) -> &[u8] {
         &self.0
     }
@@ -554,6 +599,24 @@ impl Default for TextEncoding {
     }
 }
 
+/// The line ending to use for [`crate::ReadDoc::text_normalized`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl LineEnding {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
 impl ListEncoding {
     pub(crate) fn new(obj: ObjType, text_encoding: TextEncoding) -> Self {
         if obj == ObjType::Text {
@@ -682,6 +745,16 @@ impl Op {
             )
     }
 
+    /// Like [`Self::valid_mark_anchor`] but as if the mark had been created with `expand`
+    pub(crate) fn valid_mark_anchor_with(&self, expand: ExpandMark) -> bool {
+        self.succ.is_empty()
+            && match &self.action {
+                OpType::MarkBegin(_, _) => expand.before(),
+                OpType::MarkEnd(_) => !expand.after(),
+                _ => false,
+            }
+    }
+
     pub(crate) fn is_noop(&self, action: &OpType) -> bool {
         matches!((&self.action, action), (OpType::Put(n), OpType::Put(m)) if n == m)
     }
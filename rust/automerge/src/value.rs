@@ -3,6 +3,7 @@ use crate::types::ObjType;
 use serde::{Deserialize, Serialize, Serializer};
 use smol_str::SmolStr;
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::fmt;
 
 /// The type of values in an automerge document
@@ -16,6 +17,23 @@ pub enum Value<'a> {
     Scalar(Cow<'a, ScalarValue>),
 }
 
+/// The type of a [`Value`] without the value itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    Object(ObjType),
+    Bytes,
+    Str,
+    Int,
+    Uint,
+    F64,
+    Counter,
+    Timestamp,
+    Boolean,
+    /// A value from a future version of automerge
+    Unknown,
+    Null,
+}
+
 impl<'a> Value<'a> {
     pub fn map() -> Value<'a> {
         Value::Object(ObjType::Map)
@@ -61,6 +79,24 @@ impl<'a> Value<'a> {
         Value::Scalar(Cow::Owned(ScalarValue::Bytes(b)))
     }
 
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::Object(o) => ValueType::Object(*o),
+            Value::Scalar(s) => match s.as_ref() {
+                ScalarValue::Bytes(_) => ValueType::Bytes,
+                ScalarValue::Str(_) => ValueType::Str,
+                ScalarValue::Int(_) => ValueType::Int,
+                ScalarValue::Uint(_) => ValueType::Uint,
+                ScalarValue::F64(_) => ValueType::F64,
+                ScalarValue::Counter(_) => ValueType::Counter,
+                ScalarValue::Timestamp(_) => ValueType::Timestamp,
+                ScalarValue::Boolean(_) => ValueType::Boolean,
+                ScalarValue::Unknown { .. } => ValueType::Unknown,
+                ScalarValue::Null => ValueType::Null,
+            },
+        }
+    }
+
     pub fn is_object(&self) -> bool {
         matches!(&self, Value::Object(_))
     }
@@ -662,6 +698,211 @@ impl ScalarValue {
     pub fn counter(n: i64) -> ScalarValue {
         ScalarValue::Counter(n.into())
     }
+
+    /// A total order over all scalar values, useful for deterministically bucketing values of
+    /// mixed types.
+    ///
+    /// Values are ordered first by type, in the order the variants of `ScalarValue` are declared
+    /// (`Bytes`, `Str`, `Int`, `Uint`, `F64`, `Counter`, `Timestamp`, `Boolean`, `Unknown`,
+    /// `Null`), and then by value within a type. Floats are compared using the IEEE 754
+    /// `totalOrder` predicate, so `-0.0` sorts before `0.0` and `NaN` is ordered rather than
+    /// incomparable. Unknown values are ordered by their type code and then by their raw bytes.
+    /// Note that this means values which compare equal using `==` may still be ordered by
+    /// `type_cmp` (e.g. `0.0` and `-0.0`) and that numerically equal values of different types
+    /// (e.g. `Int(1)` and `Uint(1)`) are never equal.
+    pub fn type_cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (ScalarValue::F64(a), ScalarValue::F64(b)) => f64_total_cmp(*a, *b),
+            (
+                ScalarValue::Unknown {
+                    type_code: a_code,
+                    bytes: a_bytes,
+                },
+                ScalarValue::Unknown {
+                    type_code: b_code,
+                    bytes: b_bytes,
+                },
+            ) => a_code.cmp(b_code).then_with(|| a_bytes.cmp(b_bytes)),
+            (a, b) => match a.partial_cmp(b) {
+                Some(ord) => ord,
+                None => a.type_index().cmp(&b.type_index()),
+            },
+        }
+    }
+
+    fn type_index(&self) -> u8 {
+        match self {
+            ScalarValue::Bytes(_) => 0,
+            ScalarValue::Str(_) => 1,
+            ScalarValue::Int(_) => 2,
+            ScalarValue::Uint(_) => 3,
+            ScalarValue::F64(_) => 4,
+            ScalarValue::Counter(_) => 5,
+            ScalarValue::Timestamp(_) => 6,
+            ScalarValue::Boolean(_) => 7,
+            ScalarValue::Unknown { .. } => 8,
+            ScalarValue::Null => 9,
+        }
+    }
+
+    /// Format this value for display, see [`ValueFormat`] for the output of each mode
+    pub fn display_as(&self, fmt: ValueFormat) -> String {
+        match fmt {
+            ValueFormat::Raw => self.to_string(),
+            ValueFormat::Json => match self {
+                ScalarValue::Bytes(b) => json_bytes(b),
+                ScalarValue::Str(s) => json_string(s),
+                ScalarValue::Int(i) => i.to_string(),
+                ScalarValue::Uint(u) => u.to_string(),
+                ScalarValue::F64(f) if f.is_finite() => f.to_string(),
+                ScalarValue::F64(_) => "null".to_string(),
+                ScalarValue::Counter(c) => c.current.to_string(),
+                ScalarValue::Timestamp(t) => t.to_string(),
+                ScalarValue::Boolean(b) => b.to_string(),
+                ScalarValue::Unknown { type_code, bytes } => format!(
+                    "{{\"type_code\":{},\"bytes\":{}}}",
+                    type_code,
+                    json_bytes(bytes)
+                ),
+                ScalarValue::Null => "null".to_string(),
+            },
+            ValueFormat::Human => match self {
+                ScalarValue::Bytes(b) => hex::encode(b),
+                ScalarValue::Str(s) => s.to_string(),
+                ScalarValue::Int(i) => i.to_string(),
+                ScalarValue::Uint(u) => u.to_string(),
+                ScalarValue::F64(f) => f.to_string(),
+                ScalarValue::Counter(c) => format!("{:+}", c.current),
+                ScalarValue::Timestamp(t) => human_timestamp(*t),
+                ScalarValue::Boolean(b) => b.to_string(),
+                ScalarValue::Unknown { type_code, bytes } => {
+                    format!("unknown type {}: {}", type_code, hex::encode(bytes))
+                }
+                ScalarValue::Null => "null".to_string(),
+            },
+        }
+    }
+}
+
+// `f64::total_cmp` is only stable since 1.62, this is the same implementation
+fn f64_total_cmp(a: f64, b: f64) -> Ordering {
+    let mut left = a.to_bits() as i64;
+    let mut right = b.to_bits() as i64;
+    left ^= (((left >> 63) as u64) >> 1) as i64;
+    right ^= (((right >> 63) as u64) >> 1) as i64;
+    left.cmp(&right)
+}
+
+/// How [`ScalarValue::display_as`] formats a value
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ValueFormat {
+    /// The same as the `Display` implementation of [`ScalarValue`]
+    Raw,
+    /// A JSON value, matching how [`crate::AutoSerde`] serializes the value
+    ///
+    /// Strings are quoted and escaped, bytes are an array of numbers and counters and timestamps
+    /// are plain numbers. Non finite floats, which JSON can't represent, are `null`. Unknown
+    /// values are an object with their `type_code` and their `bytes` as an array of numbers.
+    Json,
+    /// Text intended to be shown to a person
+    ///
+    /// Strings are unquoted, bytes are lowercase hex, counters always have a sign (`+3`, `-2`),
+    /// timestamps are UTC dates in RFC 3339 format with milliseconds
+    /// (`2023-01-02T03:04:05.678Z`) and floats use `NaN`, `inf` and `-inf` for the non finite
+    /// values. Unknown values show their type code and their bytes in hex.
+    Human,
+}
+
+fn json_bytes(bytes: &[u8]) -> String {
+    format!(
+        "[{}]",
+        bytes
+            .iter()
+            .map(|b| b.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Format a timestamp in milliseconds since the unix epoch as an RFC 3339 date in UTC
+fn human_timestamp(millis: i64) -> String {
+    let days = millis.div_euclid(86_400_000);
+    let millis_of_day = millis.rem_euclid(86_400_000);
+    // Converts days since the epoch to a civil date, from
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        millis_of_day / 3_600_000,
+        millis_of_day / 60_000 % 60,
+        millis_of_day / 1000 % 60,
+        millis_of_day % 1000
+    )
+}
+
+/// Scalar values are only comparable with values of the same type.
+///
+/// Comparing two values of different types (including `Int` with `Uint` or `F64`, and `Counter`
+/// or `Timestamp` with any plain number) returns `None`, as does comparing two `Unknown` values
+/// with different type codes or comparing a `NaN`. Within a type numbers compare numerically,
+/// strings compare by their UTF-8 bytes, bytes compare lexicographically, counters compare by their
+/// current value, `false` is less than `true` and `Null` is equal to `Null`. Use
+/// [`ScalarValue::type_cmp`] if you need a total order across types.
+impl PartialOrd for ScalarValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (ScalarValue::Bytes(a), ScalarValue::Bytes(b)) => a.partial_cmp(b),
+            (ScalarValue::Str(a), ScalarValue::Str(b)) => a.partial_cmp(b),
+            (ScalarValue::Int(a), ScalarValue::Int(b)) => a.partial_cmp(b),
+            (ScalarValue::Uint(a), ScalarValue::Uint(b)) => a.partial_cmp(b),
+            (ScalarValue::F64(a), ScalarValue::F64(b)) => a.partial_cmp(b),
+            (ScalarValue::Counter(a), ScalarValue::Counter(b)) => a.current.partial_cmp(&b.current),
+            (ScalarValue::Timestamp(a), ScalarValue::Timestamp(b)) => a.partial_cmp(b),
+            (ScalarValue::Boolean(a), ScalarValue::Boolean(b)) => a.partial_cmp(b),
+            (
+                ScalarValue::Unknown {
+                    type_code: a_code,
+                    bytes: a_bytes,
+                },
+                ScalarValue::Unknown {
+                    type_code: b_code,
+                    bytes: b_bytes,
+                },
+            ) if a_code == b_code => a_bytes.partial_cmp(b_bytes),
+            (ScalarValue::Null, ScalarValue::Null) => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
 }
 
 impl From<&str> for ScalarValue {
@@ -752,3 +993,192 @@ impl fmt::Display for ScalarValue {
         }
     }
 }
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Object(o) => write!(f, "{}", o),
+            ValueType::Bytes => write!(f, "bytes"),
+            ValueType::Str => write!(f, "str"),
+            ValueType::Int => write!(f, "int"),
+            ValueType::Uint => write!(f, "uint"),
+            ValueType::F64 => write!(f, "f64"),
+            ValueType::Counter => write!(f, "counter"),
+            ValueType::Timestamp => write!(f, "timestamp"),
+            ValueType::Boolean => write!(f, "boolean"),
+            ValueType::Unknown => write!(f, "unknown"),
+            ValueType::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// Types which can be read out of a [`Value`], see [`crate::ReadDoc::get_as`]
+///
+/// The numeric types are coerced from any numeric value in the same way as
+/// [`ScalarValue::to_i64`] and [`ScalarValue::to_f64`].
+pub trait FromValue: Sized {
+    /// The name of the type of value this is read from, used in errors
+    const EXPECTED: &'static str;
+
+    /// Convert `value`, giving it back if it has the wrong type
+    fn from_value(value: Value<'_>) -> Result<Self, Value<'_>>;
+}
+
+impl FromValue for i64 {
+    const EXPECTED: &'static str = "int";
+
+    fn from_value(value: Value<'_>) -> Result<Self, Value<'_>> {
+        value.to_i64().ok_or(value)
+    }
+}
+
+impl FromValue for f64 {
+    const EXPECTED: &'static str = "f64";
+
+    fn from_value(value: Value<'_>) -> Result<Self, Value<'_>> {
+        value.to_f64().ok_or(value)
+    }
+}
+
+impl FromValue for bool {
+    const EXPECTED: &'static str = "boolean";
+
+    fn from_value(value: Value<'_>) -> Result<Self, Value<'_>> {
+        value.to_bool().ok_or(value)
+    }
+}
+
+impl FromValue for String {
+    const EXPECTED: &'static str = "str";
+
+    fn from_value(value: Value<'_>) -> Result<Self, Value<'_>> {
+        value.into_string()
+    }
+}
+
+impl FromValue for Vec<u8> {
+    const EXPECTED: &'static str = "bytes";
+
+    fn from_value(value: Value<'_>) -> Result<Self, Value<'_>> {
+        value.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scalar_values_compare_within_a_type() {
+        assert!(ScalarValue::Int(-1) < ScalarValue::Int(2));
+        assert!(ScalarValue::Uint(3) > ScalarValue::Uint(2));
+        assert!(ScalarValue::F64(1.5) < ScalarValue::F64(2.0));
+        assert!(ScalarValue::from("apple") < ScalarValue::from("banana"));
+        assert!(ScalarValue::Timestamp(10) < ScalarValue::Timestamp(11));
+        assert!(ScalarValue::counter(4) > ScalarValue::counter(3));
+        assert!(ScalarValue::Boolean(false) < ScalarValue::Boolean(true));
+        assert_eq!(
+            ScalarValue::Null.partial_cmp(&ScalarValue::Null),
+            Some(Ordering::Equal)
+        );
+    }
+
+    #[test]
+    fn scalar_values_of_different_types_are_incomparable() {
+        assert_eq!(ScalarValue::Int(1).partial_cmp(&ScalarValue::Uint(1)), None);
+        assert_eq!(
+            ScalarValue::Int(1).partial_cmp(&ScalarValue::F64(1.0)),
+            None
+        );
+        assert_eq!(
+            ScalarValue::Int(1).partial_cmp(&ScalarValue::Timestamp(1)),
+            None
+        );
+        assert_eq!(
+            ScalarValue::from("1").partial_cmp(&ScalarValue::Int(1)),
+            None
+        );
+        assert_eq!(
+            ScalarValue::F64(f64::NAN).partial_cmp(&ScalarValue::F64(f64::NAN)),
+            None
+        );
+        let unknown = |type_code| ScalarValue::Unknown {
+            type_code,
+            bytes: vec![],
+        };
+        assert_eq!(unknown(20).partial_cmp(&unknown(21)), None);
+    }
+
+    #[test]
+    fn type_cmp_is_a_total_order() {
+        let mut values = vec![
+            ScalarValue::Null,
+            ScalarValue::Int(2),
+            ScalarValue::F64(f64::NAN),
+            ScalarValue::from("b"),
+            ScalarValue::Uint(1),
+            ScalarValue::Boolean(true),
+            ScalarValue::Int(-3),
+            ScalarValue::F64(0.5),
+            ScalarValue::from("a"),
+            ScalarValue::Bytes(vec![1]),
+        ];
+        values.sort_by(ScalarValue::type_cmp);
+        assert_eq!(
+            values.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            vec![
+                ScalarValue::Bytes(vec![1]).to_string(),
+                ScalarValue::from("a").to_string(),
+                ScalarValue::from("b").to_string(),
+                ScalarValue::Int(-3).to_string(),
+                ScalarValue::Int(2).to_string(),
+                ScalarValue::Uint(1).to_string(),
+                ScalarValue::F64(0.5).to_string(),
+                ScalarValue::F64(f64::NAN).to_string(),
+                ScalarValue::Boolean(true).to_string(),
+                ScalarValue::Null.to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn display_as_formats_each_type() {
+        let cases = vec![
+            (ScalarValue::Bytes(vec![0, 10, 255]), "[0,10,255]", "000aff"),
+            (
+                ScalarValue::from("say \"hi\"\n"),
+                "\"say \\\"hi\\\"\\n\"",
+                "say \"hi\"\n",
+            ),
+            (ScalarValue::Int(-5), "-5", "-5"),
+            (ScalarValue::Uint(5), "5", "5"),
+            (ScalarValue::F64(1.5), "1.5", "1.5"),
+            (ScalarValue::F64(f64::NAN), "null", "NaN"),
+            (ScalarValue::F64(f64::NEG_INFINITY), "null", "-inf"),
+            (ScalarValue::counter(3), "3", "+3"),
+            (ScalarValue::counter(-2), "-2", "-2"),
+            (
+                ScalarValue::Timestamp(1_672_628_645_678),
+                "1672628645678",
+                "2023-01-02T03:04:05.678Z",
+            ),
+            (ScalarValue::Timestamp(-1), "-1", "1969-12-31T23:59:59.999Z"),
+            (ScalarValue::Boolean(true), "true", "true"),
+            (ScalarValue::Null, "null", "null"),
+            (
+                ScalarValue::Unknown {
+                    type_code: 20,
+                    bytes: vec![1, 2],
+                },
+                "{\"type_code\":20,\"bytes\":[1,2]}",
+                "unknown type 20: 0102",
+            ),
+        ];
+        for (value, json, human) in cases {
+            assert_eq!(value.display_as(ValueFormat::Raw), value.to_string());
+            assert_eq!(value.display_as(ValueFormat::Json), json);
+            assert_eq!(serde_json::to_string(&value).unwrap(), json);
+            assert_eq!(value.display_as(ValueFormat::Human), human);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::{exid::ExId, AutomergeError, ObjType, Prop, ReadDoc, ScalarValue, Value};
+use sha2::{Digest, Sha256};
+
+/// Callbacks for [`ReadDoc::walk`]
+///
+/// Every method does nothing by default so a visitor only needs to implement the ones it is
+/// interested in. The `prop` passed to each method is the property of the parent object the
+/// value is at, it is `None` for the object the walk started from. Tables are visited as maps.
+pub trait TreeVisitor {
+    fn enter_map(&mut self, _prop: Option<&Prop>, _obj: &ExId) {}
+
+    fn leave_map(&mut self, _prop: Option<&Prop>, _obj: &ExId) {}
+
+    fn enter_list(&mut self, _prop: Option<&Prop>, _obj: &ExId) {}
+
+    fn leave_list(&mut self, _prop: Option<&Prop>, _obj: &ExId) {}
+
+    fn visit_text(&mut self, _prop: Option<&Prop>, _obj: &ExId, _text: &str) {}
+
+    /// `id` is the ID of the operation which set the value
+    fn visit_scalar(&mut self, _prop: &Prop, _value: &ScalarValue, _id: &ExId) {}
+}
+
+pub(crate) fn walk_object<R: ReadDoc + ?Sized, V: TreeVisitor>(
+    doc: &R,
+    prop: Option<&Prop>,
+    obj: &ExId,
+    obj_type: ObjType,
+    visitor: &mut V,
+) -> Result<(), AutomergeError> {
+    match obj_type {
+        ObjType::Map | ObjType::Table => {
+            visitor.enter_map(prop, obj);
+            for (key, value, id) in doc.map_range(obj, ..) {
+                walk_value(doc, &Prop::Map(key.to_string()), value, &id, visitor)?;
+            }
+            visitor.leave_map(prop, obj);
+        }
+        ObjType::List => {
+            visitor.enter_list(prop, obj);
+            for (index, value, id) in doc.list_range(obj, ..) {
+                walk_value(doc, &Prop::Seq(index), value, &id, visitor)?;
+            }
+            visitor.leave_list(prop, obj);
+        }
+        ObjType::Text => visitor.visit_text(prop, obj, &doc.text(obj)?),
+    }
+    Ok(())
+}
+
+fn walk_value<R: ReadDoc + ?Sized, V: TreeVisitor>(
+    doc: &R,
+    prop: &Prop,
+    value: Value<'_>,
+    id: &ExId,
+    visitor: &mut V,
+) -> Result<(), AutomergeError> {
+    match value {
+        Value::Object(obj_type) => walk_object(doc, Some(prop), id, obj_type, visitor),
+        Value::Scalar(value) => {
+            visitor.visit_scalar(prop, &value, id);
+            Ok(())
+        }
+    }
+}
+
+/// The size of a subtree of a document, see [`ReadDoc::subtree_stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubtreeStats {
+    /// The number of values in the subtree, each character of a text object counts as one
+    pub elements: usize,
+    /// The number of objects in the subtree, not counting the object it starts from
+    pub objects: usize,
+    /// An estimate of how many bytes the subtree takes up once read out of the document
+    ///
+    /// This is the length of the map keys, strings, text and byte arrays plus eight bytes for
+    /// each number and one for each boolean or null. It doesn't include the overhead of
+    /// whatever the values are read into.
+    pub bytes: usize,
+}
+
+impl SubtreeStats {
+    fn object(&mut self, prop: Option<&Prop>) {
+        if prop.is_some() {
+            self.objects += 1;
+            self.value(prop);
+        }
+    }
+
+    fn value(&mut self, prop: Option<&Prop>) {
+        self.elements += 1;
+        if let Some(Prop::Map(key)) = prop {
+            self.bytes += key.len();
+        }
+    }
+}
+
+impl TreeVisitor for SubtreeStats {
+    fn enter_map(&mut self, prop: Option<&Prop>, _obj: &ExId) {
+        self.object(prop);
+    }
+
+    fn enter_list(&mut self, prop: Option<&Prop>, _obj: &ExId) {
+        self.object(prop);
+    }
+
+    fn visit_text(&mut self, prop: Option<&Prop>, _obj: &ExId, text: &str) {
+        self.object(prop);
+        self.elements += text.chars().count();
+        self.bytes += text.len();
+    }
+
+    fn visit_scalar(&mut self, prop: &Prop, value: &ScalarValue, _id: &ExId) {
+        self.value(Some(prop));
+        self.bytes += match value {
+            ScalarValue::Bytes(bytes) => bytes.len(),
+            ScalarValue::Str(s) => s.len(),
+            ScalarValue::Unknown { bytes, .. } => bytes.len(),
+            ScalarValue::Int(_)
+            | ScalarValue::Uint(_)
+            | ScalarValue::F64(_)
+            | ScalarValue::Counter(_)
+            | ScalarValue::Timestamp(_) => 8,
+            ScalarValue::Boolean(_) | ScalarValue::Null => 1,
+        };
+    }
+}
+
+/// A [`TreeVisitor`] which hashes the values it visits, used by [`crate::Automerge::state_hash`]
+///
+/// Every value is written with a tag and strings and bytes are length prefixed so that different
+/// trees can't produce the same stream of bytes.
+pub(crate) struct StateHasher(Sha256);
+
+impl StateHasher {
+    pub(crate) fn new() -> Self {
+        Self(Sha256::new())
+    }
+
+    pub(crate) fn finish(self) -> [u8; 32] {
+        self.0.finalize().into()
+    }
+
+    fn tag(&mut self, tag: u8) {
+        self.0.update([tag]);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.update((bytes.len() as u64).to_be_bytes());
+        self.0.update(bytes);
+    }
+
+    fn prop(&mut self, prop: Option<&Prop>) {
+        match prop {
+            None => self.tag(0),
+            Some(Prop::Map(key)) => {
+                self.tag(1);
+                self.bytes(key.as_bytes());
+            }
+            Some(Prop::Seq(index)) => {
+                self.tag(2);
+                self.0.update((*index as u64).to_be_bytes());
+            }
+        }
+    }
+}
+
+impl TreeVisitor for StateHasher {
+    fn enter_map(&mut self, prop: Option<&Prop>, _obj: &ExId) {
+        self.tag(b'm');
+        self.prop(prop);
+    }
+
+    fn leave_map(&mut self, _prop: Option<&Prop>, _obj: &ExId) {
+        self.tag(b'M');
+    }
+
+    fn enter_list(&mut self, prop: Option<&Prop>, _obj: &ExId) {
+        self.tag(b'l');
+        self.prop(prop);
+    }
+
+    fn leave_list(&mut self, _prop: Option<&Prop>, _obj: &ExId) {
+        self.tag(b'L');
+    }
+
+    fn visit_text(&mut self, prop: Option<&Prop>, _obj: &ExId, text: &str) {
+        self.tag(b't');
+        self.prop(prop);
+        self.bytes(text.as_bytes());
+    }
+
+    fn visit_scalar(&mut self, prop: &Prop, value: &ScalarValue, _id: &ExId) {
+        self.tag(b's');
+        self.prop(Some(prop));
+        match value {
+            ScalarValue::Bytes(bytes) => {
+                self.tag(0);
+                self.bytes(bytes);
+            }
+            ScalarValue::Str(s) => {
+                self.tag(1);
+                self.bytes(s.as_bytes());
+            }
+            ScalarValue::Int(n) => {
+                self.tag(2);
+                self.0.update(n.to_be_bytes());
+            }
+            ScalarValue::Uint(n) => {
+                self.tag(3);
+                self.0.update(n.to_be_bytes());
+            }
+            ScalarValue::F64(n) => {
+                self.tag(4);
+                self.0.update(n.to_bits().to_be_bytes());
+            }
+            ScalarValue::Counter(c) => {
+                self.tag(5);
+                self.0.update(i64::from(c).to_be_bytes());
+            }
+            ScalarValue::Timestamp(n) => {
+                self.tag(6);
+                self.0.update(n.to_be_bytes());
+            }
+            ScalarValue::Boolean(b) => {
+                self.tag(7);
+                self.tag(*b as u8);
+            }
+            ScalarValue::Unknown { type_code, bytes } => {
+                self.tag(8);
+                self.tag(*type_code);
+                self.bytes(bytes);
+            }
+            ScalarValue::Null => self.tag(9),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Run with `cargo test --features debug-internals --test debug_ops`
+#![cfg(feature = "debug-internals")]
+
+use automerge::transaction::Transactable;
+use automerge::{AutoCommit, DebugKey, ObjType, OpType, ScalarValue, ROOT};
+
+#[test]
+fn debug_ops_includes_overwritten_and_deleted_values() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1).unwrap();
+    doc.put(ROOT, "a", 2).unwrap();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, "x").unwrap();
+    doc.insert(&list, 1, "y").unwrap();
+    doc.delete(&list, 0).unwrap();
+
+    let ops = doc.debug_ops().collect::<Vec<_>>();
+    // the delete is only recorded in the succ of the op it deleted
+    assert_eq!(ops.len(), 5);
+
+    let puts = ops
+        .iter()
+        .filter(|op| op.key == DebugKey::Map("a".to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(puts.len(), 2);
+    assert_eq!(puts[0].action, OpType::Put(ScalarValue::Int(1)));
+    assert_eq!(puts[0].succ, vec![puts[1].id.clone()]);
+    assert_eq!(puts[1].pred, vec![puts[0].id.clone()]);
+    assert!(puts[1].succ.is_empty());
+
+    let in_list = ops.iter().filter(|op| op.obj == list).collect::<Vec<_>>();
+    let x = in_list.iter().find(|op| op.key == DebugKey::Head).unwrap();
+    assert!(x.insert);
+    assert_eq!(x.succ.len(), 1);
+    let y = in_list
+        .iter()
+        .find(|op| op.key == DebugKey::Elem(x.id.clone()) && op.insert)
+        .unwrap();
+    assert_eq!(y.action, OpType::Put(ScalarValue::from("y")));
+    assert!(y.succ.is_empty());
+    assert!(!ops.iter().any(|op| op.id == x.succ[0]));
+}
@@ -0,0 +1,31 @@
+//! Run with `cargo test --features deterministic --test deterministic`
+#![cfg(feature = "deterministic")]
+
+use automerge::transaction::Transactable;
+use automerge::{ActorId, AutoCommit, ChangeHash, ObjType, ROOT};
+
+fn edit_concurrently() -> Vec<ChangeHash> {
+    let mut doc1 = AutoCommit::new();
+    let list = doc1.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc1.insert(&list, 0, "a").unwrap();
+    doc1.commit();
+    let mut doc2 = doc1.fork();
+    doc1.insert(&list, 1, "b").unwrap();
+    doc2.insert(&list, 1, "c").unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    doc1.get_heads()
+}
+
+#[test]
+fn heads_are_the_same_every_run() {
+    ActorId::reset_seed(0);
+    let first = edit_concurrently();
+    ActorId::reset_seed(0);
+    let second = edit_concurrently();
+    assert_eq!(first, second);
+    assert_eq!(first.len(), 2);
+
+    // a different seed gives different actors and so different hashes
+    ActorId::reset_seed(100);
+    assert_ne!(edit_concurrently(), first);
+}
@@ -1,10 +1,11 @@
 use automerge::marks::{ExpandMark, Mark};
 use automerge::op_observer::HasPatches;
 use automerge::op_tree::B;
-use automerge::transaction::Transactable;
+use automerge::transaction::{CommitOptions, InverseOp, Transactable};
 use automerge::{
-    ActorId, AutoCommit, Automerge, AutomergeError, Change, ExpandedChange, ObjId, ObjType, Patch,
-    PatchAction, Prop, ReadDoc, ScalarValue, SequenceTree, Value, VecOpObserver, ROOT,
+    ActorId, AutoCommit, Automerge, AutomergeError, Change, ExpandedChange, LineEnding,
+    LoadWarning, MergeStrategies, MergeStrategy, ObjId, ObjType, Patch, PatchAction, Prop, ReadDoc,
+    ScalarValue, SequenceTree, TextCell, TextEncoding, Value, VecOpObserver, ROOT,
 };
 use std::fs;
 
@@ -1725,3 +1726,2512 @@ fn marks() {
     assert_eq!(marks[1].name(), "bold");
     assert_eq!(marks[1].value(), &ScalarValue::from(true));
 }
+
+#[test]
+fn marks_after_splice_matches_marks_after_splicing() {
+    let splices = [
+        (0, 0, 3),
+        (2, 0, 3),
+        (5, 0, 3),
+        (6, 0, 3),
+        (11, 0, 2),
+        (3, 4, 0),
+        (0, 5, 0),
+        (4, 3, 2),
+        (5, 6, 1),
+        // deleting everything after `pos`
+        (4, usize::MAX, 1),
+    ];
+    for expand in [
+        ExpandMark::Before,
+        ExpandMark::After,
+        ExpandMark::Both,
+        ExpandMark::None,
+    ] {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "hello world").unwrap();
+        doc.mark(&text, Mark::new("bold".to_string(), true, 0, 5), expand)
+            .unwrap();
+        doc.mark(&text, Mark::new("link".to_string(), "x", 6, 11), expand)
+            .unwrap();
+
+        for (pos, del, insert) in splices {
+            let predicted = doc.marks_after_splice(&text, pos, del, insert).unwrap();
+            let mut spliced = doc.fork();
+            spliced
+                .splice_text(&text, pos, del, &"-".repeat(insert))
+                .unwrap();
+            assert_eq!(
+                predicted,
+                spliced.marks(&text).unwrap(),
+                "splice({}, {}, {}) with {:?}",
+                pos,
+                del,
+                insert,
+                expand
+            );
+        }
+        // the document itself is not changed
+        assert_eq!(doc.text(&text).unwrap(), "hello world");
+        assert!(doc.marks_after_splice(&text, 12, 0, 1).is_err());
+    }
+}
+
+#[test]
+fn inserting_inside_a_mark_keeps_the_mark_on_both_halves() {
+    for expand in [
+        ExpandMark::Before,
+        ExpandMark::After,
+        ExpandMark::Both,
+        ExpandMark::None,
+    ] {
+        assert!(expand.inherits_on_split());
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "hello world").unwrap();
+        doc.mark(&text, Mark::new("bold".to_string(), true, 2, 9), expand)
+            .unwrap();
+
+        // split the run with a newline, as an editor would when breaking a paragraph
+        doc.splice_text(&text, 5, 0, "\n").unwrap();
+        assert_eq!(
+            doc.marks(&text).unwrap(),
+            vec![Mark::new("bold".to_string(), true, 2, 10)],
+            "{:?}",
+            expand
+        );
+    }
+}
+
+#[test]
+fn marks_at_index_reports_marks_covering_the_caret() {
+    use std::collections::BTreeMap;
+
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 0, 11),
+        ExpandMark::After,
+    )
+    .unwrap();
+    doc.mark(
+        &text,
+        Mark::new("link".to_string(), "x", 6, 11),
+        ExpandMark::None,
+    )
+    .unwrap();
+
+    let bold = BTreeMap::from([("bold".to_string(), ScalarValue::from(true))]);
+    let both = BTreeMap::from([
+        ("bold".to_string(), ScalarValue::from(true)),
+        ("link".to_string(), ScalarValue::from("x")),
+    ]);
+    assert_eq!(doc.marks_at_index(&text, 0).unwrap(), bold);
+    assert_eq!(doc.marks_at_index(&text, 5).unwrap(), bold);
+    assert_eq!(doc.marks_at_index(&text, 6).unwrap(), both);
+    assert_eq!(doc.marks_at_index(&text, 10).unwrap(), both);
+
+    // at the end only the mark which expands after itself is inherited
+    assert_eq!(doc.marks_at_index(&text, 11).unwrap(), bold);
+    assert!(matches!(
+        doc.marks_at_index(&text, 12),
+        Err(AutomergeError::IndexOutOfBounds { len: 11, pos: 12 })
+    ));
+
+    // and typing there gets the same marks
+    doc.splice_text(&text, 11, 0, "!").unwrap();
+    assert_eq!(doc.marks_at_index(&text, 11).unwrap(), bold);
+}
+
+#[test]
+fn load_strict_rejects_unknown_chunks() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1).unwrap();
+    let mut bytes = doc.save();
+    assert!(Automerge::load_strict(&bytes).is_ok());
+
+    // magic bytes, checksum, an unknown chunk type and three bytes of data
+    bytes.extend([0x85, 0x6f, 0x4a, 0x83, 0, 0, 0, 0, 0x10, 3, 1, 2, 3]);
+    assert!(matches!(
+        Automerge::load_strict(&bytes),
+        Err(AutomergeError::Load(_))
+    ));
+
+    // a lenient load keeps everything up to the unknown chunk
+    let mut lenient = Automerge::new();
+    lenient.load_incremental(&bytes).unwrap();
+    assert_eq!(lenient.get(ROOT, "a").unwrap().unwrap().0, Value::int(1));
+}
+
+#[test]
+fn load_strict_rejects_extra_change_bytes() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1).unwrap();
+    let change = doc.get_last_local_change().unwrap().clone();
+    let mut expanded = ExpandedChange::from(&change);
+    expanded.extra_bytes = vec![1, 2, 3];
+    let mut change = Change::from(expanded);
+    let bytes = change.bytes().to_vec();
+
+    assert!(Automerge::load(&bytes).is_ok());
+    assert!(matches!(
+        Automerge::load_strict(&bytes),
+        Err(AutomergeError::Load(_))
+    ));
+}
+
+#[test]
+fn load_with_warnings_reports_skipped_data() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1).unwrap();
+    let mut bytes = doc.save();
+    let (_, warnings) = Automerge::load_with_warnings(&bytes).unwrap();
+    assert!(warnings.is_empty());
+
+    doc.put(ROOT, "b", 2).unwrap();
+    let change = doc.get_last_local_change().unwrap().clone();
+    let mut expanded = ExpandedChange::from(&change);
+    expanded.extra_bytes = vec![1, 2, 3];
+    bytes.extend(Change::from(expanded).bytes().iter());
+
+    let (loaded, warnings) = Automerge::load_with_warnings(&bytes).unwrap();
+    assert_eq!(warnings, vec![LoadWarning::ExtraBytes { chunk: 1 }]);
+    assert_eq!(loaded.get(ROOT, "b").unwrap().unwrap().0, Value::int(2));
+
+    // data which can't be loaded is still an error
+    bytes.extend([0x85, 0x6f, 0x4a, 0x83, 0, 0, 0, 0, 0x10, 3, 1, 2, 3]);
+    assert!(Automerge::load_with_warnings(&bytes).is_err());
+}
+
+#[test]
+fn observe_subtree_only_sees_changes_under_the_object() {
+    let mut doc = AutoCommit::new();
+    let a = doc.put_object(ROOT, "a", ObjType::Map).unwrap();
+    let b = doc.put_object(ROOT, "b", ObjType::Map).unwrap();
+    let mut observed = Automerge::load(&doc.save()).unwrap();
+    let mut obs = observed
+        .observe_subtree(&a, VecOpObserver::default())
+        .unwrap();
+
+    let heads = doc.get_heads();
+    let list = doc.put_object(&a, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, "x").unwrap();
+    doc.put(&b, "key", "value").unwrap();
+    doc.put(ROOT, "c", 1).unwrap();
+    let changes = doc.get_changes(&heads).unwrap().into_iter().cloned();
+    observed
+        .apply_changes_with(changes, Some(&mut obs))
+        .unwrap();
+    let patches = obs.take_patches();
+    assert_eq!(
+        patches.into_iter().map(|p| p.obj).collect::<Vec<_>>(),
+        vec![a.clone(), list.clone()]
+    );
+    assert!(!obs.removed());
+
+    // deleting the watched object produces one final patch and then nothing else
+    let heads = doc.get_heads();
+    doc.delete(ROOT, "a").unwrap();
+    doc.put(&a, "after", 1).unwrap();
+    doc.insert(&list, 1, "y").unwrap();
+    doc.put(&b, "key", "other value").unwrap();
+    let changes = doc.get_changes(&heads).unwrap().into_iter().cloned();
+    observed
+        .apply_changes_with(changes, Some(&mut obs))
+        .unwrap();
+    assert_eq!(
+        obs.take_patches(),
+        vec![Patch {
+            obj: ROOT,
+            path: vec![],
+            action: PatchAction::DeleteMap {
+                key: "a".to_string()
+            },
+        }]
+    );
+    assert!(obs.removed());
+}
+
+#[test]
+fn entries_typed_reports_each_key_with_the_type_of_its_value() {
+    use automerge::ValueType;
+
+    let mut doc = AutoCommit::new();
+    let map = doc.put_object(ROOT, "map", ObjType::Map).unwrap();
+    doc.put(&map, "str", "hello").unwrap();
+    doc.put(&map, "int", 1).unwrap();
+    doc.put(&map, "counter", ScalarValue::counter(1)).unwrap();
+    doc.put(&map, "null", ()).unwrap();
+    let list = doc.put_object(&map, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, 1).unwrap();
+    let heads = doc.get_heads();
+    doc.put_object(&map, "text", ObjType::Text).unwrap();
+    doc.delete(&map, "int").unwrap();
+
+    assert_eq!(
+        doc.entries_typed(&map).unwrap(),
+        vec![
+            ("counter".to_string(), ValueType::Counter),
+            ("list".to_string(), ValueType::Object(ObjType::List)),
+            ("null".to_string(), ValueType::Null),
+            ("str".to_string(), ValueType::Str),
+            ("text".to_string(), ValueType::Object(ObjType::Text)),
+        ]
+    );
+    assert_eq!(
+        doc.entries_typed_at(&map, &heads).unwrap(),
+        vec![
+            ("counter".to_string(), ValueType::Counter),
+            ("int".to_string(), ValueType::Int),
+            ("list".to_string(), ValueType::Object(ObjType::List)),
+            ("null".to_string(), ValueType::Null),
+            ("str".to_string(), ValueType::Str),
+        ]
+    );
+    assert!(matches!(
+        doc.entries_typed(&list),
+        Err(AutomergeError::InvalidOp(ObjType::List))
+    ));
+}
+
+#[test]
+fn list_length_matches_a_rescan_under_concurrent_edits() {
+    // a tiny LCG so the test is reproducible without pulling in rand
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move |bound: usize| {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        ((seed >> 33) as usize) % bound.max(1)
+    };
+
+    let mut doc1 = AutoCommit::new().with_actor(ActorId::from([1]));
+    let list = doc1.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc1.commit();
+    let mut docs = vec![
+        doc1.fork().with_actor(ActorId::from([2])),
+        doc1.fork().with_actor(ActorId::from([3])),
+        doc1,
+    ];
+    let check = |doc: &AutoCommit| {
+        assert_eq!(doc.length(&list), doc.list_range(&list, ..).count());
+    };
+
+    let mut history = vec![];
+    for round in 0..30 {
+        for doc in docs.iter_mut() {
+            for _ in 0..next(8) {
+                let len = doc.length(&list);
+                match next(4) {
+                    0 | 1 => doc.insert(&list, next(len + 1), round as i64).unwrap(),
+                    2 if len > 0 => doc.delete(&list, next(len)).unwrap(),
+                    3 if len > 0 => doc.put(&list, next(len), -1).unwrap(),
+                    _ => {}
+                }
+                check(doc);
+            }
+            doc.commit();
+        }
+        let (a, b) = (next(docs.len()), next(docs.len()));
+        if a != b {
+            let mut other = docs[b].clone();
+            docs[a].merge(&mut other).unwrap();
+            check(&docs[a]);
+        }
+        history.push(docs[0].get_heads());
+    }
+    let mut merged = docs.remove(0);
+    for doc in docs.iter_mut() {
+        merged.merge(doc).unwrap();
+    }
+    check(&merged);
+    for heads in history {
+        assert_eq!(
+            merged.length_at(&list, &heads),
+            merged.list_range_at(&list, .., &heads).count()
+        );
+    }
+}
+
+#[test]
+fn walk_visits_the_whole_tree_in_order() {
+    use automerge::TreeVisitor;
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    fn name(prop: Option<&Prop>) -> String {
+        prop.map(|p| p.to_string())
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    impl TreeVisitor for Recorder {
+        fn enter_map(&mut self, prop: Option<&Prop>, _obj: &ObjId) {
+            self.0.push(format!("enter map {}", name(prop)));
+        }
+        fn leave_map(&mut self, prop: Option<&Prop>, _obj: &ObjId) {
+            self.0.push(format!("leave map {}", name(prop)));
+        }
+        fn enter_list(&mut self, prop: Option<&Prop>, _obj: &ObjId) {
+            self.0.push(format!("enter list {}", name(prop)));
+        }
+        fn leave_list(&mut self, prop: Option<&Prop>, _obj: &ObjId) {
+            self.0.push(format!("leave list {}", name(prop)));
+        }
+        fn visit_text(&mut self, prop: Option<&Prop>, _obj: &ObjId, text: &str) {
+            self.0.push(format!("text {} {}", name(prop), text));
+        }
+        fn visit_scalar(&mut self, prop: &Prop, value: &ScalarValue, _id: &ObjId) {
+            self.0.push(format!("scalar {} {}", prop, value));
+        }
+    }
+
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "b", 1).unwrap();
+    let list = doc.put_object(ROOT, "a", ObjType::List).unwrap();
+    doc.insert(&list, 0, "x").unwrap();
+    let map = doc.insert_object(&list, 1, ObjType::Map).unwrap();
+    doc.put(&map, "z", true).unwrap();
+    let text = doc.put_object(ROOT, "c", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello").unwrap();
+
+    let mut recorder = Recorder::default();
+    doc.walk(ROOT, &mut recorder).unwrap();
+    assert_eq!(
+        recorder.0,
+        vec![
+            "enter map -",
+            "enter list a",
+            "scalar 0 \"x\"",
+            "enter map 1",
+            "scalar z true",
+            "leave map 1",
+            "leave list a",
+            "scalar b 1",
+            "text c hello",
+            "leave map -",
+        ]
+    );
+
+    let mut recorder = Recorder::default();
+    doc.walk(&map, &mut recorder).unwrap();
+    assert_eq!(
+        recorder.0,
+        vec!["enter map -", "scalar z true", "leave map -"]
+    );
+}
+
+#[test]
+fn get_annotated_reports_conflicts() {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(ROOT, "plain", "value").unwrap();
+    let mut doc2 = doc1.fork();
+    doc1.put(ROOT, "conflict", 1).unwrap();
+    doc2.put(ROOT, "conflict", 2).unwrap();
+    doc1.merge(&mut doc2).unwrap();
+
+    let plain = doc1.get_annotated(ROOT, "plain").unwrap().unwrap();
+    assert_eq!(plain.value, Value::str("value"));
+    assert!(!plain.conflicted);
+    assert_eq!(plain.count, 1);
+
+    let conflict = doc1.get_annotated(ROOT, "conflict").unwrap().unwrap();
+    let (value, id) = doc1.get(ROOT, "conflict").unwrap().unwrap();
+    assert_eq!((conflict.value, conflict.id), (value, id));
+    assert!(conflict.conflicted);
+    assert_eq!(conflict.count, 2);
+
+    assert!(doc1.get_annotated(ROOT, "missing").unwrap().is_none());
+}
+
+#[test]
+fn get_annotated_at_reports_conflicts_which_were_since_resolved() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new();
+    let mut doc2 = doc1.fork();
+    doc1.put(ROOT, "key", 1)?;
+    doc2.put(ROOT, "key", 2)?;
+    doc1.merge(&mut doc2)?;
+    let conflicted = doc1.get_heads();
+    doc1.put(ROOT, "key", 3)?;
+    let resolved = doc1.get_heads();
+
+    let then = doc1.get_annotated_at(ROOT, "key", &conflicted)?.unwrap();
+    let (value, id) = doc1.get_at(ROOT, "key", &conflicted)?.unwrap();
+    assert_eq!((then.value, then.id), (value, id));
+    assert!(then.conflicted);
+    assert_eq!(then.count, 2);
+
+    let now = doc1.get_annotated_at(ROOT, "key", &resolved)?.unwrap();
+    assert_eq!(now, doc1.get_annotated(ROOT, "key")?.unwrap());
+    assert_eq!(now.value, Value::int(3));
+    assert!(!now.conflicted);
+
+    assert!(doc1.get_annotated_at(ROOT, "key", &[])?.is_none());
+    Ok(())
+}
+
+#[test]
+fn flatten_keeps_the_current_state() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new();
+    let list = doc1.put_object(ROOT, "list", ObjType::List)?;
+    doc1.insert(&list, 0, "a")?;
+    doc1.insert(&list, 1, "b")?;
+    let text = doc1.put_object(ROOT, "text", ObjType::Text)?;
+    doc1.splice_text(&text, 0, 0, "hello world")?;
+    doc1.put(ROOT, "counter", ScalarValue::counter(1))?;
+    doc1.commit();
+
+    let mut doc2 = doc1.fork();
+    doc2.delete(&list, 0)?;
+    doc2.increment(ROOT, "counter", 5)?;
+    let map = doc2.put_object(ROOT, "map", ObjType::Map)?;
+    doc2.put(&map, "key", "value")?;
+    doc1.put(ROOT, "counter", "conflict")?;
+    let mark = Mark::new("bold".to_string(), true, 0, 5);
+    doc1.mark(&text, mark, ExpandMark::Both)?;
+    doc1.merge(&mut doc2)?;
+
+    let before = realize(doc1.document());
+    let marks_before = doc1
+        .marks(&text)?
+        .into_iter()
+        .map(Mark::into_owned)
+        .collect::<Vec<_>>();
+    let actor = doc1.get_actor().clone();
+
+    doc1.flatten()?;
+    assert_eq!(doc1.get_changes(&[])?.len(), 1);
+    assert_ne!(doc1.get_actor(), &actor);
+    assert_eq!(realize(doc1.document()), before);
+
+    let loaded = Automerge::load(&doc1.save())?;
+    assert_eq!(loaded.get_changes(&[])?.len(), 1);
+    assert_eq!(realize(&loaded), before);
+    let (_, text) = loaded.get(ROOT, "text")?.unwrap();
+    assert_eq!(loaded.text(&text)?, "hello world");
+    assert_eq!(loaded.marks(&text)?, marks_before);
+    Ok(())
+}
+
+#[test]
+fn autocommit_transact_rolls_back_on_error() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1)?;
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+    doc.insert(&list, 0, "x")?;
+    let heads = doc.get_heads();
+    let before = realize(doc.document());
+
+    let result: Result<(), AutomergeError> = doc.transact(|tx| {
+        tx.put(ROOT, "a", 2)?;
+        tx.put(ROOT, "b", "new")?;
+        tx.insert(&list, 1, "y")?;
+        tx.insert(&list, 5, "z")?;
+        tx.put(ROOT, "c", "unreachable")?;
+        Ok(())
+    });
+    assert!(matches!(result, Err(AutomergeError::InvalidIndex(5))));
+    assert_eq!(doc.get_heads(), heads);
+    assert_eq!(realize(doc.document()), before);
+
+    let len = doc.transact(|tx| {
+        tx.put(ROOT, "b", "new")?;
+        Ok::<_, AutomergeError>(tx.length(&list))
+    })?;
+    assert_eq!(len, 1);
+    assert_eq!(doc.get_changes(&heads)?.len(), 1);
+    assert_eq!(doc.get(ROOT, "b")?.unwrap().0, Value::str("new"));
+    Ok(())
+}
+
+#[test]
+fn marks_in_range_clips_marks_to_the_range() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "the quick brown fox").unwrap();
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 0, 9),
+        ExpandMark::None,
+    )
+    .unwrap();
+    doc.mark(
+        &text,
+        Mark::new("italic".to_string(), true, 4, 15),
+        ExpandMark::None,
+    )
+    .unwrap();
+    doc.mark(
+        &text,
+        Mark::new("link".to_string(), "x", 16, 19),
+        ExpandMark::None,
+    )
+    .unwrap();
+
+    let marks = doc.marks_in_range(&text, 6, 12).unwrap();
+    assert_eq!(
+        marks,
+        vec![
+            Mark::new("bold".to_string(), true, 6, 9),
+            Mark::new("italic".to_string(), true, 6, 12),
+        ]
+    );
+
+    // a mark ending at the start of the range doesn't overlap it
+    assert_eq!(
+        doc.marks_in_range(&text, 15, 16).unwrap(),
+        Vec::<Mark<'static>>::new()
+    );
+    assert_eq!(
+        doc.marks_in_range(&text, 0, 19).unwrap(),
+        doc.marks(&text)
+            .unwrap()
+            .into_iter()
+            .map(Mark::into_owned)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn changes_page_walks_the_whole_history() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new();
+    let mut doc2 = AutoCommit::new();
+    for i in 0..5 {
+        doc1.put(ROOT, "a", i)?;
+        doc1.commit();
+        doc2.put(ROOT, "b", i)?;
+        doc2.commit();
+        if i % 2 == 0 {
+            doc1.merge(&mut doc2)?;
+        }
+    }
+    doc1.merge(&mut doc2)?;
+
+    let mut pages = vec![];
+    let mut after = None;
+    loop {
+        let page = doc1
+            .changes_page(after, 3)?
+            .into_iter()
+            .map(|c| c.hash())
+            .collect::<Vec<_>>();
+        if page.is_empty() {
+            break;
+        }
+        assert!(page.len() <= 3);
+        after = page.last().copied();
+        pages.extend(page);
+    }
+
+    let all = doc1.get_changes(&[])?;
+    assert_eq!(pages.len(), all.len());
+    for change in all {
+        let pos = pages.iter().position(|h| *h == change.hash()).unwrap();
+        for dep in change.deps() {
+            assert!(pages[..pos].contains(dep));
+        }
+    }
+
+    let mut other = AutoCommit::new();
+    other.put(ROOT, "c", 1)?;
+    let missing = other.get_heads()[0];
+    assert!(matches!(
+        doc1.changes_page(Some(missing), 3),
+        Err(AutomergeError::InvalidHash(h)) if h == missing
+    ));
+    assert!(doc1.changes_page(None, 0)?.is_empty());
+    assert_eq!(doc1.changes_page(None, usize::MAX)?.len(), pages.len());
+    Ok(())
+}
+
+#[test]
+fn changes_page_puts_empty_changes_before_their_dependents() -> Result<(), AutomergeError> {
+    // An empty change has the same start op as the change after it so the two can only be told
+    // apart by their dependencies
+    let mut doc = AutoCommit::new();
+    for i in 0..8 {
+        doc.empty_change(CommitOptions::default());
+        doc.put(ROOT, "key", i)?;
+        doc.commit();
+    }
+
+    let mut pages = vec![];
+    let mut after = None;
+    loop {
+        let page = doc
+            .changes_page(after, 2)?
+            .into_iter()
+            .map(|c| c.hash())
+            .collect::<Vec<_>>();
+        if page.is_empty() {
+            break;
+        }
+        after = page.last().copied();
+        pages.extend(page);
+    }
+
+    let all = doc.get_changes(&[])?;
+    assert_eq!(pages.len(), 16);
+    assert_eq!(pages, all.iter().map(|c| c.hash()).collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn get_as_converts_values() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "int", 3)?;
+    doc.put(ROOT, "uint", 4_u64)?;
+    doc.put(ROOT, "float", 1.5)?;
+    doc.put(ROOT, "bool", true)?;
+    doc.put(ROOT, "str", "hello")?;
+    doc.put(ROOT, "bytes", vec![1_u8, 2, 3])?;
+    doc.put(ROOT, "counter", ScalarValue::counter(1))?;
+    doc.increment(ROOT, "counter", 2)?;
+    let map = doc.put_object(ROOT, "map", ObjType::Map)?;
+
+    assert_eq!(doc.get_as::<i64, _, _>(ROOT, "int")?, Some(3));
+    assert_eq!(doc.get_as::<i64, _, _>(ROOT, "uint")?, Some(4));
+    assert_eq!(doc.get_as::<i64, _, _>(ROOT, "counter")?, Some(3));
+    assert_eq!(doc.get_as::<f64, _, _>(ROOT, "float")?, Some(1.5));
+    assert_eq!(doc.get_as::<f64, _, _>(ROOT, "int")?, Some(3.0));
+    assert_eq!(doc.get_as::<bool, _, _>(ROOT, "bool")?, Some(true));
+    assert_eq!(
+        doc.get_as::<String, _, _>(ROOT, "str")?,
+        Some("hello".to_string())
+    );
+    assert_eq!(
+        doc.get_as::<Vec<u8>, _, _>(ROOT, "bytes")?,
+        Some(vec![1, 2, 3])
+    );
+    assert_eq!(doc.get_as::<i64, _, _>(ROOT, "missing")?, None);
+    assert_eq!(doc.get_as::<String, _, _>(&map, "missing")?, None);
+
+    let err = doc.get_as::<i64, _, _>(ROOT, "str").unwrap_err();
+    assert!(matches!(
+        err,
+        AutomergeError::InvalidValueType { expected, unexpected }
+            if expected == "int" && unexpected == "str"
+    ));
+    let err = doc.get_as::<String, _, _>(ROOT, "map").unwrap_err();
+    assert!(matches!(
+        err,
+        AutomergeError::InvalidValueType { expected, unexpected }
+            if expected == "str" && unexpected == "map"
+    ));
+    Ok(())
+}
+
+#[test]
+fn clear_empties_maps_lists_and_text() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new();
+    let map = doc1.put_object(ROOT, "map", ObjType::Map)?;
+    let list = doc1.put_object(ROOT, "list", ObjType::List)?;
+    let text = doc1.put_object(ROOT, "text", ObjType::Text)?;
+    doc1.put(&map, "a", 1)?;
+    doc1.put_object(&map, "b", ObjType::List)?;
+    doc1.insert_many(&list, 0, vec![1, 2, 3])?;
+    doc1.splice_text(&text, 0, 0, "hello")?;
+
+    // a conflicted key is removed along with all of its values
+    let mut doc2 = doc1.fork();
+    doc1.put(&map, "c", "one")?;
+    doc2.put(&map, "c", "two")?;
+    doc1.merge(&mut doc2)?;
+    assert_eq!(doc1.get_all(&map, "c")?.len(), 2);
+
+    doc1.clear(&map)?;
+    doc1.clear(&list)?;
+    doc1.clear(&text)?;
+    assert!(doc1.keys(&map).next().is_none());
+    assert_eq!(doc1.length(&list), 0);
+    assert_eq!(doc1.length(&text), 0);
+    assert_eq!(doc1.text(&text)?, "");
+
+    // the objects are still there and can be used again
+    assert_eq!(doc1.get(ROOT, "map")?.unwrap().1, map);
+    doc1.insert(&list, 0, "x")?;
+    assert_eq!(doc1.length(&list), 1);
+
+    // clearing an empty object does nothing
+    let heads = doc1.get_heads();
+    doc1.clear(&text)?;
+    doc1.commit();
+    assert_eq!(doc1.get_heads(), heads);
+    Ok(())
+}
+
+#[test]
+fn apply_change_applies_one_change_at_a_time() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new();
+    for i in 0..3 {
+        doc1.put(ROOT, "n", i)?;
+        doc1.commit();
+    }
+    let changes = doc1
+        .get_changes(&[])?
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut doc2 = Automerge::new();
+    assert!(matches!(
+        doc2.apply_change(changes[1].clone()),
+        Err(AutomergeError::MissingDeps)
+    ));
+    assert!(doc2.get_heads().is_empty());
+
+    for change in &changes {
+        doc2.apply_change(change.clone())?;
+    }
+    assert_eq!(doc2.get_heads(), doc1.get_heads());
+    assert_eq!(doc2.get(ROOT, "n")?.unwrap().0, Value::int(2));
+
+    // applying a change again does nothing
+    doc2.apply_change(changes[2].clone())?;
+    assert_eq!(doc2.get_changes(&[])?.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn text_slice_matches_slicing_the_text() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    let mut expected = String::new();
+    for i in 0..40 {
+        let chunk = format!("line {} ", i);
+        doc.splice_text(&text, expected.len(), 0, &chunk)?;
+        expected.push_str(&chunk);
+        if i % 3 == 0 {
+            doc.splice_text(&text, expected.len() / 2, 4, "")?;
+            expected.replace_range(expected.len() / 2..expected.len() / 2 + 4, "");
+        }
+    }
+    assert_eq!(doc.text(&text)?, expected);
+
+    let len = expected.len();
+    for start in (0..len).step_by(7) {
+        for end in (start..len).step_by(13) {
+            assert_eq!(doc.text_slice(&text, start, end)?, expected[start..end]);
+        }
+    }
+    assert_eq!(doc.text_slice(&text, 10, len + 100)?, expected[10..]);
+    assert_eq!(doc.text_slice(&text, len + 1, len + 5)?, "");
+    assert_eq!(doc.text_slice(&text, 5, 5)?, "");
+
+    // a range starting or ending inside a character includes all of it
+    let mut doc = AutoCommit::new().with_encoding(automerge::TextEncoding::Utf16);
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "h😀llo")?;
+    assert_eq!(doc.text_slice(&text, 1, 2)?, "😀");
+    assert_eq!(doc.text_slice(&text, 2, 4)?, "😀l");
+    Ok(())
+}
+
+#[test]
+fn counter_components_add_up_to_the_counter() -> Result<(), AutomergeError> {
+    let actor1 = ActorId::from(vec![1]);
+    let actor2 = ActorId::from(vec![2]);
+    let actor3 = ActorId::from(vec![3]);
+    let mut doc1 = AutoCommit::new().with_actor(actor1.clone());
+    doc1.put(ROOT, "counter", ScalarValue::counter(5))?;
+    let list = doc1.put_object(ROOT, "list", ObjType::List)?;
+    doc1.insert(&list, 0, ScalarValue::counter(0))?;
+    doc1.insert(&list, 1, "x")?;
+    doc1.commit();
+
+    let mut doc2 = doc1.fork().with_actor(actor2.clone());
+    let mut doc3 = doc1.fork().with_actor(actor3.clone());
+    doc1.increment(ROOT, "counter", 1)?;
+    doc2.increment(ROOT, "counter", 3)?;
+    doc2.increment(ROOT, "counter", 1)?;
+    doc3.increment(ROOT, "counter", -2)?;
+    doc3.increment(&list, 0, 4)?;
+
+    assert_eq!(
+        doc2.counter_components(ROOT, "counter")?,
+        vec![(actor1.clone(), 5), (actor2.clone(), 4)]
+    );
+
+    doc1.merge(&mut doc2)?;
+    doc1.merge(&mut doc3)?;
+    let components = doc1.counter_components(ROOT, "counter")?;
+    assert_eq!(
+        components,
+        vec![(actor1.clone(), 6), (actor2, 4), (actor3.clone(), -2)]
+    );
+    let sum: i64 = components.iter().map(|(_, n)| n).sum();
+    assert_eq!(doc1.get(ROOT, "counter")?.unwrap().0, Value::counter(sum));
+
+    assert_eq!(
+        doc1.counter_components(&list, 0)?,
+        vec![(actor1, 0), (actor3, 4)]
+    );
+    assert!(matches!(
+        doc1.counter_components(&list, 1),
+        Err(AutomergeError::InvalidValueType { .. })
+    ));
+    assert!(matches!(
+        doc1.counter_components(ROOT, "missing"),
+        Err(AutomergeError::MissingProp { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn replace_returns_the_previous_value() -> Result<(), AutomergeError> {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    assert_eq!(tx.replace(ROOT, "a", "first")?, (None, None));
+    // sees the value put earlier in the same transaction
+    assert_eq!(tx.replace(ROOT, "a", 2)?, (Some(Value::str("first")), None));
+    let (previous, map) = tx.replace(ROOT, "a", Value::Object(ObjType::Map))?;
+    assert_eq!(previous, Some(Value::int(2)));
+    // the new object can be used without reading it back
+    let map = map.unwrap();
+    tx.put(&map, "inner", 1)?;
+    assert_eq!(tx.get(ROOT, "a")?.unwrap().1, map);
+    assert_eq!(
+        tx.replace(ROOT, "a", ScalarValue::Null)?,
+        (Some(Value::Object(ObjType::Map)), None)
+    );
+    tx.commit();
+    assert_eq!(doc.get(ROOT, "a")?.unwrap().0, Value::from(()));
+
+    // with a conflict the previous value is the winner
+    let mut doc1 = AutoCommit::new();
+    let mut doc2 = doc1.fork();
+    doc1.put(ROOT, "b", 1)?;
+    doc2.put(ROOT, "b", 2)?;
+    doc1.merge(&mut doc2)?;
+    let winner = doc1.get(ROOT, "b")?.unwrap().0.into_owned();
+    assert_eq!(doc1.replace(ROOT, "b", 3)?, (Some(winner), None));
+    assert_eq!(doc1.get_all(ROOT, "b")?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn merge_returns_the_number_of_changes_applied() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(ROOT, "a", 1)?;
+    doc1.commit();
+    let mut doc2 = doc1.fork();
+    for i in 0..3 {
+        doc2.put(ROOT, "b", i)?;
+        doc2.commit();
+    }
+    doc1.put(ROOT, "c", 1)?;
+
+    assert_eq!(doc1.merge(&mut doc2)?, 3);
+    let heads = doc1.get_heads();
+    assert_eq!(doc1.merge(&mut doc2)?, 0);
+    assert_eq!(doc1.get_heads(), heads);
+
+    // the other way round only the change doc2 hasn't seen is applied
+    assert_eq!(doc2.merge(&mut doc1)?, 1);
+    assert_eq!(doc2.get_heads(), heads);
+    Ok(())
+}
+
+#[test]
+fn heads_changed_since_detects_new_changes() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new();
+    assert!(!doc1.heads_changed_since(&[]));
+    doc1.put(ROOT, "a", 1)?;
+    assert!(doc1.heads_changed_since(&[]));
+
+    let mut doc2 = doc1.fork();
+    doc2.put(ROOT, "b", 1)?;
+    doc1.put(ROOT, "c", 1)?;
+    doc1.merge(&mut doc2)?;
+    let mut heads = doc1.get_heads();
+    assert_eq!(heads.len(), 2);
+    assert!(!doc1.heads_changed_since(&heads));
+    heads.reverse();
+    assert!(!doc1.heads_changed_since(&heads));
+    assert!(doc1.heads_changed_since(&heads[..1]));
+
+    doc1.put(ROOT, "d", 1)?;
+    assert!(doc1.heads_changed_since(&heads));
+    Ok(())
+}
+
+#[test]
+fn text_normalized_replaces_line_endings() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "a\r\nb\nc\rd")?;
+    assert_eq!(doc.text_normalized(&text, LineEnding::Lf)?, "a\nb\nc\nd");
+    assert_eq!(
+        doc.text_normalized(&text, LineEnding::CrLf)?,
+        "a\r\nb\r\nc\r\nd"
+    );
+    assert_eq!(doc.text(&text)?, "a\r\nb\nc\rd");
+    Ok(())
+}
+
+#[test]
+fn objects_cannot_be_inserted_into_text() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "hello")?;
+    assert!(matches!(
+        doc.insert_object(&text, 2, ObjType::Map),
+        Err(AutomergeError::InvalidTextValue(ObjType::Map))
+    ));
+    assert!(matches!(
+        doc.push_object(&text, ObjType::List),
+        Err(AutomergeError::InvalidTextValue(ObjType::List))
+    ));
+    let mut manual = doc.document().clone();
+    let mut tx = manual.transaction();
+    assert!(matches!(
+        tx.push_object(&text, ObjType::Text),
+        Err(AutomergeError::InvalidTextValue(ObjType::Text))
+    ));
+    tx.rollback();
+    assert_eq!(doc.text(&text)?, "hello");
+    assert_eq!(doc.length(&text), 5);
+    Ok(())
+}
+
+#[test]
+fn changes_needed_by_returns_what_the_peer_lacks() -> Result<(), AutomergeError> {
+    let mut server = AutoCommit::new();
+    server.put(ROOT, "a", 1)?;
+    let mut client = server.fork();
+    let common = client.get_heads();
+
+    server.put(ROOT, "b", 2)?;
+    server.commit();
+    server.put(ROOT, "c", 3)?;
+    server.commit();
+    client.put(ROOT, "d", 4)?;
+    let client_change = client.get_heads()[0];
+
+    // the client's own change is unknown to the server and ignored
+    let mut their_heads = common.clone();
+    their_heads.push(client_change);
+    let needed = server
+        .changes_needed_by(&their_heads)
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(needed.len(), 2);
+    // in dependency order
+    assert_eq!(needed[0].deps(), common.as_slice());
+    assert_eq!(needed[1].deps(), &[needed[0].hash()]);
+
+    client.apply_changes(needed)?;
+    let mut expected = server.get_heads();
+    expected.push(client_change);
+    expected.sort();
+    assert_eq!(client.get_heads(), expected);
+
+    let server_heads = server.get_heads();
+    assert!(server.changes_needed_by(&server_heads).is_empty());
+    // nothing in common can be found through an unknown head so everything is needed
+    assert_eq!(server.changes_needed_by(&[client_change]).len(), 3);
+    assert_eq!(server.changes_needed_by(&[]).len(), 3);
+    Ok(())
+}
+
+#[test]
+fn objects_of_type_finds_every_live_object() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+    let mut texts = Vec::new();
+    for i in 0..3 {
+        texts.push(doc.insert_object(&list, i, ObjType::Text)?);
+    }
+    let map = doc.put_object(ROOT, "map", ObjType::Map)?;
+    texts.push(doc.put_object(&map, "text", ObjType::Text)?);
+    let deleted = doc.put_object(ROOT, "deleted", ObjType::Map)?;
+    doc.put_object(&deleted, "text", ObjType::Text)?;
+    doc.delete(ROOT, "deleted")?;
+
+    assert_eq!(doc.objects_of_type(ObjType::Text), texts);
+    assert_eq!(doc.objects_of_type(ObjType::List), vec![list]);
+    assert_eq!(doc.objects_of_type(ObjType::Map), vec![ROOT, map]);
+    assert!(doc.objects_of_type(ObjType::Table).is_empty());
+    Ok(())
+}
+
+#[test]
+fn marks_match_a_full_scan_of_a_large_text() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new().with_encoding(TextEncoding::Utf16);
+    let text = doc1.put_object(ROOT, "text", ObjType::Text)?;
+    let line = "a line of text with an 😀 in it\n";
+    for _ in 0..40 {
+        let len = doc1.length(&text);
+        doc1.splice_text(&text, len, 0, line)?;
+    }
+    doc1.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 100, 200),
+        ExpandMark::After,
+    )?;
+    doc1.mark(
+        &text,
+        Mark::new("link".to_string(), "x", 500, 900),
+        ExpandMark::Both,
+    )?;
+    let mut doc2 = doc1.fork();
+    doc2.splice_text(&text, 50, 20, "inserted before the marks")?;
+    doc2.splice_text(&text, 600, 10, "")?;
+    doc2.mark(
+        &text,
+        Mark::new("bold".to_string(), false, 150, 160),
+        ExpandMark::None,
+    )?;
+    doc1.splice_text(&text, 1000, 0, "😀😀")?;
+    doc1.merge(&mut doc2)?;
+
+    let heads = doc1.get_heads();
+    let marks = doc1
+        .marks(&text)?
+        .into_iter()
+        .map(Mark::into_owned)
+        .collect::<Vec<_>>();
+    let scanned = doc1
+        .marks_at(&text, &heads)?
+        .into_iter()
+        .map(Mark::into_owned)
+        .collect::<Vec<_>>();
+    assert_eq!(marks.len(), 4);
+    assert_eq!(marks, scanned);
+    Ok(())
+}
+
+#[test]
+fn increment_counters_in_a_list() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+    for i in 0..4 {
+        doc.insert(&list, i, ScalarValue::counter(i as i64))?;
+    }
+    doc.insert(&list, 4, "not a counter")?;
+
+    doc.increment(&list, Prop::Seq(2), 10)?;
+    doc.increment(&list, 2, -3)?;
+    assert_eq!(
+        doc.get(&list, 2)?.unwrap().0,
+        Value::from(ScalarValue::counter(9))
+    );
+    assert_eq!(
+        doc.get(&list, 1)?.unwrap().0,
+        Value::from(ScalarValue::counter(1))
+    );
+
+    assert!(matches!(
+        doc.increment(&list, 4, 1),
+        Err(AutomergeError::MissingCounter)
+    ));
+    assert!(matches!(
+        doc.increment(&list, 5, 1),
+        Err(AutomergeError::MissingProp { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn touching_marks_are_joined_when_the_seam_would_expand() -> Result<(), AutomergeError> {
+    for (expand, joined) in [
+        (ExpandMark::None, false),
+        (ExpandMark::Before, true),
+        (ExpandMark::After, true),
+        (ExpandMark::Both, true),
+    ] {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+        doc.splice_text(&text, 0, 0, "0123456789abc")?;
+        doc.mark(&text, Mark::new("bold".to_string(), true, 0, 5), expand)?;
+        doc.mark(&text, Mark::new("bold".to_string(), true, 5, 10), expand)?;
+        let spans = doc
+            .marks(&text)?
+            .iter()
+            .map(|m| (m.start, m.end))
+            .collect::<Vec<_>>();
+        if joined {
+            assert_eq!(spans, vec![(0, 10)], "{:?}", expand);
+        } else {
+            assert_eq!(spans, vec![(0, 5), (5, 10)], "{:?}", expand);
+        }
+        let heads = doc.get_heads();
+        assert_eq!(doc.marks_at(&text, &heads)?.len(), spans.len());
+    }
+
+    // marks with different values are never joined
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "0123456789")?;
+    let link = |url, start, end| Mark::new("link".to_string(), url, start, end);
+    doc.mark(&text, link("a", 0, 5), ExpandMark::Both)?;
+    doc.mark(&text, link("b", 5, 10), ExpandMark::Both)?;
+    assert_eq!(doc.marks(&text)?.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn index_of_follows_an_element_as_text_changes() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "hello world")?;
+    let (_, _, anchor) = doc.list_range(&text, 6..7).next().unwrap();
+    assert_eq!(doc.index_of(&text, &anchor, None)?, Some(6));
+    let before = doc.get_heads();
+
+    doc.splice_text(&text, 0, 0, "oh, ")?;
+    assert_eq!(doc.index_of(&text, &anchor, None)?, Some(10));
+    assert_eq!(doc.index_of(&text, &anchor, Some(&before))?, Some(6));
+
+    doc.splice_text(&text, 10, 1, "")?;
+    assert_eq!(doc.index_of(&text, &anchor, None)?, None);
+    assert_eq!(doc.index_of(&text, &anchor, Some(&before))?, Some(6));
+    assert_eq!(doc.index_of(&text, &anchor, Some(&[]))?, None);
+
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+    assert!(matches!(
+        doc.index_of(&list, &anchor, None),
+        Err(AutomergeError::InvalidElemId(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn apply_changes_unordered_sorts_by_dependencies() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new();
+    for i in 0..5 {
+        doc1.put(ROOT, "a", i)?;
+        doc1.commit();
+    }
+    let mut doc2 = doc1.fork();
+    for i in 0..3 {
+        doc1.put(ROOT, "b", i)?;
+        doc1.commit();
+        doc2.put(ROOT, "c", i)?;
+        doc2.commit();
+    }
+    doc1.merge(&mut doc2)?;
+    doc1.put(ROOT, "d", 1)?;
+    let mut changes = doc1
+        .get_changes(&[])?
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(changes.len(), 12);
+    changes.reverse();
+    changes.rotate_left(5);
+
+    // a change whose dependencies are missing from the batch is an error
+    let mut partial = changes.clone();
+    partial.remove(3);
+    let mut doc3 = AutoCommit::new();
+    assert!(matches!(
+        doc3.apply_changes_unordered(partial),
+        Err(AutomergeError::MissingDeps)
+    ));
+    assert!(doc3.get_heads().is_empty());
+
+    doc3.apply_changes_unordered(changes)?;
+    assert_eq!(doc3.get_heads(), doc1.get_heads());
+    assert_eq!(doc3.get(ROOT, "d")?.unwrap().0, Value::int(1));
+    assert_eq!(doc3.get(ROOT, "c")?.unwrap().0, Value::int(2));
+    Ok(())
+}
+
+#[test]
+fn delete_elem_removes_the_element_wherever_it_is() -> Result<(), AutomergeError> {
+    let mut doc1 = new_doc();
+    let list = doc1.put_object(ROOT, "list", ObjType::List)?;
+    for (i, value) in ["a", "b", "c"].iter().enumerate() {
+        doc1.insert(&list, i, *value)?;
+    }
+    let (_, _, b) = doc1.list_range(&list, 1..2).next().unwrap();
+    let mut doc2 = doc1.fork().with_actor(ActorId::random());
+
+    // doc1 shifts "b" along while doc2 shifts it and then deletes it by ID
+    doc1.insert(&list, 0, "x")?;
+    doc2.insert(&list, 0, "y")?;
+    doc2.insert(&list, 0, "z")?;
+    doc2.delete_elem(&list, &b)?;
+    assert_eq!(doc2.length(&list), 4);
+
+    doc1.merge(&mut doc2)?;
+    doc2.merge(&mut doc1)?;
+    let values = |doc: &AutoCommit| {
+        doc.list_range(&list, ..)
+            .map(|(_, v, _)| v.into_string().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(values(&doc1), values(&doc2));
+    assert!(!values(&doc1).contains(&"b".to_string()));
+    assert_eq!(doc1.length(&list), 5);
+
+    assert!(matches!(
+        doc1.delete_elem(&list, &b),
+        Err(AutomergeError::ElemDeleted(_))
+    ));
+    let other = doc1.put_object(ROOT, "other", ObjType::List)?;
+    let (_, _, a) = doc1.list_range(&list, ..).last().unwrap();
+    assert!(matches!(
+        doc1.delete_elem(&other, &a),
+        Err(AutomergeError::InvalidElemId(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn save_canonical_is_independent_of_merge_order() -> Result<(), AutomergeError> {
+    let mut base = AutoCommit::new();
+    base.put(ROOT, "base", 1)?;
+    let mut doc1 = base.fork();
+    let mut doc2 = base.fork();
+    for i in 0..3 {
+        doc1.put(ROOT, "one", i)?;
+        doc1.commit();
+        doc2.put(ROOT, "two", i)?;
+        doc2.commit();
+    }
+
+    let mut left = doc1.fork();
+    left.merge(&mut doc2)?;
+    let mut right = doc2.fork();
+    right.merge(&mut doc1)?;
+    assert_eq!(left.get_heads(), right.get_heads());
+
+    assert_ne!(left.save(), right.save());
+    let canonical = left.save_canonical();
+    assert_eq!(canonical, right.save_canonical());
+
+    let mut loaded = AutoCommit::load(&canonical)?;
+    assert_eq!(loaded.get_heads(), left.get_heads());
+    assert_eq!(loaded.save_canonical(), canonical);
+    Ok(())
+}
+
+#[test]
+fn splice_text_with_expand_overrides_the_marks_at_the_boundary() {
+    let bold = |start, end| Mark::new("bold".to_string(), true, start, end);
+
+    // forced inherit on a mark which never expands
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.mark(&text, bold(2, 5), ExpandMark::None).unwrap();
+    let mut forced = doc.fork();
+
+    doc.splice_text(&text, 5, 0, "!").unwrap();
+    doc.splice_text(&text, 2, 0, "?").unwrap();
+    assert_eq!(doc.marks(&text).unwrap(), vec![bold(3, 6)]);
+
+    forced
+        .splice_text_with_expand(&text, 5, 0, "!", Some(ExpandMark::After))
+        .unwrap();
+    assert_eq!(forced.marks(&text).unwrap(), vec![bold(2, 6)]);
+    forced
+        .splice_text_with_expand(&text, 2, 0, "?", Some(ExpandMark::Both))
+        .unwrap();
+    assert_eq!(forced.marks(&text).unwrap(), vec![bold(2, 7)]);
+    assert_eq!(forced.text(&text).unwrap(), "he?llo! world");
+
+    // forced exclude on a mark which always expands
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.mark(&text, bold(2, 5), ExpandMark::Both).unwrap();
+    let mut forced = doc.fork();
+
+    doc.splice_text(&text, 5, 0, "!").unwrap();
+    doc.splice_text(&text, 2, 0, "?").unwrap();
+    assert_eq!(doc.marks(&text).unwrap(), vec![bold(2, 7)]);
+
+    forced
+        .splice_text_with_expand(&text, 5, 0, "!", Some(ExpandMark::None))
+        .unwrap();
+    forced
+        .splice_text_with_expand(&text, 2, 0, "?", Some(ExpandMark::After))
+        .unwrap();
+    assert_eq!(forced.marks(&text).unwrap(), vec![bold(3, 6)]);
+
+    // without an override it is the same as splice_text, and the override only lasts one splice
+    forced
+        .splice_text_with_expand(&text, 6, 0, "-", None)
+        .unwrap();
+    forced.splice_text(&text, 3, 0, "+").unwrap();
+    assert_eq!(forced.marks(&text).unwrap(), vec![bold(3, 8)]);
+    assert_eq!(forced.text(&text).unwrap(), "he?+llo-! world");
+}
+
+#[test]
+fn state_hash_depends_only_on_the_value() -> Result<(), AutomergeError> {
+    let mut doc1 = AutoCommit::new();
+    let list = doc1.put_object(ROOT, "list", ObjType::List)?;
+    doc1.insert(&list, 0, "a")?;
+    let text = doc1.put_object(ROOT, "text", ObjType::Text)?;
+    doc1.put(ROOT, "counter", ScalarValue::counter(1))?;
+    let mut doc2 = doc1.fork();
+
+    // the same end state reached by different edits
+    doc1.splice_text(&text, 0, 0, "hello")?;
+    doc1.insert(&list, 1, "b")?;
+    doc1.increment(ROOT, "counter", 2)?;
+    doc1.put(ROOT, "key", "value")?;
+
+    doc2.splice_text(&text, 0, 0, "help")?;
+    doc2.splice_text(&text, 3, 1, "lo")?;
+    doc2.insert(&list, 0, "b")?;
+    doc2.delete(&list, 0)?;
+    doc2.insert(&list, 1, "b")?;
+    doc2.increment(ROOT, "counter", 5)?;
+    doc2.increment(ROOT, "counter", -3)?;
+    doc2.put(ROOT, "key", "other")?;
+    doc2.put(ROOT, "key", "value")?;
+
+    assert_ne!(doc1.get_heads(), doc2.get_heads());
+    assert_eq!(doc1.state_hash(), doc2.state_hash());
+
+    doc2.put(ROOT, "key", "changed")?;
+    assert_ne!(doc1.state_hash(), doc2.state_hash());
+
+    // the same string as a scalar, or a value moved to another key, is a different state
+    let mut a = AutoCommit::new();
+    a.put(ROOT, "x", "ab")?;
+    let mut b = AutoCommit::new();
+    let t = b.put_object(ROOT, "x", ObjType::Text)?;
+    b.splice_text(&t, 0, 0, "ab")?;
+    assert_ne!(a.state_hash(), b.state_hash());
+    let mut c = AutoCommit::new();
+    c.put(ROOT, "y", "ab")?;
+    assert_ne!(a.state_hash(), c.state_hash());
+    Ok(())
+}
+
+#[test]
+fn marks_on_a_list() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "items", ObjType::List)?;
+    for i in 0..7 {
+        doc.insert(&list, i, i as i64)?;
+    }
+    let selected = Mark::new("selected".to_string(), true, 2, 5);
+    doc.mark(&list, selected.clone(), ExpandMark::None)?;
+    assert_eq!(doc.marks(&list)?, vec![selected]);
+
+    // the mark ops are not elements of the list
+    assert_eq!(doc.length(&list), 7);
+    let values = doc
+        .list_range(&list, ..)
+        .map(|(_, v, _)| v.to_i64().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec![0, 1, 2, 3, 4, 5, 6]);
+
+    // and they move with the elements
+    doc.insert(&list, 0, -1)?;
+    doc.insert(&list, 4, 100)?;
+    doc.delete(&list, 7)?;
+    assert_eq!(
+        doc.marks(&list)?,
+        vec![Mark::new("selected".to_string(), true, 3, 7)]
+    );
+
+    let map = doc.put_object(ROOT, "map", ObjType::Map)?;
+    assert!(matches!(
+        doc.mark(
+            &map,
+            Mark::new("selected".to_string(), true, 0, 1),
+            ExpandMark::None
+        ),
+        Err(AutomergeError::InvalidOp(ObjType::Map))
+    ));
+    assert!(matches!(
+        doc.unmark(&map, "selected", 0, 1, ExpandMark::None),
+        Err(AutomergeError::InvalidOp(ObjType::Map))
+    ));
+    Ok(())
+}
+
+#[test]
+fn insert_object_at_reports_where_the_object_is() -> Result<(), AutomergeError> {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let list = tx.put_object(ROOT, "list", ObjType::List)?;
+    tx.insert(&list, 0, "a")?;
+    tx.insert(&list, 1, "b")?;
+
+    let (first, first_index) = tx.insert_object_at(&list, 1, ObjType::Map)?;
+    assert_eq!(first_index, 1);
+    let (second, second_index) = tx.insert_object_at(&list, 1, ObjType::List)?;
+    assert_eq!(second_index, 1);
+    // the second insert pushed the first one along
+    assert_eq!(tx.index_of(&list, &first, None)?, Some(2));
+    assert_eq!(tx.get(&list, second_index)?.unwrap().1, second);
+    tx.put(&first, "key", "value")?;
+    tx.commit();
+
+    assert_eq!(doc.length(&list), 4);
+    assert_eq!(doc.get(&list, 2)?.unwrap().1, first);
+    assert!(doc.get(&first, "key")?.is_some());
+    Ok(())
+}
+
+#[test]
+fn changes_touching_an_object_and_its_children() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let a = doc.put_object(ROOT, "a", ObjType::Map)?;
+    let b = doc.put_object(ROOT, "b", ObjType::List)?;
+    let created = doc.commit().unwrap();
+
+    doc.put(&a, "x", 1)?;
+    let a1 = doc.commit().unwrap();
+    doc.insert(&b, 0, "y")?;
+    let b1 = doc.commit().unwrap();
+    let nested = doc.put_object(&a, "nested", ObjType::Text)?;
+    let a2 = doc.commit().unwrap();
+    doc.splice_text(&nested, 0, 0, "hello")?;
+    let a3 = doc.commit().unwrap();
+    doc.delete(&b, 0)?;
+    let b2 = doc.commit().unwrap();
+    doc.put(ROOT, "unrelated", true)?;
+    let root = doc.commit().unwrap();
+    // a change to an object which has since been deleted still counts
+    doc.delete(&a, "nested")?;
+    let a4 = doc.commit().unwrap();
+
+    assert_eq!(doc.changes_touching(&a), vec![created, a1, a2, a3, a4]);
+    assert_eq!(doc.changes_touching(&b), vec![created, b1, b2]);
+    assert_eq!(doc.changes_touching(&nested), vec![a2, a3]);
+    assert_eq!(
+        doc.changes_touching(&ROOT),
+        vec![created, a1, b1, a2, a3, b2, root, a4]
+    );
+
+    let mut other = AutoCommit::new();
+    let elsewhere = other.put_object(ROOT, "c", ObjType::Map)?;
+    assert!(doc.changes_touching(&elsewhere).is_empty());
+
+    // changes by other actors count
+    let mut fork = doc.fork();
+    fork.insert(&b, 0, "z")?;
+    let b3 = fork.commit().unwrap();
+    doc.merge(&mut fork)?;
+    assert_eq!(doc.changes_touching(&b), vec![created, b1, b2, b3]);
+    Ok(())
+}
+
+#[test]
+fn text_lengths_in_different_units() -> Result<(), AutomergeError> {
+    // "e" with a combining acute accent, a thumbs up with a skin tone and "a": three graphemes
+    let s = "e\u{301}\u{1f44d}\u{1f3fd}a";
+    for encoding in [TextEncoding::Utf8, TextEncoding::Utf16] {
+        let mut doc = AutoCommit::new().with_encoding(encoding);
+        let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+        doc.splice_text(&text, 0, 0, s)?;
+        assert_eq!(doc.text_length_chars(&text)?, 5);
+        assert_eq!(doc.text_length_utf16(&text)?, 7);
+        let expected = match encoding {
+            TextEncoding::Utf8 => 5,
+            TextEncoding::Utf16 => 7,
+        };
+        assert_eq!(doc.length(&text), expected);
+
+        doc.splice_text(&text, 0, 2, "")?;
+        assert_eq!(doc.text_length_chars(&text)?, 3);
+        assert_eq!(doc.text_length_utf16(&text)?, 5);
+
+        let list = doc.put_object(ROOT, "list", ObjType::List)?;
+        assert!(matches!(
+            doc.text_length_chars(&list),
+            Err(AutomergeError::InvalidOp(ObjType::List))
+        ));
+        assert!(matches!(
+            doc.text_length_utf16(ROOT),
+            Err(AutomergeError::InvalidOp(ObjType::Map))
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn put_bytes_round_trips_a_large_blob() -> Result<(), AutomergeError> {
+    let blob = (0..64 * 1024)
+        .map(|i| (i * 7 % 256) as u8)
+        .collect::<Vec<_>>();
+    let mut doc = AutoCommit::new();
+    doc.put_bytes(ROOT, "blob", &blob)?;
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+    doc.insert(&list, 0, "placeholder")?;
+    doc.put_bytes(&list, 0, &[])?;
+    assert_eq!(doc.get_bytes(ROOT, "blob")?, Some(blob.clone()));
+
+    let loaded = AutoCommit::load(&doc.save())?;
+    assert_eq!(loaded.get_bytes(ROOT, "blob")?, Some(blob));
+    assert_eq!(loaded.get_bytes(&list, 0)?, Some(Vec::new()));
+    assert_eq!(loaded.get_bytes(ROOT, "missing")?, None);
+    assert!(matches!(
+        loaded.get_bytes(ROOT, "list"),
+        Err(AutomergeError::InvalidValueType { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn set_clock_provides_change_timestamps() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1)?;
+    let untimed = doc.commit().unwrap();
+    assert_eq!(doc.get_change_by_hash(&untimed).unwrap().timestamp(), 0);
+
+    let ticks = std::sync::Arc::new(std::sync::atomic::AtomicI64::new(1_000));
+    let source = ticks.clone();
+    doc.set_clock(Box::new(move || {
+        source.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+    }));
+    doc.put(ROOT, "a", 2)?;
+    let first = doc.commit().unwrap();
+    doc.put(ROOT, "a", 3)?;
+    let second = doc.commit().unwrap();
+    assert_eq!(doc.get_change_by_hash(&first).unwrap().timestamp(), 1_000);
+    assert_eq!(doc.get_change_by_hash(&second).unwrap().timestamp(), 1_001);
+
+    // an explicit time still wins
+    doc.put(ROOT, "a", 4)?;
+    let explicit = doc
+        .commit_with(CommitOptions::default().with_time(42))
+        .unwrap();
+    assert_eq!(doc.get_change_by_hash(&explicit).unwrap().timestamp(), 42);
+
+    // forks keep the clock
+    let mut fork = doc.fork();
+    fork.put(ROOT, "b", 1)?;
+    let forked = fork.commit().unwrap();
+    assert_eq!(fork.get_change_by_hash(&forked).unwrap().timestamp(), 1_002);
+    Ok(())
+}
+
+#[test]
+fn try_value_treats_a_missing_object_as_missing() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let map = doc.put_object(ROOT, "map", ObjType::Map)?;
+    doc.put(&map, "key", "value")?;
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+
+    let (value, _) = doc.try_value(&map, "key")?.unwrap();
+    assert_eq!(value, Value::str("value"));
+    assert_eq!(doc.try_value(&map, "missing")?, None);
+    assert_eq!(doc.try_value(&list, 3)?, None);
+
+    let mut other = AutoCommit::new();
+    let elsewhere = other.put_object(ROOT, "map", ObjType::Map)?;
+    assert_eq!(doc.try_value(&elsewhere, "key")?, None);
+    assert!(matches!(
+        doc.get(&elsewhere, "key"),
+        Err(AutomergeError::InvalidObj(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn splice_with_generates_values_from_their_position() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+    doc.splice(
+        &list,
+        0,
+        0,
+        ["first", "x", "y", "last"].map(ScalarValue::from),
+    )?;
+
+    let ids = doc.splice_with(&list, 1, 2, |i| {
+        (i < 100).then(|| Value::from(format!("id-{}", i)))
+    })?;
+    assert_eq!(ids.len(), 100);
+    assert_eq!(doc.length(&list), 102);
+    for (i, id) in ids.iter().enumerate() {
+        let (value, elem) = doc.get(&list, i + 1)?.unwrap();
+        assert_eq!(value.into_string().unwrap(), format!("id-{}", i));
+        assert_eq!(&elem, id);
+    }
+    assert_eq!(doc.get(&list, 101)?.unwrap().0, Value::str("last"));
+
+    let objects = doc.splice_with(&list, 0, 0, |i| match i {
+        0 => Some(Value::Object(ObjType::Map)),
+        1 => Some(Value::int(1)),
+        _ => None,
+    })?;
+    doc.put(&objects[0], "key", "value")?;
+    assert_eq!(doc.get(&list, 1)?.unwrap().0, Value::int(1));
+
+    // wide characters in utf16 text still end up in order
+    let mut doc = AutoCommit::new().with_encoding(TextEncoding::Utf16);
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    let chars = ['a', '\u{1f600}', 'b'];
+    doc.splice_with(&text, 0, 0, |i| chars.get(i).map(|c| Value::from(*c)))?;
+    assert_eq!(doc.text(&text)?, "a\u{1f600}b");
+    Ok(())
+}
+
+#[test]
+fn text_with_tombstones_includes_deleted_elements() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "hello")?;
+    let ids = doc
+        .list_range(&text, ..)
+        .map(|(_, _, id)| id)
+        .collect::<Vec<_>>();
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 0, 2),
+        ExpandMark::None,
+    )?;
+    doc.splice_text(&text, 1, 2, "")?;
+
+    let cells = doc.text_with_tombstones(&text)?;
+    assert_eq!(
+        cells,
+        vec![
+            TextCell::Visible("h".to_string(), ids[0].clone()),
+            TextCell::Deleted(ids[1].clone()),
+            TextCell::Deleted(ids[2].clone()),
+            TextCell::Visible("l".to_string(), ids[3].clone()),
+            TextCell::Visible("o".to_string(), ids[4].clone()),
+        ]
+    );
+    // the visible cells are the text
+    let visible = cells
+        .iter()
+        .filter_map(|c| match c {
+            TextCell::Visible(s, _) => Some(s.as_str()),
+            TextCell::Deleted(_) => None,
+        })
+        .collect::<String>();
+    assert_eq!(visible, doc.text(&text)?);
+
+    // flattening discards the deleted elements
+    doc.flatten()?;
+    let text = doc.get(ROOT, "text")?.unwrap().1;
+    assert!(doc
+        .text_with_tombstones(&text)?
+        .iter()
+        .all(|c| matches!(c, TextCell::Visible(..))));
+
+    assert!(matches!(
+        doc.text_with_tombstones(ROOT),
+        Err(AutomergeError::InvalidOp(ObjType::Map))
+    ));
+    Ok(())
+}
+
+#[test]
+fn diff_subtree_only_includes_patches_in_the_subtree() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let a = doc.put_object(ROOT, "a", ObjType::Map)?;
+    let b = doc.put_object(ROOT, "b", ObjType::Map)?;
+    doc.commit();
+    let before = doc.get_heads();
+
+    doc.put(&a, "x", 1)?;
+    doc.commit();
+    doc.put(&b, "y", 2)?;
+    doc.commit();
+    let nested = doc.put_object(&a, "nested", ObjType::List)?;
+    doc.insert(&nested, 0, "z")?;
+    doc.commit();
+    doc.put(ROOT, "unrelated", true)?;
+    let after = doc.get_heads();
+
+    let patches = doc.diff_subtree(&a, &before, &after)?;
+    let objs = patches.iter().map(|p| p.obj.clone()).collect::<Vec<_>>();
+    assert_eq!(objs, vec![a.clone(), a.clone(), nested.clone()]);
+    assert!(matches!(
+        &patches[0].action,
+        PatchAction::PutMap { key, value: (Value::Scalar(v), _), .. }
+            if key == "x" && v.as_ref() == &ScalarValue::Int(1)
+    ));
+    assert!(matches!(
+        &patches[2].action,
+        PatchAction::Insert { index: 0, .. }
+    ));
+
+    let patches = doc.diff_subtree(&b, &before, &after)?;
+    assert_eq!(patches.len(), 1);
+    assert!(matches!(
+        &patches[0].action,
+        PatchAction::PutMap { key, .. } if key == "y"
+    ));
+
+    assert!(doc.diff_subtree(&b, &after, &after)?.is_empty());
+
+    // a `before` on a concurrent branch isn't an ancestor of `after`
+    let mut branch = doc.fork_at(&before)?;
+    branch.put(&a, "x", 3)?;
+    let branch_heads = branch.get_heads();
+    doc.merge(&mut branch)?;
+    assert!(matches!(
+        doc.diff_subtree(&a, &branch_heads, &after),
+        Err(AutomergeError::NotAnAncestor(h)) if h == branch_heads[0]
+    ));
+    let mut other = AutoCommit::new();
+    other.put(ROOT, "c", 1)?;
+    let missing = other.get_heads();
+    assert!(matches!(
+        doc.diff_subtree(&a, &missing, &after),
+        Err(AutomergeError::InvalidHash(h)) if h == missing[0]
+    ));
+    Ok(())
+}
+
+#[test]
+fn sorted_element_ids_match_list_order() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+    for i in 0..5 {
+        doc.insert(&list, i, i as i64)?;
+    }
+    doc.commit();
+    doc.insert(&list, 5, "last")?;
+    let mut ids = doc
+        .list_range(&list, ..)
+        .map(|(_, _, id)| id)
+        .collect::<Vec<_>>();
+    let in_order = ids.clone();
+    ids.reverse();
+    ids.push(ROOT);
+    ids.sort();
+    assert_eq!(ids[0], ROOT);
+    assert_eq!(ids[1..], in_order[..]);
+    assert!(list < in_order[0]);
+    Ok(())
+}
+
+#[test]
+fn marks_are_sorted_by_start_end_and_name() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "the quick brown fox")?;
+    for (name, start, end) in [
+        ("underline", 2, 8),
+        ("italic", 0, 5),
+        ("bold", 0, 5),
+        ("comment", 2, 4),
+    ] {
+        doc.mark(
+            &text,
+            Mark::new(name.to_string(), true, start, end),
+            ExpandMark::None,
+        )?;
+    }
+
+    let spans = |marks: Vec<Mark<'_>>| {
+        marks
+            .iter()
+            .map(|m| (m.name().to_string(), m.start, m.end))
+            .collect::<Vec<_>>()
+    };
+    let expected = vec![
+        ("bold".to_string(), 0, 5),
+        ("italic".to_string(), 0, 5),
+        ("comment".to_string(), 2, 4),
+        ("underline".to_string(), 2, 8),
+    ];
+    assert_eq!(spans(doc.marks(&text)?), expected);
+    let heads = doc.get_heads();
+    assert_eq!(spans(doc.marks_at(&text, &heads)?), expected);
+
+    // clipping to the range puts all four marks at the same start
+    assert_eq!(
+        spans(doc.marks_in_range(&text, 3, 6)?),
+        vec![
+            ("comment".to_string(), 3, 4),
+            ("bold".to_string(), 3, 5),
+            ("italic".to_string(), 3, 5),
+            ("underline".to_string(), 3, 6),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn inverse_ops_undo_a_transaction() -> Result<(), AutomergeError> {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "title", "draft")?;
+    let list = tx.put_object(ROOT, "list", ObjType::List)?;
+    tx.splice(&list, 0, 0, vec![1.into(), 2.into(), 3.into()])?;
+    let text = tx.put_object(ROOT, "text", ObjType::Text)?;
+    tx.splice_text(&text, 0, 0, "hello world")?;
+    tx.put(ROOT, "counter", ScalarValue::counter(1))?;
+    tx.commit();
+    let before = doc.get_heads();
+
+    let mut tx = doc.transaction();
+    tx.record_inverse();
+    tx.put(ROOT, "title", "final")?;
+    tx.put(ROOT, "added", true)?;
+    tx.delete(&list, 1)?;
+    tx.splice(&list, 0, 1, vec!["a".into(), "b".into()])?;
+    tx.splice_text(&text, 0, 5, "goodbye")?;
+    tx.increment(ROOT, "counter", 5)?;
+    let undo = tx.inverse();
+    tx.commit();
+    assert_eq!(undo.len(), 6);
+    assert_eq!(
+        undo[5],
+        InverseOp::Put {
+            obj: ROOT,
+            prop: "title".into(),
+            value: "draft".into(),
+        }
+    );
+
+    let mut tx = doc.transaction();
+    for op in &undo {
+        op.apply(&mut tx)?;
+    }
+    tx.commit();
+    assert_eq!(doc.get(ROOT, "title")?.unwrap().0, Value::str("draft"));
+    assert_eq!(doc.get(ROOT, "added")?, None);
+    assert_eq!(
+        doc.list_range(&list, ..)
+            .map(|(_, v, _)| v.into_owned())
+            .collect::<Vec<_>>(),
+        doc.list_range_at(&list, .., &before)
+            .map(|(_, v, _)| v.into_owned())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(doc.text(&text)?, "hello world");
+    assert_eq!(doc.get(ROOT, "counter")?.unwrap().0, Value::counter(1));
+    Ok(())
+}
+
+#[test]
+fn inverse_is_empty_unless_recording() -> Result<(), AutomergeError> {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "a", 1)?;
+    assert!(tx.inverse().is_empty());
+    tx.record_inverse();
+    tx.put(ROOT, "a", 2)?;
+    tx.delete(ROOT, "missing")?;
+    assert_eq!(
+        tx.inverse(),
+        vec![InverseOp::Put {
+            obj: ROOT,
+            prop: "a".into(),
+            value: 1.into(),
+        }]
+    );
+    Ok(())
+}
+
+#[test]
+fn inverse_of_an_oversized_splice() -> Result<(), AutomergeError> {
+    let mut doc = Automerge::new().with_encoding(TextEncoding::Utf16);
+    let mut tx = doc.transaction();
+    let list = tx.put_object(ROOT, "list", ObjType::List)?;
+    tx.splice(&list, 0, 0, vec![1.into(), 2.into(), 3.into()])?;
+    let text = tx.put_object(ROOT, "text", ObjType::Text)?;
+    tx.splice_text(&text, 0, 0, "h\u{1f600}llo")?;
+    tx.commit();
+
+    let mut tx = doc.transaction();
+    tx.record_inverse();
+    tx.splice(&list, 1, usize::MAX, vec![])?;
+    // starts inside the surrogate pair of "\u{1f600}"
+    tx.splice_text(&text, 2, usize::MAX, "")?;
+    assert_eq!(tx.length(&list), 1);
+    let undo = tx.inverse();
+    tx.commit();
+
+    let mut tx = doc.transaction();
+    for op in &undo {
+        op.apply(&mut tx)?;
+    }
+    tx.commit();
+    assert_eq!(
+        doc.list_range(&list, ..)
+            .map(|(_, v, _)| v.into_owned())
+            .collect::<Vec<_>>(),
+        vec![Value::from(1), Value::from(2), Value::from(3)]
+    );
+    assert_eq!(doc.text(&text)?, "h\u{1f600}llo");
+    Ok(())
+}
+
+#[test]
+fn merge_strategies_settle_conflicts_the_same_way_on_every_replica() -> Result<(), AutomergeError> {
+    let mut strategies = MergeStrategies::new();
+    strategies
+        .register(ObjType::Map, "version", MergeStrategy::Max)
+        .register(ObjType::Map, "*_low", MergeStrategy::Min);
+
+    let mut doc1 = Automerge::new();
+    let mut tx = doc1.transaction();
+    tx.put(ROOT, "version", 1)?;
+    tx.put(ROOT, "temp_low", 10)?;
+    tx.commit();
+    let mut doc2 = doc1.fork();
+
+    let mut tx = doc1.transaction();
+    tx.put(ROOT, "version", 3)?;
+    tx.put(ROOT, "temp_low", 4)?;
+    tx.put(ROOT, "title", "one")?;
+    tx.commit();
+    let mut tx = doc2.transaction();
+    tx.put(ROOT, "version", 5)?;
+    tx.put(ROOT, "temp_low", 7)?;
+    tx.put(ROOT, "title", "two")?;
+    tx.commit();
+
+    doc1.merge_with_strategies(&mut doc2, &strategies)?;
+    doc2.merge_with_strategies(&mut doc1.clone(), &strategies)?;
+    for doc in [&doc1, &doc2] {
+        assert_eq!(doc.get(ROOT, "version")?.unwrap().0, Value::int(5));
+        assert_eq!(doc.get(ROOT, "temp_low")?.unwrap().0, Value::int(4));
+        // properties without a rule keep their conflict
+        assert_eq!(doc.get_all(ROOT, "title")?.len(), 2);
+    }
+
+    // the deciding changes made on each replica agree so nothing more is written
+    doc1.merge(&mut doc2)?;
+    doc2.merge(&mut doc1)?;
+    assert_eq!(doc1.get_heads(), doc2.get_heads());
+    assert_eq!(doc1.apply_merge_strategies(&strategies), None);
+    assert_eq!(doc1.get(ROOT, "version")?.unwrap().0, Value::int(5));
+    assert_eq!(doc1.get(ROOT, "temp_low")?.unwrap().0, Value::int(4));
+    Ok(())
+}
+
+#[test]
+fn changes_where_filters_on_metadata() -> Result<(), AutomergeError> {
+    let alice = ActorId::from(b"alice".to_vec());
+    let bob = ActorId::from(b"bob".to_vec());
+    let mut doc = AutoCommit::new().with_actor(alice.clone());
+    let mut other = doc.fork().with_actor(bob.clone());
+    let mut hashes = Vec::new();
+    for (i, time) in [100, 200, 300].iter().enumerate() {
+        doc.put(ROOT, "a", i as i64)?;
+        hashes.push(
+            doc.commit_with(CommitOptions::default().with_time(*time))
+                .unwrap(),
+        );
+    }
+    other.put(ROOT, "b", true)?;
+    let bobs = other
+        .commit_with(CommitOptions::default().with_time(250))
+        .unwrap();
+    doc.merge(&mut other)?;
+
+    let by_actor = |doc: &mut AutoCommit, actor: &ActorId| {
+        doc.changes_where(|meta| meta.actor == actor)
+            .iter()
+            .map(|c| c.hash())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(by_actor(&mut doc, &alice), hashes);
+    assert_eq!(by_actor(&mut doc, &bob), vec![bobs]);
+
+    let in_range = doc
+        .changes_where(|meta| (200..=250).contains(&meta.timestamp))
+        .iter()
+        .map(|c| c.hash())
+        .collect::<Vec<_>>();
+    assert_eq!(in_range, vec![hashes[1], bobs]);
+    Ok(())
+}
+
+#[test]
+fn marks_by_name_groups_marks() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "the quick brown fox")?;
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 0, 5),
+        ExpandMark::None,
+    )?;
+    doc.mark(
+        &text,
+        Mark::new("link".to_string(), "a", 2, 7),
+        ExpandMark::None,
+    )?;
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), false, 3, 8),
+        ExpandMark::None,
+    )?;
+    doc.mark(
+        &text,
+        Mark::new("link".to_string(), "b", 10, 12),
+        ExpandMark::None,
+    )?;
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 15, 19),
+        ExpandMark::None,
+    )?;
+
+    let by_name = doc.marks_by_name(&text)?;
+    assert_eq!(by_name.keys().collect::<Vec<_>>(), vec!["bold", "link"]);
+    // the later bold mark wins where the two overlap
+    assert_eq!(
+        by_name["bold"],
+        vec![
+            (0, 3, ScalarValue::Boolean(true)),
+            (3, 8, ScalarValue::Boolean(false)),
+            (15, 19, ScalarValue::Boolean(true)),
+        ]
+    );
+    assert_eq!(
+        by_name["link"],
+        vec![
+            (2, 7, ScalarValue::from("a")),
+            (10, 12, ScalarValue::from("b"))
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn put_if_changed_skips_writing_the_same_value() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "name", "alice")?;
+    doc.put(ROOT, "age", 30)?;
+    doc.commit();
+    let heads = doc.get_heads();
+
+    assert!(!doc.put_if_changed(ROOT, "name", "alice")?);
+    assert!(!doc.put_if_changed(ROOT, "age", 30)?);
+    assert_eq!(doc.commit(), None);
+    assert_eq!(doc.get_heads(), heads);
+
+    // a value of a different type is a change
+    assert!(doc.put_if_changed(ROOT, "age", 30_u64)?);
+    assert!(doc.put_if_changed(ROOT, "new", true)?);
+    assert!(doc.commit().is_some());
+    assert_eq!(doc.get(ROOT, "age")?.unwrap().0, Value::uint(30));
+    Ok(())
+}
+
+#[test]
+fn load_with_progress_reports_ops_and_changes() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, &"a".repeat(20_000))?;
+    let mut saved = doc.save();
+    for i in 0..1200 {
+        doc.put(ROOT, "n", i)?;
+        doc.commit();
+    }
+    saved.extend(doc.save_incremental());
+
+    let mut reports = Vec::new();
+    let loaded = Automerge::load_with_progress(&saved, |p| reports.push(p))?;
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+
+    let total_bytes = saved.len();
+    let ops = reports.iter().map(|p| p.ops).collect::<Vec<_>>();
+    // the ops reported while decoding, then the document chunk is done
+    assert_eq!(ops[0], 16384);
+    assert!(ops[1..].iter().all(|n| *n == 20_001));
+    let changes = reports[1..].iter().map(|p| p.changes).collect::<Vec<_>>();
+    assert_eq!(changes, vec![1, 1001, 1201, 1201]);
+    assert!(reports[..reports.len() - 1]
+        .iter()
+        .all(|p| p.bytes < total_bytes && p.total_bytes == total_bytes));
+    assert_eq!(reports.last().unwrap().bytes, total_bytes);
+    Ok(())
+}
+
+#[test]
+fn value_by_actor_reads_each_side_of_a_conflict() -> Result<(), AutomergeError> {
+    let alice = ActorId::from(b"alice".to_vec());
+    let bob = ActorId::from(b"bob".to_vec());
+    let carol = ActorId::from(b"carol".to_vec());
+    let mut doc1 = AutoCommit::new().with_actor(alice.clone());
+    doc1.put(ROOT, "title", "original")?;
+    doc1.commit();
+    let mut doc2 = doc1.fork().with_actor(bob.clone());
+    doc1.put(ROOT, "title", "alice's")?;
+    doc2.put(ROOT, "title", "bob's")?;
+    doc1.merge(&mut doc2)?;
+    assert_eq!(doc1.get_all(ROOT, "title")?.len(), 2);
+
+    let (value, id) = doc1.value_by_actor(ROOT, "title", &alice)?.unwrap();
+    assert_eq!(value, Value::str("alice's"));
+    assert!(matches!(id, ObjId::Id(_, actor, _) if actor == alice));
+    let (value, _) = doc1.value_by_actor(ROOT, "title", &bob)?.unwrap();
+    assert_eq!(value, Value::str("bob's"));
+    assert_eq!(doc1.value_by_actor(ROOT, "title", &carol)?, None);
+    assert_eq!(doc1.value_by_actor(ROOT, "missing", &alice)?, None);
+    Ok(())
+}
+
+#[test]
+fn splice_from_converts_the_values() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+    doc.splice_from(&list, 0, 0, vec![1_i64, 2, 3, 4])?;
+    let result = doc.splice_from(&list, 1, 2, vec!["two".to_string(), "three".to_string()])?;
+    assert_eq!(result.removed, 2);
+    assert_eq!(result.inserted, 2);
+    let values = doc
+        .list_range(&list, ..)
+        .map(|(_, v, _)| v.into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec![
+            Value::int(1),
+            Value::str("two"),
+            Value::str("three"),
+            Value::int(4)
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn replay_patches_rebuild_the_document() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "title", "shopping")?;
+    let items = doc.put_object(ROOT, "items", ObjType::List)?;
+    doc.insert(&items, 0, "eggs")?;
+    let item = doc.insert_object(&items, 1, ObjType::Map)?;
+    doc.put(&item, "name", "milk")?;
+    doc.put(&item, "count", 2)?;
+    doc.delete(&items, 0)?;
+    let notes = doc.put_object(ROOT, "notes", ObjType::Text)?;
+    doc.splice_text(&notes, 0, 0, "buy soon")?;
+    doc.put(ROOT, "title", "groceries")?;
+    doc.commit();
+
+    let mut rebuilt = AutoCommit::new();
+    let mut objs = std::collections::HashMap::new();
+    objs.insert(ROOT, ROOT);
+    let put = |rebuilt: &mut AutoCommit,
+               objs: &mut std::collections::HashMap<ObjId, ObjId>,
+               obj: &ObjId,
+               prop: Prop,
+               insert: bool,
+               value: &(Value<'static>, ObjId)|
+     -> Result<(), AutomergeError> {
+        match (&value.0, prop) {
+            (Value::Object(t), Prop::Map(key)) => {
+                objs.insert(value.1.clone(), rebuilt.put_object(obj, key, *t)?);
+            }
+            (Value::Object(t), Prop::Seq(i)) if insert => {
+                objs.insert(value.1.clone(), rebuilt.insert_object(obj, i, *t)?);
+            }
+            (Value::Scalar(v), Prop::Map(key)) => rebuilt.put(obj, key, v.as_ref().clone())?,
+            (Value::Scalar(v), Prop::Seq(i)) if insert => {
+                rebuilt.insert(obj, i, v.as_ref().clone())?
+            }
+            (value, prop) => panic!("unexpected put of {:?} at {:?}", value, prop),
+        }
+        Ok(())
+    };
+    for patch in doc.replay_patches() {
+        let obj = objs[&patch.obj].clone();
+        match patch.action {
+            PatchAction::PutMap { key, value, .. } => {
+                put(&mut rebuilt, &mut objs, &obj, Prop::Map(key), false, &value)?
+            }
+            PatchAction::Insert { index, values, .. } => {
+                for (i, value) in values.iter().enumerate() {
+                    put(
+                        &mut rebuilt,
+                        &mut objs,
+                        &obj,
+                        Prop::Seq(index + i),
+                        true,
+                        value,
+                    )?;
+                }
+            }
+            PatchAction::SpliceText { index, value } => {
+                rebuilt.splice_text(&obj, index, 0, &value.iter().collect::<String>())?;
+            }
+            other => panic!("unexpected patch {:?}", other),
+        }
+    }
+
+    assert_eq!(rebuilt.state_hash(), doc.state_hash());
+    assert_eq!(rebuilt.text(&objs[&notes])?, "buy soon");
+    Ok(())
+}
+
+#[test]
+fn has_mark_looks_for_a_mark_by_name() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "the quick brown fox")?;
+    assert!(!doc.has_mark(&text, "link")?);
+
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 0, 3),
+        ExpandMark::None,
+    )?;
+    doc.mark(
+        &text,
+        Mark::new("link".to_string(), "x", 4, 9),
+        ExpandMark::None,
+    )?;
+    assert!(doc.has_mark(&text, "bold")?);
+    assert!(doc.has_mark(&text, "link")?);
+    assert!(!doc.has_mark(&text, "italic")?);
+
+    // a mark which has been removed again no longer counts
+    doc.unmark(&text, "link", 0, 19, ExpandMark::None)?;
+    assert!(!doc.has_mark(&text, "link")?);
+    assert!(doc.has_mark(&text, "bold")?);
+    Ok(())
+}
+
+#[test]
+fn unmark_all_removes_every_span_of_a_mark() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "the quick brown fox")?;
+    for (start, end) in [(0, 3), (4, 9), (16, 19)] {
+        doc.mark(
+            &text,
+            Mark::new("bold".to_string(), true, start, end),
+            ExpandMark::After,
+        )?;
+    }
+    doc.mark(
+        &text,
+        Mark::new("link".to_string(), "x", 2, 6),
+        ExpandMark::None,
+    )?;
+
+    let covers_text = |doc: &AutoCommit| -> Result<bool, AutomergeError> {
+        Ok(doc
+            .marks(&text)?
+            .iter()
+            .any(|m| m.name() == "bold" && m.start < m.end))
+    };
+
+    assert_eq!(doc.unmark_all(&text, "bold")?, 3);
+    assert!(!covers_text(&doc)?);
+    // the spans left behind cover nothing
+    assert!(!doc.has_mark(&text, "bold")?);
+    assert_eq!(
+        doc.marks_by_name(&text)?["link"],
+        vec![(2, 6, ScalarValue::from("x"))]
+    );
+    // typing after a removed span doesn't bring the mark back
+    doc.splice_text(&text, 19, 0, "!")?;
+    assert!(!covers_text(&doc)?);
+    assert_eq!(doc.unmark_all(&text, "bold")?, 0);
+    Ok(())
+}
+
+#[test]
+fn heads_borrows_the_sorted_heads() {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(&ROOT, "a", 1).unwrap();
+    let mut doc2 = doc1.fork();
+    let mut doc3 = doc1.fork();
+    doc2.put(&ROOT, "b", 2).unwrap();
+    doc3.put(&ROOT, "c", 3).unwrap();
+    doc1.put(&ROOT, "d", 4).unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    doc1.merge(&mut doc3).unwrap();
+
+    let heads = doc1.get_heads();
+    assert_eq!(heads.len(), 3);
+    assert_eq!(doc1.heads(), heads.as_slice());
+    assert!(doc1.heads().windows(2).all(|w| w[0] < w[1]));
+
+    // an uncommitted change is committed first
+    doc1.put(&ROOT, "e", 5).unwrap();
+    let heads = doc1.document().get_heads();
+    assert_eq!(doc1.heads(), heads.as_slice());
+    assert_eq!(heads.len(), 1);
+}
+
+#[test]
+fn subtree_stats_counts_one_subtree() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    doc.put(&ROOT, "ignored", "x".repeat(100))?;
+    let notes = doc.put_object(&ROOT, "notes", ObjType::Map)?;
+    let tags = doc.put_object(&notes, "tags", ObjType::List)?;
+    for (i, tag) in ["red", "green", "blue"].iter().enumerate() {
+        doc.insert(&tags, i, *tag)?;
+    }
+    let body = doc.put_object(&notes, "body", ObjType::Text)?;
+    doc.splice_text(&body, 0, 0, "hello")?;
+    doc.put(&notes, "count", 3)?;
+    doc.put(&notes, "done", false)?;
+
+    let stats = doc.subtree_stats(&notes)?;
+    // tags, its three elements, body, its five characters, count and done
+    assert_eq!(stats.elements, 12);
+    assert_eq!(stats.objects, 2);
+    assert_eq!(
+        stats.bytes,
+        "tags".len()
+            + "redgreenblue".len()
+            + "body".len()
+            + "hello".len()
+            + "count".len()
+            + 8
+            + "done".len()
+            + 1
+    );
+    assert!(doc.subtree_stats(&ROOT)?.bytes > stats.bytes + 100);
+    Ok(())
+}
+
+#[test]
+fn subtree_stats_estimates_the_materialized_size() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let people = doc.put_object(&ROOT, "people", ObjType::List)?;
+    for i in 0..50 {
+        let person = doc.insert_object(&people, i, ObjType::Map)?;
+        doc.put(&person, "name", format!("person number {}", i))?;
+        doc.put(&person, "email", format!("person{}@example.com", i))?;
+        doc.put(&person, "age", 20 + i as i64)?;
+    }
+
+    let stats = doc.subtree_stats(&ROOT)?;
+    assert_eq!(stats.objects, 51);
+    assert_eq!(stats.elements, 51 + 150);
+    // the estimate leaves out the quotes, braces and commas of the JSON
+    let json = serde_json::to_string(&automerge::AutoSerde::from(&doc)).unwrap();
+    assert!(stats.bytes <= json.len());
+    assert!(stats.bytes * 2 >= json.len());
+    Ok(())
+}
+
+#[test]
+fn flatten_keeps_list_marks_and_how_marks_expand() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "list", ObjType::List)?;
+    doc.splice(&list, 0, 0, (0..6).map(ScalarValue::from))?;
+    doc.mark(
+        &list,
+        Mark::new("selected".to_string(), true, 1, 3),
+        ExpandMark::None,
+    )?;
+    let text = doc.put_object(ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "hello world")?;
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 0, 5),
+        ExpandMark::Before,
+    )?;
+    doc.mark(
+        &text,
+        Mark::new("link".to_string(), "x", 6, 11),
+        ExpandMark::Both,
+    )?;
+
+    let mut flat = doc.fork();
+    flat.flatten()?;
+    let (_, flat_list) = flat.get(ROOT, "list")?.unwrap();
+    let (_, flat_text) = flat.get(ROOT, "text")?.unwrap();
+    assert_eq!(flat.marks(&flat_list)?, doc.marks(&list)?);
+    assert_eq!(flat.marks(&flat_text)?, doc.marks(&text)?);
+
+    // inserting at the ends of each mark gives the same marks as in the original
+    for (pos, count) in [(1, 1), (3, 1)] {
+        let mut original = doc.fork();
+        let mut flattened = flat.fork();
+        original.splice(&list, pos, 0, vec![ScalarValue::from(-1); count])?;
+        flattened.splice(&flat_list, pos, 0, vec![ScalarValue::from(-1); count])?;
+        assert_eq!(flattened.marks(&flat_list)?, original.marks(&list)?);
+    }
+    for pos in [0, 5, 6, 11] {
+        let mut original = doc.fork();
+        let mut flattened = flat.fork();
+        original.splice_text(&text, pos, 0, "-")?;
+        flattened.splice_text(&flat_text, pos, 0, "-")?;
+        assert_eq!(
+            flattened.marks(&flat_text)?,
+            original.marks(&text)?,
+            "insert at {}",
+            pos
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn marks_in_range_matches_clipping_every_mark() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "the quick brown fox jumps")?;
+    let mut other = doc.fork();
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 0, 12),
+        ExpandMark::After,
+    )?;
+    doc.unmark(&text, "bold", 4, 6, ExpandMark::None)?;
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 12, 16),
+        ExpandMark::Both,
+    )?;
+    doc.mark(
+        &text,
+        Mark::new("link".to_string(), "a", 2, 20),
+        ExpandMark::None,
+    )?;
+    other.mark(
+        &text,
+        Mark::new("link".to_string(), "b", 8, 25),
+        ExpandMark::Both,
+    )?;
+    doc.merge(&mut other)?;
+
+    let marks = doc.marks(&text)?;
+    let len = doc.length(&text);
+    for start in 0..=len {
+        for end in start..=len {
+            let mut expected = marks
+                .iter()
+                .filter(|m| m.start < end && start < m.end)
+                .map(|m| {
+                    let mut m = m.clone().into_owned();
+                    m.start = m.start.max(start);
+                    m.end = m.end.min(end);
+                    m
+                })
+                .collect::<Vec<_>>();
+            expected.sort_by(|a, b| (a.start, a.end, a.name()).cmp(&(b.start, b.end, b.name())));
+            assert_eq!(
+                doc.marks_in_range(&text, start, end)?,
+                expected,
+                "{}..{}",
+                start,
+                end
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn save_canonical_ignores_actors_which_made_no_changes() {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(&ROOT, "key", "value").unwrap();
+    doc1.commit();
+    let mut doc2 = Automerge::load(&doc1.save()).unwrap();
+    // committing an empty transaction caches the actor of doc2 without making a change
+    assert_eq!(doc2.transaction().commit(), None);
+
+    assert_eq!(doc2.get_heads(), doc1.get_heads());
+    assert_eq!(doc2.save_canonical(), doc1.save_canonical());
+    assert_eq!(
+        Automerge::load(&doc2.save_canonical()).unwrap().get_heads(),
+        doc1.get_heads()
+    );
+}